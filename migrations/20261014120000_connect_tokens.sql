@@ -0,0 +1,9 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `connect_tokens` (
+	`id` int AUTO_INCREMENT PRIMARY KEY,
+	`user_id` varchar(32) NOT NULL,
+	`server_addr` varchar(64) NOT NULL,
+	`token` varchar(32) NOT NULL,
+	`expires_at` timestamp NOT NULL,
+	`revoked` boolean NOT NULL DEFAULT false
+);
\ No newline at end of file
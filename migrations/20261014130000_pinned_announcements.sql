@@ -0,0 +1,5 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `pinned_announcements` (
+	`channel_id` varchar(32) PRIMARY KEY,
+	`message_id` varchar(32) NOT NULL
+);
\ No newline at end of file
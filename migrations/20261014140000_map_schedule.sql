@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `map_schedule` (
+	`server_addr` varchar(64) NOT NULL,
+	`hour` int NOT NULL,
+	`map` varchar(128) NOT NULL,
+	PRIMARY KEY (`server_addr`, `hour`)
+);
\ No newline at end of file
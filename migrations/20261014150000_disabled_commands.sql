@@ -0,0 +1,4 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `disabled_commands` (
+	`name` varchar(64) PRIMARY KEY
+);
\ No newline at end of file
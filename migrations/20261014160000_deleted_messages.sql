@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `deleted_messages` (
+	`id` bigint unsigned NOT NULL AUTO_INCREMENT,
+	`author_id` varchar(32) NOT NULL,
+	`channel_id` varchar(32) NOT NULL,
+	`content` text NOT NULL,
+	`deleted_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (`id`),
+	KEY `author_id` (`author_id`)
+);
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `rcon_aliases` (
+	`name` varchar(64) NOT NULL,
+	`command` text NOT NULL,
+	`created_by` varchar(32) NOT NULL,
+	PRIMARY KEY (`name`)
+);
\ No newline at end of file
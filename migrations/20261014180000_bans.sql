@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `bans` (
+	`id` bigint unsigned NOT NULL AUTO_INCREMENT,
+	`target` varchar(128) NOT NULL,
+	`steamid` varchar(32),
+	`server_addr` varchar(64),
+	`issued_by` varchar(32) NOT NULL,
+	`reason` varchar(255) NOT NULL,
+	`created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`expires_at` timestamp NULL,
+	`lifted` boolean NOT NULL DEFAULT false,
+	PRIMARY KEY (`id`),
+	KEY `steamid` (`steamid`)
+);
\ No newline at end of file
@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `warnings` (
+	`id` bigint unsigned NOT NULL AUTO_INCREMENT,
+	`steamid` varchar(32) NOT NULL,
+	`reason` varchar(255) NOT NULL,
+	`issued_by` varchar(32) NOT NULL,
+	`created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (`id`),
+	KEY `steamid` (`steamid`)
+);
\ No newline at end of file
@@ -0,0 +1,14 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `sessions` (
+	`id` bigint unsigned NOT NULL AUTO_INCREMENT,
+	`steamid` varchar(32) NOT NULL,
+	`name` varchar(128) NOT NULL,
+	`server_addr` varchar(64) NOT NULL,
+	`started_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`ended_at` timestamp NULL DEFAULT NULL,
+	`seconds` int DEFAULT NULL,
+	`orphaned` boolean NOT NULL DEFAULT false,
+	PRIMARY KEY (`id`),
+	KEY `steamid` (`steamid`),
+	KEY `name` (`name`)
+);
\ No newline at end of file
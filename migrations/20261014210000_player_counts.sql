@@ -0,0 +1,10 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `player_counts` (
+	`id` bigint unsigned NOT NULL AUTO_INCREMENT,
+	`server_addr` varchar(64) NOT NULL,
+	`players` int NOT NULL,
+	`max_players` int NOT NULL,
+	`recorded_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (`id`),
+	KEY `server_recorded` (`server_addr`, `recorded_at`)
+);
\ No newline at end of file
@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `seeder_cooldowns` (
+	`server_addr` varchar(64) NOT NULL,
+	`seeded_at` timestamp NOT NULL,
+	PRIMARY KEY (`server_addr`)
+);
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `cooldown_messages` (
+	`message_id` varchar(32) NOT NULL,
+	`channel_id` varchar(32) NOT NULL,
+	`user_id` varchar(32) NOT NULL,
+	`delete_at` timestamp NOT NULL,
+	PRIMARY KEY (`message_id`)
+);
\ No newline at end of file
@@ -0,0 +1,8 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `steam_links` (
+	`user_id` varchar(32) NOT NULL,
+	`steamid` varchar(32) NOT NULL,
+	`linked_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (`user_id`),
+	UNIQUE KEY `steamid` (`steamid`)
+);
\ No newline at end of file
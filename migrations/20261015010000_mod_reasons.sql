@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `mod_reasons` (
+	`reason` varchar(255) NOT NULL,
+	PRIMARY KEY (`reason`)
+);
+INSERT IGNORE INTO `mod_reasons` (`reason`) VALUES ('cheating'), ('mic spam'), ('toxicity');
\ No newline at end of file
@@ -0,0 +1,13 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `mod_actions` (
+	`id` bigint unsigned NOT NULL AUTO_INCREMENT,
+	`kind` varchar(32) NOT NULL,
+	`steamid` varchar(32) NOT NULL,
+	`server_addr` varchar(64),
+	`issued_by` varchar(32) NOT NULL,
+	`reason` varchar(255) NOT NULL,
+	`minutes` int unsigned,
+	`created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (`id`),
+	KEY `steamid` (`steamid`)
+);
\ No newline at end of file
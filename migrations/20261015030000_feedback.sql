@@ -0,0 +1,12 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `feedback` (
+	`id` bigint unsigned NOT NULL AUTO_INCREMENT,
+	`author_id` varchar(32) NOT NULL,
+	`message` text NOT NULL,
+	`status` varchar(16) NOT NULL DEFAULT 'open',
+	`resolved_by` varchar(32),
+	`created_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	`resolved_at` timestamp NULL,
+	PRIMARY KEY (`id`),
+	KEY `status` (`status`)
+);
\ No newline at end of file
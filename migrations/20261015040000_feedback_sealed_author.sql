@@ -0,0 +1,6 @@
+-- Add migration script here
+-- authors are sealed with FEEDBACK_KEY from now on. the bot seals the plain ids at startup
+-- and drops `author_id` once none are left
+ALTER TABLE `feedback`
+	MODIFY COLUMN `author_id` varchar(32) NULL,
+	ADD COLUMN `sealed_author` varchar(128) AFTER `id`;
\ No newline at end of file
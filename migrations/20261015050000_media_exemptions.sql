@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `media_exemptions` (
+	`user_id` varchar(64) NOT NULL,
+	`until` timestamp NULL,
+	PRIMARY KEY (`user_id`)
+);
\ No newline at end of file
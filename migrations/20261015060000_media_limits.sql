@@ -0,0 +1,7 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `media_limits` (
+	`channel_id` varchar(32) NOT NULL,
+	`posts` int unsigned NULL,
+	`minutes` int unsigned NULL,
+	PRIMARY KEY (`channel_id`)
+);
\ No newline at end of file
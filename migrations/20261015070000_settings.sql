@@ -0,0 +1,6 @@
+-- Add migration script here
+CREATE TABLE IF NOT EXISTS `settings` (
+	`name` varchar(64) NOT NULL,
+	`value` varchar(255) NOT NULL,
+	PRIMARY KEY (`name`)
+);
\ No newline at end of file
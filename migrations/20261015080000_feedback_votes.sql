@@ -0,0 +1,6 @@
+-- Add migration script here
+-- feedback posted to FEEDBACK_CHANNEL_ID is voted on with reactions
+ALTER TABLE `feedback`
+	ADD COLUMN `message_id` varchar(32) AFTER `message`,
+	ADD COLUMN `score` int NOT NULL DEFAULT 0 AFTER `message_id`,
+	ADD KEY `message_id` (`message_id`);
\ No newline at end of file
@@ -0,0 +1,7 @@
+-- Add migration script here
+-- young accounts whose first message the new account gate already saw
+CREATE TABLE IF NOT EXISTS `new_account_seen` (
+	`user_id` varchar(32) NOT NULL,
+	`seen_at` timestamp NOT NULL DEFAULT CURRENT_TIMESTAMP,
+	PRIMARY KEY (`user_id`)
+);
\ No newline at end of file
@@ -0,0 +1,134 @@
+use std::collections::BTreeMap;
+use std::net::SocketAddr;
+use std::time::Duration;
+
+use tokio::net::UdpSocket;
+use tokio::time::timeout;
+
+use crate::Error;
+
+/// how long to wait for each packet from the server
+const QUERY_TIMEOUT: Duration = Duration::from_secs(3);
+
+const SINGLE_PACKET: i32 = -1;
+const MULTI_PACKET: i32 = -2;
+const A2S_RULES: u8 = 0x56;
+const S2C_CHALLENGE: u8 = 0x41;
+const A2S_RULES_RESPONSE: u8 = 0x45;
+
+/// reads little endian values and null terminated strings off a packet
+struct Reader<'a> {
+    buf: &'a [u8],
+}
+
+impl<'a> Reader<'a> {
+    fn bytes(&mut self, n: usize) -> Result<&'a [u8], Error> {
+        if self.buf.len() < n {
+            return Err("A2S response ended early".into());
+        }
+        let (head, rest) = self.buf.split_at(n);
+        self.buf = rest;
+        Ok(head)
+    }
+
+    fn u8(&mut self) -> Result<u8, Error> {
+        Ok(self.bytes(1)?[0])
+    }
+
+    fn i16(&mut self) -> Result<i16, Error> {
+        Ok(i16::from_le_bytes(self.bytes(2)?.try_into()?))
+    }
+
+    fn i32(&mut self) -> Result<i32, Error> {
+        Ok(i32::from_le_bytes(self.bytes(4)?.try_into()?))
+    }
+
+    fn string(&mut self) -> Result<String, Error> {
+        let Some(end) = self.buf.iter().position(|b| *b == 0) else {
+            return Err("A2S string is not terminated".into());
+        };
+        let s = String::from_utf8_lossy(&self.buf[..end]).into_owned();
+        self.buf = &self.buf[end + 1..];
+        Ok(s)
+    }
+}
+
+async fn recv(socket: &UdpSocket) -> Result<Vec<u8>, Error> {
+    let mut buf = vec![0; 1400];
+    let len = timeout(QUERY_TIMEOUT, socket.recv(&mut buf))
+        .await
+        .map_err(|_| "A2S query timed out")??;
+    buf.truncate(len);
+    Ok(buf)
+}
+
+/// receives a full response, stitching split packets back together
+async fn recv_response(socket: &UdpSocket) -> Result<Vec<u8>, Error> {
+    let first = recv(socket).await?;
+    let mut r = Reader { buf: &first };
+    match r.i32()? {
+        SINGLE_PACKET => Ok(r.buf.to_vec()),
+        MULTI_PACKET => {
+            let mut parts: BTreeMap<u8, Vec<u8>> = BTreeMap::new();
+            let id = r.i32()?;
+            if id as u32 & 0x8000_0000 != 0 {
+                return Err("Compressed A2S responses are not supported".into());
+            }
+            let total = r.u8()?;
+            let mut packet = first.clone();
+            loop {
+                let mut r = Reader { buf: &packet };
+                r.i32()?;
+                if r.i32()? == id {
+                    let number = r.u8()?;
+                    // source engine packets carry a max size we don't need
+                    r.i16()?;
+                    parts.insert(number, r.buf.to_vec());
+                }
+                if parts.len() >= total as usize {
+                    break;
+                }
+                packet = recv(socket).await?;
+            }
+            let payload: Vec<u8> = parts.into_values().flatten().collect();
+            let mut r = Reader { buf: &payload };
+            if r.i32()? != SINGLE_PACKET {
+                return Err("Malformed split A2S response".into());
+            }
+            Ok(r.buf.to_vec())
+        }
+        _ => Err("Unknown A2S packet header".into()),
+    }
+}
+
+/// fetches every rule (cvar) the server exposes through A2S_RULES, sorted by name
+pub async fn query_rules(addr: SocketAddr) -> Result<Vec<(String, String)>, Error> {
+    let socket = UdpSocket::bind("0.0.0.0:0").await?;
+    socket.connect(addr).await?;
+
+    let mut challenge: i32 = -1;
+    // the first request only gets us a challenge number to send back
+    for _ in 0..3 {
+        let mut request = SINGLE_PACKET.to_le_bytes().to_vec();
+        request.push(A2S_RULES);
+        request.extend(challenge.to_le_bytes());
+        socket.send(&request).await?;
+
+        let response = recv_response(&socket).await?;
+        let mut r = Reader { buf: &response };
+        match r.u8()? {
+            S2C_CHALLENGE => challenge = r.i32()?,
+            A2S_RULES_RESPONSE => {
+                let count = r.i16()?;
+                let mut rules = Vec::with_capacity(count.max(0) as usize);
+                for _ in 0..count {
+                    rules.push((r.string()?, r.string()?));
+                }
+                rules.sort();
+                return Ok(rules);
+            }
+            _ => return Err("Unexpected A2S response".into()),
+        }
+    }
+    Err("Server kept sending challenges".into())
+}
@@ -1,24 +1,51 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
 use std::net::SocketAddr;
+use std::process::ExitCode;
+use std::sync::atomic::{AtomicBool, Ordering};
 use std::sync::Arc;
 
+use crate::metrics::{self, metrics};
 use crate::steamid::SteamIDClient;
 use crate::{logs::LogReceiver, Error};
-use crate::{parse_env, Server};
+use crate::{parse_env, parse_env_opt, Server};
 use chrono::{DateTime, Duration, Utc};
 use poise::serenity_prelude::{self as serenity};
 
-use rand::random;
 use sqlx::{MySql, Pool};
-use tokio::sync::mpsc::error::TryRecvError;
 use tokio::sync::mpsc::Sender;
 use tokio::sync::OnceCell;
 use tokio::{self, sync::RwLock};
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument};
 
+mod activity;
+mod auto_cap;
+mod bans;
+mod chat_bridge;
+mod command_cooldowns;
 mod commands;
+mod connect_tokens;
+mod deleted_messages;
+mod feedback;
+mod kill_feed;
+mod links;
 mod log_handler;
+mod map_schedule;
+mod map_vote;
 mod media_cooldown;
+mod mod_actions;
+mod new_account_gate;
 mod player_count;
+mod plugin_alerts;
+mod population;
+mod positivity;
+mod rcon_policy;
+mod sessions;
+mod settings;
+mod steam_group;
+mod warnings;
+
+pub use auto_cap::CapSchedule;
 
 pub struct PoiseData {
     pub servers: HashMap<SocketAddr, Server>,
@@ -26,16 +53,82 @@ pub struct PoiseData {
     pub guild_id: serenity::GuildId,
     /// guild the bot operates in
     pub media_cooldown: Arc<RwLock<media_cooldown::MediaCooldown>>,
-    media_cooldown_thread: OnceCell<Sender<Cooldown>>,
+    media_cooldown_thread: OnceCell<Sender<CooldownEvent>>,
     deleted_message_log_channel: serenity::ChannelId,
+    /// where edited messages are logged, if anywhere
+    edited_message_log_channel: Option<serenity::ChannelId>,
+    /// where moderation actions are recorded
+    pub mod_log_channel: Option<serenity::ChannelId>,
     pub private_channel: serenity::ChannelId,
     pub private_welcome_channel: serenity::ChannelId,
+    /// role allowed to request connect tokens for private servers
+    pub private_role: Option<serenity::RoleId>,
     pub seeder_role: serenity::RoleId,
     pub trial_mod_channel: serenity::ChannelId,
-    pub msg_counts: Arc<RwLock<HashMap<u64, u64>>>,
     pub seeder_cooldown: Arc<RwLock<HashMap<SocketAddr, DateTime<Utc>>>>,
     pub pool: Pool<MySql>,
-    pub client: SteamIDClient,
+    pub client: Arc<SteamIDClient>,
+    /// (name, addr) of every server sorted by name, built once for autocompletes
+    pub server_choices: Vec<(String, SocketAddr)>,
+    /// maps that may be added to the mapcycle. empty allows everything
+    pub allowed_maps: Vec<String>,
+    /// role that may add maps outside of allowed_maps
+    pub map_override_role: Option<serenity::RoleId>,
+    /// config exec'd by /matchsetup
+    pub match_cfg: String,
+    /// config exec'd by /matchend to go back to normal
+    pub public_cfg: String,
+    /// where /matchsetup posts connect info
+    pub match_channel: Option<serenity::ChannelId>,
+    pub match_state: Arc<RwLock<HashMap<SocketAddr, commands::MatchState>>>,
+    /// role required for mod actions. unset leaves it to discord permissions
+    pub mod_role: Option<serenity::RoleId>,
+    /// (role, max minutes) limits on timed mod actions, e.g. for trial mods
+    pub duration_caps: Vec<(serenity::RoleId, u32)>,
+    /// holds first messages from young accounts, if configured
+    pub new_account_gate: Option<new_account_gate::NewAccountGate>,
+    /// whether mods skip command cooldowns
+    pub mods_bypass_cooldowns: bool,
+    /// top level commands turned off with /togglecommand
+    pub disabled_commands: Arc<RwLock<HashSet<String>>>,
+    /// reason/duration shortcuts offered on /tf2ban
+    pub ban_presets: Vec<commands::BanPreset>,
+    /// whether banned players with a linked discord account are DMed the reason
+    pub notify_banned_users: bool,
+    /// recent in-game chat, filled in by the log thread
+    pub chat_history: Arc<RwLock<log_handler::ChatHistory>>,
+    /// kills and deaths on the current map, from the logs
+    pub map_stats: Arc<RwLock<kill_feed::MapStats>>,
+    /// sends messages from the chat relay channel into the game, if there is one
+    pub chat_bridge: Option<chat_bridge::ChatBridge>,
+    /// how long a server waits between seeder pings, changed with /seeder_cooldown
+    pub seed_cooldown: Arc<RwLock<Duration>>,
+    /// player lists kept for a few seconds for autocompletes
+    pub player_cache: Arc<RwLock<commands::util::PlayerCache>>,
+    /// who may use raw rcon and what it may run
+    pub rcon_policy: rcon_policy::RconPolicy,
+    /// installed maps per server, for /changelevel
+    pub map_cache: Arc<RwLock<commands::MapCache>>,
+    /// /nominate and /rtv votes per server, cleared by the log thread on map change
+    pub map_votes: Arc<RwLock<map_vote::MapVotes>>,
+    /// canned reasons suggested on mod commands, changed with /addreason and /rmreason
+    pub mod_reasons: Arc<RwLock<Vec<String>>>,
+    /// the trial mod channel positivity quota
+    pub positivity: Arc<RwLock<positivity::Positivity>>,
+    /// cvar sets offered on /respawntimes
+    pub respawn_presets: Vec<commands::RespawnPreset>,
+    /// servers whose cap follows their population, toggled with /playercap
+    pub auto_cap_servers: Arc<RwLock<HashSet<SocketAddr>>>,
+    /// seals feedback authors in the db, unset if FEEDBACK_KEY isn't
+    pub feedback_seal: Option<feedback::AuthorSeal>,
+    /// role allowed to use /feedback_whois
+    pub feedback_whois_role: Option<serenity::RoleId>,
+    /// gives steam group members a role, if configured
+    pub steam_group: Option<steam_group::GroupSync>,
+    /// cancelled on shutdown, background threads stop once it is
+    pub shutdown: CancellationToken,
+    /// set by /restart-bot, so start_bot exits with RESTART_EXIT_CODE once everything stopped
+    pub restart_requested: Arc<AtomicBool>,
 }
 impl PoiseData {
     /// fetch the server with the given socket address
@@ -45,94 +138,347 @@ impl PoiseData {
             .ok_or("Server not found".into())
     }
 
+    /// the role pinged when seeding a server, falling back to the global seeder role
+    pub fn seeder_role(&self, server_addr: SocketAddr) -> serenity::RoleId {
+        self.servers
+            .get(&server_addr)
+            .and_then(|s| s.seeder_role)
+            .unwrap_or(self.seeder_role)
+    }
+
     /// checks if a seeder ping is allowed. if on cooldown, returns time until usable
     pub async fn can_seed(&self, server_addr: SocketAddr) -> Result<(), Duration> {
-        // 4 hrs
-        const SEED_COOLDOWN: Duration = Duration::milliseconds(4 * 60 * 60 * 1000);
+        let seed_cooldown = *self.seed_cooldown.read().await;
+        let last_used = self.seeder_cooldown.read().await.get(&server_addr).copied();
+        seed_allowed(last_used, seed_cooldown, Utc::now())
+    }
 
-        let mut map = self.seeder_cooldown.write().await;
-        let last_used = map.entry(server_addr).or_insert(DateTime::<Utc>::MIN_UTC);
+    /// marks the server as just seeded, resetting the cooldown.
+    /// saved so a restart doesn't allow pinging again right away
+    pub async fn reset_seed_cooldown(&self, server_addr: SocketAddr) -> Result<(), Error> {
         let now = chrono::Utc::now();
+        self.seeder_cooldown.write().await.insert(server_addr, now);
 
-        let allowed_at = *last_used + SEED_COOLDOWN;
+        sqlx::query!(
+            r#"
+		INSERT INTO `seeder_cooldowns` (`server_addr`, `seeded_at`) VALUES (?, ?)
+		ON DUPLICATE KEY UPDATE `seeded_at` = VALUES(`seeded_at`)
+	"#,
+            server_addr.to_string(),
+            now
+        )
+        .execute(&self.pool)
+        .await?;
+        Ok(())
+    }
 
-        if allowed_at < now {
-            // allowed
-            Ok(())
-        } else {
-            Err(allowed_at - now)
+    /// deletes every pending cooldown message, for shutting down cleanly
+    pub async fn flush_cooldowns(&self) {
+        let Some(sender) = self.media_cooldown_thread.get() else {
+            return;
+        };
+        let (done, wait) = tokio::sync::oneshot::channel();
+        if sender.send(CooldownEvent::Flush(done)).await.is_err() || wait.await.is_err() {
+            warn!("The cooldown manager stopped before flushing its messages");
         }
     }
+}
+pub type Context<'a> = poise::Context<'a, PoiseData, Error>;
 
-    /// marks the server as just seeded, resetting the cooldown
-    pub async fn reset_seed_cooldown(&self, server_addr: SocketAddr) {
-        let mut map = self.seeder_cooldown.write().await;
-        let last_used = map.entry(server_addr).or_insert(DateTime::<Utc>::MIN_UTC);
+/// the saved (server_addr, seeded_at) rows by server, skipping addresses that don't parse
+fn load_seeder_cooldowns(
+    rows: impl IntoIterator<Item = (String, DateTime<Utc>)>,
+) -> HashMap<SocketAddr, DateTime<Utc>> {
+    rows.into_iter()
+        .filter_map(|(addr, seeded_at)| Some((addr.parse().ok()?, seeded_at)))
+        .collect()
+}
 
-        *last_used = chrono::Utc::now();
+/// whether a server last seeded at `last_used` can be pinged for again at `now`.
+/// if not, returns how long until it can
+fn seed_allowed(
+    last_used: Option<DateTime<Utc>>,
+    cooldown: Duration,
+    now: DateTime<Utc>,
+) -> Result<(), Duration> {
+    let Some(last_used) = last_used else {
+        return Ok(());
+    };
+    let allowed_at = last_used + cooldown;
+    if allowed_at <= now {
+        Ok(())
+    } else {
+        Err(allowed_at - now)
     }
 }
-pub type Context<'a> = poise::Context<'a, PoiseData, Error>;
 
 struct Cooldown {
     user: serenity::UserId,
     channel: serenity::ChannelId,
+    kind: media_cooldown::MediaKind,
     delete_at: DateTime<Utc>,
 }
 
-fn spawn_cooldown_manager(ctx: serenity::Context) -> Sender<Cooldown> {
-    let (cooldown_sender, mut cooldown_receiver) = tokio::sync::mpsc::channel::<Cooldown>(64);
+enum CooldownEvent {
+    Start(Cooldown),
+    /// delete all pending cooldown messages now, then signal back
+    Flush(tokio::sync::oneshot::Sender<()>),
+}
 
-    tokio::spawn(async move {
-        let mut queue: Vec<(Cooldown, serenity::Message)> = vec![];
+/// a cooldown message waiting to be deleted. kept in the db so a restart doesn't orphan it
+struct PendingCooldown {
+    user: serenity::UserId,
+    channel: serenity::ChannelId,
+    message: serenity::MessageId,
+    delete_at: DateTime<Utc>,
+}
+
+/// cooldown messages left behind by the last run, soonest first
+async fn pending_cooldowns(pool: &Pool<MySql>) -> Result<Vec<PendingCooldown>, Error> {
+    let rows = sqlx::query!(
+        r#"
+		SELECT `user_id`, `channel_id`, `message_id`, `delete_at` FROM `cooldown_messages`
+		ORDER BY `delete_at`
+	"#
+    )
+    .fetch_all(pool)
+    .await?;
+    Ok(rehydrate(rows.into_iter().map(|r| {
+        (r.user_id, r.channel_id, r.message_id, r.delete_at)
+    })))
+}
+
+/// the saved (user_id, channel_id, message_id, delete_at) rows as a queue, soonest first.
+/// rows with ids that don't parse are skipped
+fn rehydrate(
+    rows: impl IntoIterator<Item = (String, String, String, DateTime<Utc>)>,
+) -> Vec<PendingCooldown> {
+    let mut queue: Vec<PendingCooldown> = rows
+        .into_iter()
+        .filter_map(|(user, channel, message, delete_at)| {
+            Some(PendingCooldown {
+                user: serenity::UserId(user.parse().ok()?),
+                channel: serenity::ChannelId(channel.parse().ok()?),
+                message: serenity::MessageId(message.parse().ok()?),
+                delete_at,
+            })
+        })
+        .collect();
+    queue.sort_by_key(|p| p.delete_at);
+    queue
+}
+
+/// splits the queue into the messages due for deletion at `now` and the ones still waiting
+fn take_due(
+    queue: Vec<PendingCooldown>,
+    now: DateTime<Utc>,
+) -> (Vec<PendingCooldown>, Vec<PendingCooldown>) {
+    queue.into_iter().partition(|p| p.delete_at <= now)
+}
+
+async fn save_cooldown(pool: &Pool<MySql>, pending: &PendingCooldown) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		INSERT INTO `cooldown_messages` (`message_id`, `channel_id`, `user_id`, `delete_at`)
+		VALUES (?, ?, ?, ?)
+	"#,
+        pending.message.0.to_string(),
+        pending.channel.0.to_string(),
+        pending.user.0.to_string(),
+        pending.delete_at
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// whether discord said the thing doesn't exist (anymore)
+fn is_not_found(e: &serenity::Error) -> bool {
+    let serenity::Error::Http(e) = e else {
+        return false;
+    };
+    matches!(**e, serenity::HttpError::UnsuccessfulRequest(ref r) if r.status_code == 404)
+}
+
+/// deletes the cooldown message and forgets it. one someone already deleted by hand is fine
+async fn delete_cooldown(http: &serenity::Http, pool: &Pool<MySql>, pending: &PendingCooldown) {
+    match http
+        .delete_message(pending.channel.0, pending.message.0)
+        .await
+    {
+        Ok(()) => {}
+        Err(e) if is_not_found(&e) => {}
+        Err(e) => {
+            // kept in the db, so it's tried again after a restart
+            warn!("Could not delete cooldown message: {:?}", e);
+            return;
+        }
+    }
+    if let Err(e) = sqlx::query!(
+        r#"DELETE FROM `cooldown_messages` WHERE `message_id` = ?"#,
+        pending.message.0.to_string()
+    )
+    .execute(pool)
+    .await
+    {
+        error!("Could not forget cooldown message: {:?}", e);
+    }
+}
+
+/// cooldown messages left when it stops are in the db, the next run picks them up
+fn spawn_cooldown_manager(
+    ctx: serenity::Context,
+    pool: Pool<MySql>,
+    shutdown: CancellationToken,
+) -> Sender<CooldownEvent> {
+    let (cooldown_sender, mut cooldown_receiver) = tokio::sync::mpsc::channel::<CooldownEvent>(64);
+
+    let thread = async move {
+        let mut queue = match pending_cooldowns(&pool).await {
+            Ok(queue) => queue,
+            Err(e) => {
+                error!("Could not load pending cooldown messages: {:?}", e);
+                vec![]
+            }
+        };
         loop {
-            match cooldown_receiver.try_recv() {
-                Err(TryRecvError::Disconnected) => break,
-                Err(_) => (),
-                // when a cooldown request is received...
-                Ok(
-                    cooldown @ Cooldown {
+            metrics().cooldown_queue(queue.len());
+            // sleep until the soonest cooldown is up, or until a message comes in
+            let next_delete = queue.iter().map(|p| p.delete_at).min();
+            let due = async {
+                match next_delete {
+                    Some(at) => {
+                        tokio::time::sleep((at - Utc::now()).to_std().unwrap_or_default()).await
+                    }
+                    None => std::future::pending().await,
+                }
+            };
+            tokio::select! {
+                _ = shutdown.cancelled() => break,
+                event = cooldown_receiver.recv() => match event {
+                    None => break,
+                    Some(CooldownEvent::Flush(done)) => {
+                        for pending in queue.drain(..) {
+                            delete_cooldown(&ctx.http, &pool, &pending).await;
+                        }
+                        let _ = done.send(());
+                    }
+                    // when a cooldown request is received...
+                    Some(CooldownEvent::Start(Cooldown {
                         user,
                         channel,
+                        kind,
                         delete_at,
-                    },
-                ) if !queue
-                    .iter()
-                    .any(|(cd, _)| cd.user == user && cd.channel == channel) =>
-                {
-                    let msg_string = format!(
-                        "<@{}> guh!! >_<... post again <t:{}:R>",
-                        user.0,
-                        delete_at.timestamp()
-                    );
-                    if let Ok(msg) = ctx
-                        .http
-                        .send_message(channel.0, &serenity::json::json!({ "content": msg_string }))
-                        .await
-                    {
-                        queue.push((cooldown, msg));
+                    })) if !queue.iter().any(|p| p.user == user && p.channel == channel) => {
+                        let msg_string = format!(
+                            "<@{}> guh!! >_<... post another {} <t:{}:R>",
+                            user.0,
+                            kind,
+                            delete_at.timestamp()
+                        );
+                        match ctx
+                            .http
+                            .send_message(channel.0, &serenity::json::json!({ "content": msg_string }))
+                            .await
+                        {
+                            Ok(msg) => {
+                                let pending = PendingCooldown {
+                                    user,
+                                    channel,
+                                    message: msg.id,
+                                    delete_at,
+                                };
+                                if let Err(e) = save_cooldown(&pool, &pending).await {
+                                    error!("Could not save cooldown message: {:?}", e);
+                                }
+                                queue.push(pending);
+                            }
+                            Err(e) => warn!("Could not send cooldown message: {:?}", e),
+                        }
+                    }
+                    Some(_) => (),
+                },
+                _ = due => {
+                    // the ones that should be deleted by now
+                    let (due, waiting) = take_due(queue, Utc::now());
+                    queue = waiting;
+                    for pending in due {
+                        let http = ctx.http.clone();
+                        let pool = pool.clone();
+                        tokio::task::spawn(async move {
+                            delete_cooldown(&http, &pool, &pending).await;
+                        });
                     }
                 }
-                Ok(_) => (),
             }
-            queue.retain(|(cooldown, msg)| {
-                let http = ctx.http.clone();
-                // if it should be deleted by now
-                let delete = Utc::now() - cooldown.delete_at > Duration::zero();
-                if delete {
-                    let mid = msg.id.0;
-                    let cid = msg.channel_id.0;
-                    tokio::task::spawn(async move { http.delete_message(cid, mid).await });
-                }
-                !delete
-            });
-            tokio::task::yield_now().await;
         }
-    });
+    };
+    tokio::spawn(thread.instrument(info_span!("cooldown_manager")));
 
     cooldown_sender
 }
 
+/// logs every command that got past its checks
+async fn pre_command(ctx: Context<'_>) {
+    metrics().command(&ctx.command().qualified_name);
+    info!(
+        "{} ran /{} in {}",
+        ctx.author().tag(),
+        ctx.command().qualified_name,
+        ctx.channel_id()
+    );
+}
+
+/// logs command errors, then replies the way poise does by default
+async fn on_error(error: poise::FrameworkError<'_, PoiseData, Error>) {
+    if let poise::FrameworkError::Command { error, ctx } = &error {
+        error!("/{} failed: {:?}", ctx.command().qualified_name, error);
+    }
+    if let Err(e) = poise::builtins::on_error(error).await {
+        warn!("Could not report a command error: {:?}", e);
+    }
+}
+
+/// runs before every command. refuses disabled commands,
+/// and applies cooldowns here instead of in poise so staff can skip them
+async fn command_check(ctx: Context<'_>) -> Result<bool, Error> {
+    let root = ctx
+        .parent_commands()
+        .first()
+        .map_or(&ctx.command().name, |c| &c.name);
+    if ctx.data().disabled_commands.read().await.contains(root) {
+        ctx.send(|m| {
+            m.content("This command is disabled for maintenance, try again later :3")
+                .ephemeral(true)
+        })
+        .await?;
+        return Ok(false);
+    }
+    if commands::util::is_exempt(ctx).await {
+        return Ok(true);
+    }
+    let remaining = {
+        let mut cooldowns = ctx.command().cooldowns.lock().unwrap();
+        let remaining = cooldowns.remaining_cooldown(ctx);
+        if remaining.is_none() {
+            cooldowns.start_cooldown(ctx);
+        }
+        remaining
+    };
+    if let Some(remaining) = remaining {
+        ctx.send(|m| {
+            m.content(format!(
+                "You're too fast. Please wait {} seconds before retrying",
+                remaining.as_secs()
+            ))
+            .ephemeral(true)
+        })
+        .await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
+
 /// handle discord events
 pub async fn event_handler(
     ctx: &serenity::Context,
@@ -145,117 +491,525 @@ pub async fn event_handler(
     let cooldown_handler = {
         let ctx = ctx.clone();
         data.media_cooldown_thread
-            .get_or_init(|| async { spawn_cooldown_manager(ctx) })
+            .get_or_init(|| async {
+                spawn_cooldown_manager(ctx, data.pool.clone(), data.shutdown.clone())
+            })
             .await
     };
     match event {
         Event::Message { new_message } => {
             if let Some(_guild_id) = new_message.guild_id {
+                // throwaway account spam
+                if let Some(gate) = &data.new_account_gate {
+                    match gate.should_gate(&data.pool, new_message).await {
+                        Ok(true) => {
+                            gate.gate(ctx, new_message).await?;
+                            return Ok(());
+                        }
+                        Ok(false) => {}
+                        Err(e) => warn!("Could not check the new account gate: {:?}", e),
+                    }
+                }
+
+                if let Some(bridge) = &data.chat_bridge {
+                    if let Err(e) = bridge.relay_to_game(ctx, &data.servers, new_message).await {
+                        warn!("Could not relay a discord message to the game: {:?}", e);
+                    }
+                }
+
                 // trial mod channel positivity quota
                 if new_message.channel_id == data.trial_mod_channel {
-                    let r: f32 = random();
-                    if r < 0.1 {
-                        new_message
-                            .channel_id
-                            .send_message(ctx, |m| {
-                                m.content("keep up the good work :white_check_mark:")
-                            })
-                            .await?;
+                    let note = data
+                        .positivity
+                        .write()
+                        .await
+                        .roll(new_message.channel_id, Utc::now());
+                    if let Some(note) = note {
+                        new_message.channel_id.say(ctx, note).await?;
                     }
                 }
 
                 // media channel spam limit
                 let mut media_cooldown = data.media_cooldown.write().await;
+                // roles come from the cache so this doesn't hit the api for every message
+                let roles = new_message
+                    .guild_id
+                    .and_then(|g| {
+                        ctx.cache
+                            .member_field(g, new_message.author.id, |m| m.roles.clone())
+                    })
+                    // the gateway sends the author's roles along with the message too
+                    .or_else(|| new_message.member.as_ref().map(|m| m.roles.clone()))
+                    .unwrap_or_default();
+                let allowed = if media_cooldown.has_exempt_role(&roles) {
+                    Ok(())
+                } else {
+                    media_cooldown.try_allow_one(new_message)
+                };
                 // if we have to wait before posting an image...
-                if let Err(time_left) = media_cooldown.try_allow_one(new_message) {
+                if let Err((kind, time_left)) = allowed {
                     // delete the image
                     new_message.delete(ctx).await?;
                     // send da cooldown msg
-                    let _ = cooldown_handler
-                        .send(Cooldown {
+                    let sent = cooldown_handler
+                        .send(CooldownEvent::Start(Cooldown {
                             channel: new_message.channel_id,
                             user: new_message.author.id,
+                            kind,
                             delete_at: Utc::now() + time_left,
-                        })
+                        }))
                         .await;
+                    if sent.is_err() {
+                        warn!("Dropped a cooldown message, the cooldown manager has stopped");
+                    }
                 }
             }
         }
+        Event::InteractionCreate {
+            interaction: serenity::Interaction::MessageComponent(mci),
+        } => {
+            new_account_gate::handle_review(ctx, mci).await?;
+            map_vote::handle_vote(ctx, mci, data).await?;
+            feedback::handle_resolve(ctx, mci, data).await?;
+        }
+        Event::ReactionAdd { add_reaction } => {
+            feedback::handle_vote(ctx, add_reaction, true, data).await?
+        }
+        Event::ReactionRemove { removed_reaction } => {
+            feedback::handle_vote(ctx, removed_reaction, false, data).await?
+        }
         Event::MessageDelete {
             channel_id,
             deleted_message_id,
             ..
         } => {
-            let Some(message) = ctx.cache.message(channel_id, deleted_message_id) else {
-                return Err("Message not found in cache")?;
-            };
-            let Some(channel) = channel_id.to_channel(ctx).await?.guild() else {
-                return Err("Channel not found.")?;
+            // only the last few hundred messages are cached, older ones are logged without content
+            let message = ctx.cache.message(channel_id, deleted_message_id);
+            if let Some(message) = &message {
+                if let Err(e) = deleted_messages::record_deleted_message(&data.pool, message).await
+                {
+                    error!("Could not store deleted message: {:?}", e);
+                }
+            }
+            let attachments = match &message {
+                Some(message) => deleted_messages::save_attachments(message).await,
+                None => Default::default(),
             };
-            let _ = data
+            let channel = channel_label(ctx, *channel_id).await;
+            if let Err(e) = data
                 .deleted_message_log_channel
                 .send_message(&ctx, |m| {
                     m.embed(|e| {
                         e.title("Deleted Message");
-                        e.field("Author", message.author.tag(), true);
-                        e.field("Channel", channel.name(), true);
-                        e.field("Content", message.content, false);
+                        match &message {
+                            Some(message) => {
+                                e.field("Author", message.author.tag(), true);
+                                e.field("Channel", &channel, true);
+                                e.field(
+                                    "Content",
+                                    if message.content.is_empty() {
+                                        "*no text*"
+                                    } else {
+                                        &message.content
+                                    },
+                                    false,
+                                );
+                                if !attachments.links.is_empty() {
+                                    e.field(
+                                        "Attachments",
+                                        excerpt(&attachments.links.join("\n")),
+                                        false,
+                                    );
+                                }
+                                if let Some(image) = &attachments.image {
+                                    e.image(image);
+                                }
+                            }
+                            None => {
+                                e.field("Channel", &channel, true);
+                                e.field("Message ID", deleted_message_id.0, true);
+                                e.field(
+                                    "Sent",
+                                    format!(
+                                        "<t:{}:f>",
+                                        deleted_message_id.created_at().unix_timestamp()
+                                    ),
+                                    true,
+                                );
+                                e.field("Content", "*not cached, content unavailable*", false);
+                            }
+                        }
                         e
                     });
-                    m
+                    m.add_files(attachments.files.into_iter().map(|(filename, data)| {
+                        serenity::AttachmentType::Bytes {
+                            data: data.into(),
+                            filename,
+                        }
+                    }))
                 })
-                .await;
+                .await
+            {
+                warn!("Could not log deleted message: {:?}", e);
+            }
         }
+        Event::MessageUpdate {
+            old_if_available: Some(old),
+            event,
+            ..
+        } => log_edit(ctx, data, old, event).await,
+        Event::MessageDeleteBulk {
+            channel_id,
+            multiple_deleted_messages_ids,
+            ..
+        } => log_bulk_delete(ctx, data, *channel_id, multiple_deleted_messages_ids).await,
         _ => (),
     };
     Ok(())
 }
 
+/// keeps the start of long content, so before and after both fit in one embed
+fn excerpt(content: &str) -> String {
+    if content.is_empty() {
+        return "*no text*".to_owned();
+    }
+    let mut excerpt: String = content.chars().take(1000).collect();
+    if excerpt.len() < content.len() {
+        excerpt += "...";
+    }
+    excerpt
+}
+
+/// posts the before and after of an edit. only the cached old version can be compared,
+/// and updates that leave the text alone (link unfurls, embeds) are skipped
+async fn log_edit(
+    ctx: &serenity::Context,
+    data: &PoiseData,
+    old: &serenity::Message,
+    event: &serenity::MessageUpdateEvent,
+) {
+    let Some(log_channel) = data.edited_message_log_channel else {
+        return;
+    };
+    let Some(content) = &event.content else {
+        return;
+    };
+    if old.author.bot || *content == old.content {
+        return;
+    }
+
+    let channel = channel_label(ctx, old.channel_id).await;
+    if let Err(e) = log_channel
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title("Edited Message");
+                e.url(old.link());
+                e.field("Author", old.author.tag(), true);
+                e.field("Channel", channel, true);
+                e.field("Before", excerpt(&old.content), false);
+                e.field("After", excerpt(content), false);
+                e
+            });
+            m
+        })
+        .await
+    {
+        warn!("Could not log edited message: {:?}", e);
+    }
+}
+
+/// the channel's name, dms and channels the bot can't see fall back to a mention
+async fn channel_label(ctx: &serenity::Context, channel_id: serenity::ChannelId) -> String {
+    match channel_id.to_channel(ctx).await {
+        Ok(serenity::Channel::Guild(channel)) => channel.name().to_owned(),
+        _ => format!("<#{}>", channel_id.0),
+    }
+}
+
+/// posts one embed for a purge or raid cleanup, with whatever of it was cached
+async fn log_bulk_delete(
+    ctx: &serenity::Context,
+    data: &PoiseData,
+    channel_id: serenity::ChannelId,
+    ids: &[serenity::MessageId],
+) {
+    let mut messages: Vec<serenity::Message> = ids
+        .iter()
+        .filter_map(|id| ctx.cache.message(channel_id, *id))
+        .collect();
+    messages.sort_by_key(|m| m.id);
+    for message in &messages {
+        if let Err(e) = deleted_messages::record_deleted_message(&data.pool, message).await {
+            error!("Could not store deleted message: {:?}", e);
+        }
+    }
+
+    // message counts per author, in the order they first show up
+    let mut authors: Vec<(String, usize)> = vec![];
+    for message in &messages {
+        let tag = message.author.tag();
+        match authors.iter_mut().find(|(a, _)| *a == tag) {
+            Some((_, count)) => *count += 1,
+            None => authors.push((tag, 1)),
+        }
+    }
+    let mut authors = authors
+        .iter()
+        .map(|(tag, count)| format!("{} ({})", tag, count))
+        .collect::<Vec<String>>()
+        .join("\n");
+    let uncached = ids.len() - messages.len();
+    if uncached > 0 {
+        authors += &format!("\n*{} not cached, content unavailable*", uncached);
+    }
+
+    // as much of the content as fits in the description
+    let mut content = String::new();
+    for message in &messages {
+        let line = format!("**{}**: {}\n", message.author.tag(), message.content);
+        if content.len() + line.len() > 4000 {
+            content += "...";
+            break;
+        }
+        content += &line;
+    }
+
+    let channel = channel_label(ctx, channel_id).await;
+    if let Err(e) = data
+        .deleted_message_log_channel
+        .send_message(&ctx, |m| {
+            m.embed(|e| {
+                e.title(format!("{} Messages Bulk Deleted", ids.len()));
+                if !content.is_empty() {
+                    e.description(content);
+                }
+                e.field("Channel", channel, true);
+                e.field("Authors", authors.trim_start(), true);
+                e
+            });
+            m
+        })
+        .await
+    {
+        warn!("Could not log bulk deleted messages: {:?}", e);
+    }
+}
+
+/// warns if the bot can't hand out the role, so self-service role commands don't fail silently
+async fn check_role_assignable(
+    ctx: &serenity::Context,
+    guild: serenity::GuildId,
+    role: serenity::RoleId,
+) {
+    let roles = match guild.roles(ctx).await {
+        Ok(roles) => roles,
+        Err(e) => {
+            warn!("Could not fetch guild roles: {:?}", e);
+            return;
+        }
+    };
+    let Some(target) = roles.get(&role) else {
+        warn!("Role {} does not exist in the guild!", role.0);
+        return;
+    };
+    let bot_top = match guild.member(ctx, ctx.cache.current_user_id()).await {
+        Ok(member) => member
+            .roles
+            .iter()
+            .filter_map(|r| roles.get(r))
+            .map(|r| r.position)
+            .max()
+            .unwrap_or(0),
+        Err(e) => {
+            warn!("Could not fetch the bot's member: {:?}", e);
+            return;
+        }
+    };
+    if target.managed || target.position >= bot_top {
+        warn!(
+            "The bot can't assign role `{}`, move it below the bot's highest role.",
+            target.name
+        );
+    }
+}
+
 /// initialize the discord bot
 pub async fn start_bot(
     pool: Pool<MySql>,
     log_receiver: LogReceiver,
     servers: HashMap<SocketAddr, crate::Server>,
-) {
+) -> Result<ExitCode, Error> {
     let bot_token: String = parse_env("BOT_TOKEN");
     let guild_id: u64 = parse_env("GUILD_ID");
     let private_channel_id: u64 = parse_env("PRIVATE_CHANNEL_ID");
     let private_welcome_channel_id: u64 = parse_env("PRIVATE_WELCOME_CHANNEL_ID");
     let deleted_messages_log_channel_id: u64 = parse_env("DELETED_MESSAGE_LOG_CHANNEL_ID");
+    let edited_message_log_channel_id: Option<u64> = parse_env_opt("EDITED_MESSAGE_LOG_CHANNEL_ID");
+    let mod_log_channel_id: Option<u64> = parse_env_opt("MOD_LOG_CHANNEL_ID");
+    let plugin_alert_channel_id: Option<u64> =
+        parse_env_opt("PLUGIN_ALERT_CHANNEL_ID").or(mod_log_channel_id);
     let seeder_role_id: u64 = parse_env("SEEDER_ROLE");
+    let active_alert_channel_id: Option<u64> = parse_env_opt("ACTIVE_ALERT_CHANNEL_ID");
     let trial_mod_channel_id: u64 = parse_env("TRIAL_MOD_CHANNEL_ID");
+    let private_role_id: Option<u64> = parse_env_opt("PRIVATE_ROLE");
+    let allowed_maps: Vec<String> = parse_env_opt::<String>("ALLOWED_MAPS")
+        .unwrap_or_default()
+        .split(',')
+        .map(|m| m.trim().to_owned())
+        .filter(|m| !m.is_empty())
+        .collect();
+    let map_override_role_id: Option<u64> = parse_env_opt("MAP_OVERRIDE_ROLE");
+    let match_cfg: String = parse_env_opt("MATCH_CFG").unwrap_or("competitive".to_owned());
+    let public_cfg: String = parse_env_opt("PUBLIC_CFG").unwrap_or("server".to_owned());
+    let match_channel_id: Option<u64> = parse_env_opt("MATCH_CHANNEL_ID");
+    let mod_role_id: Option<u64> = parse_env_opt("MOD_ROLE");
+    let mods_bypass_cooldowns: bool = parse_env_opt("MOD_COOLDOWN_EXEMPT").unwrap_or(true);
+    let notify_banned_users: bool = parse_env_opt("NOTIFY_BANNED_USERS").unwrap_or(false);
+    // role:minutes,role:minutes
+    let duration_caps: Vec<(serenity::RoleId, u32)> = parse_env_opt::<String>("MOD_DURATION_CAPS")
+        .unwrap_or_default()
+        .split(',')
+        .filter_map(|cap| {
+            let (role, minutes) = cap.trim().split_once(':')?;
+            Some((serenity::RoleId(role.parse().ok()?), minutes.parse().ok()?))
+        })
+        .collect();
+    let ban_presets =
+        commands::parse_ban_presets(&parse_env_opt::<String>("BAN_PRESETS").unwrap_or_default());
+    let respawn_presets = commands::parse_respawn_presets(
+        &parse_env_opt::<String>("RESPAWN_PRESETS").unwrap_or_default(),
+    );
     let intents = serenity::GatewayIntents::non_privileged()
         | serenity::GatewayIntents::MESSAGE_CONTENT
         | serenity::GatewayIntents::GUILD_MESSAGES;
 
+    let chat_history = Arc::new(RwLock::new(log_handler::ChatHistory::from_env()));
+    let map_stats = Arc::new(RwLock::new(kill_feed::MapStats::default()));
+    let map_votes = Arc::new(RwLock::new(map_vote::MapVotes::from_env()));
+    let match_state: Arc<RwLock<HashMap<SocketAddr, commands::MatchState>>> =
+        Arc::new(RwLock::new(HashMap::new()));
+    // servers with a schedule follow it, unless /playercap turned that off
+    let mut auto_cap = HashSet::new();
+    for server in servers.values().filter(|s| s.auto_cap.is_some()) {
+        if settings::get(&pool, &settings::auto_cap(server.addr))
+            .await?
+            .unwrap_or(true)
+        {
+            auto_cap.insert(server.addr);
+        }
+    }
+    let auto_cap_servers: Arc<RwLock<HashSet<SocketAddr>>> = Arc::new(RwLock::new(auto_cap));
+    let chat_relay_channel: Option<serenity::ChannelId> =
+        parse_env_opt("CHAT_RELAY_CHANNEL_ID").map(serenity::ChannelId);
+    // shared with the steam group sync thread
+    let steam_client = Arc::new(SteamIDClient::new(
+        parse_env("STEAMID_MYID"),
+        parse_env("STEAMID_API_KEY"),
+        parse_env_opt("STEAM_API_KEY"),
+        std::time::Duration::from_secs(parse_env_opt("STEAMID_CACHE_SECS").unwrap_or(60 * 60)),
+    ));
+    let steam_group = steam_group::GroupSync::from_env();
+    let shutdown = CancellationToken::new();
+    let restart_requested = Arc::new(AtomicBool::new(false));
+
+    let mut commands = vec![
+        commands::rcon(),
+        commands::rcon_multi(),
+        commands::snipers(),
+        commands::seeder(),
+        commands::seeder_cooldown(),
+        commands::seedrole(),
+        commands::respawntimes(),
+        commands::playercap(),
+        commands::private_add(),
+        commands::meow(),
+        commands::mediaexempt(),
+        commands::mediacooldown(),
+        commands::deletedsearch(),
+        commands::restart_bot(),
+        commands::togglecommand(),
+        commands::rcon_as(),
+        commands::steamid_cache(),
+        commands::health(),
+        commands::map(),
+        commands::changelevel(),
+        commands::nominate(),
+        commands::rtv(),
+        commands::mapschedule(),
+        commands::alias(),
+        commands::status(),
+        commands::serverinfo(),
+        commands::playerinfo(),
+        commands::find(),
+        commands::players(),
+        commands::serverchat(),
+        commands::serverquery(),
+        commands::cvardiff(),
+        commands::plugininfo(),
+        commands::demo(),
+        commands::lookup(),
+        commands::steamlink(),
+        commands::unlink(),
+        commands::forcelink(),
+        commands::forceunlink(),
+        commands::syncgroup(),
+        commands::playtime(),
+        commands::top(),
+        commands::population(),
+        commands::connect(),
+        commands::announce(),
+        commands::setmotd(),
+        commands::matchsetup(),
+        commands::matchend(),
+        commands::reacted_users(),
+        commands::feedback(),
+        commands::feedback_list(),
+        commands::feedback_reply(),
+        commands::feedback_whois(),
+        commands::tf2ban(),
+        commands::tf2banid(),
+        commands::ban_all(),
+        commands::bans(),
+        commands::recentbans(),
+        commands::warn(),
+        commands::warnings(),
+        commands::history(),
+        commands::addreason(),
+        commands::rmreason(),
+        commands::tf2unban(),
+        commands::tf2kick(),
+        commands::slap(),
+        commands::slay(),
+        commands::tf2mute(),
+        commands::tf2unmute(),
+        commands::tf2gag(),
+        commands::tf2ungag(),
+        commands::tf2noclip(),
+        commands::tf2spectate(),
+        commands::massgag(),
+        commands::massmute(),
+    ];
+    command_cooldowns::apply(&mut commands);
+
     let girlpounder = {
         let servers = servers.clone();
         let pool = pool.clone();
+        let chat_history = chat_history.clone();
+        let map_stats = map_stats.clone();
+        let map_votes = map_votes.clone();
+        let auto_cap_servers = auto_cap_servers.clone();
+        let match_state = match_state.clone();
+        let steam_client = steam_client.clone();
+        let shutdown = shutdown.clone();
+        let restart_requested = restart_requested.clone();
         poise::Framework::builder()
             .options(poise::FrameworkOptions {
-                commands: vec![
-                    commands::rcon(),
-                    commands::snipers(),
-                    commands::seeder(),
-                    commands::respawntimes(),
-                    commands::playercap(),
-                    commands::private_add(),
-                    commands::meow(),
-                    commands::map(),
-                    commands::status(),
-                    commands::lookup(),
-                    commands::reacted_users(),
-                    commands::feedback(),
-                    commands::tf2ban(),
-                    commands::tf2banid(),
-                    commands::tf2unban(),
-                    commands::tf2kick(),
-                    commands::tf2mute(),
-                    commands::tf2unmute(),
-                    commands::tf2gag(),
-                    commands::tf2ungag(),
-                ],
+                commands,
                 event_handler: |a, b, c, d| Box::pin(event_handler(a, b, c, d)),
+                command_check: Some(|ctx| Box::pin(command_check(ctx))),
+                pre_command: |ctx| Box::pin(pre_command(ctx)),
+                on_error: |error| Box::pin(on_error(error)),
+                manual_cooldowns: true,
                 ..Default::default()
             })
             .token(bot_token)
@@ -270,52 +1024,439 @@ pub async fn start_bot(
                     )
                     .await?;
 
-                    ctx.set_activity(serenity::Activity::playing("tf2.fluffycat.gay"))
-                        .await;
+                    activity::spawn_activity_thread(ctx.clone(), servers.clone(), shutdown.clone());
+                    let mut seeder_roles: Vec<serenity::RoleId> =
+                        servers.values().filter_map(|s| s.seeder_role).collect();
+                    seeder_roles.push(serenity::RoleId(seeder_role_id));
+                    seeder_roles.sort();
+                    seeder_roles.dedup();
+                    for role in seeder_roles {
+                        check_role_assignable(ctx, serenity::GuildId(guild_id), role).await;
+                    }
+
+                    let disabled_commands: HashSet<String> =
+                        sqlx::query!(r#"SELECT `name` FROM `disabled_commands`"#)
+                            .fetch_all(&pool)
+                            .await?
+                            .into_iter()
+                            .map(|r| r.name)
+                            .collect();
+
+                    let mod_reasons: Vec<String> =
+                        sqlx::query!(r#"SELECT `reason` FROM `mod_reasons` ORDER BY `reason`"#)
+                            .fetch_all(&pool)
+                            .await?
+                            .into_iter()
+                            .map(|r| r.reason)
+                            .collect();
+
+                    // seeder pings from before the restart still count
+                    let seeder_cooldown: HashMap<SocketAddr, DateTime<Utc>> = sqlx::query!(
+                        r#"SELECT `server_addr`, `seeded_at` FROM `seeder_cooldowns`"#
+                    )
+                    .fetch_all(&pool)
+                    .await
+                    .map(|rows| {
+                        load_seeder_cooldowns(
+                            rows.into_iter().map(|r| (r.server_addr, r.seeded_at)),
+                        )
+                    })?;
+
+                    let new_account_gate = new_account_gate::NewAccountGate::from_env();
+                    if let Some(gate) = &new_account_gate {
+                        gate.forget_grown(&pool).await?;
+                    }
+
+                    // feedback stored before authors were sealed gets sealed now
+                    let feedback_seal = feedback::AuthorSeal::from_env();
+                    if feedback_seal.is_none() {
+                        warn!("FEEDBACK_KEY isn't set, so feedback authors aren't recorded and /feedback_reply can't be used");
+                    }
+                    feedback::seal_plain_authors(&pool, feedback_seal.as_ref()).await?;
+
+                    // exemptions granted before the restart still apply
+                    sqlx::query!(
+                        r#"DELETE FROM `media_exemptions` WHERE `until` <= ?"#,
+                        Utc::now()
+                    )
+                    .execute(&pool)
+                    .await?;
+                    let mut media_cooldown = media_cooldown::MediaCooldown::from_env();
+                    for r in sqlx::query!(r#"SELECT `user_id`, `until` FROM `media_exemptions`"#)
+                        .fetch_all(&pool)
+                        .await?
+                    {
+                        if let Ok(uid) = r.user_id.parse() {
+                            media_cooldown.exempt(serenity::UserId(uid), r.until);
+                        }
+                    }
+                    // limits set with /mediacooldown override MEDIA_COOLDOWN
+                    for r in sqlx::query!(
+                        r#"SELECT `channel_id`, `posts`, `minutes` FROM `media_limits`"#
+                    )
+                    .fetch_all(&pool)
+                    .await?
+                    {
+                        if let Ok(cid) = r.channel_id.parse() {
+                            let limit = r.posts.zip(r.minutes).map(|(posts, minutes)| {
+                                media_cooldown::MediaLimit { posts, minutes }
+                            });
+                            media_cooldown.set_limit(serenity::ChannelId(cid), limit);
+                        }
+                    }
+
+                    // /seeder_cooldown overrides SEED_COOLDOWN_MINUTES
+                    let seed_cooldown: i64 =
+                        match settings::get(&pool, settings::SEED_COOLDOWN_MINUTES).await? {
+                            Some(minutes) => minutes,
+                            None => parse_env_opt("SEED_COOLDOWN_MINUTES").unwrap_or(4 * 60),
+                        };
+
+                    let mut server_choices: Vec<(String, SocketAddr)> = servers
+                        .iter()
+                        .map(|(addr, s)| (s.name.clone(), *addr))
+                        .collect();
+                    server_choices.sort();
 
                     Ok(PoiseData {
                         servers,
-                        media_cooldown: Arc::new(RwLock::new(
-                            media_cooldown::MediaCooldown::from_env(),
-                        )),
+                        server_choices,
+                        allowed_maps,
+                        map_override_role: map_override_role_id.map(serenity::RoleId),
+                        match_cfg,
+                        public_cfg,
+                        match_channel: match_channel_id.map(serenity::ChannelId),
+                        match_state,
+                        mod_role: mod_role_id.map(serenity::RoleId),
+                        duration_caps,
+                        mods_bypass_cooldowns,
+                        ban_presets,
+                        notify_banned_users,
+                        chat_history,
+                        map_stats,
+                        chat_bridge: chat_relay_channel.map(chat_bridge::ChatBridge::new),
+                        seed_cooldown: Arc::new(RwLock::new(Duration::minutes(seed_cooldown))),
+                        player_cache: Arc::new(RwLock::new(Default::default())),
+                        rcon_policy: rcon_policy::RconPolicy::from_env(),
+                        map_cache: Arc::new(RwLock::new(Default::default())),
+                        map_votes,
+                        mod_reasons: Arc::new(RwLock::new(mod_reasons)),
+                        positivity: Arc::new(RwLock::new(positivity::Positivity::from_env())),
+                        respawn_presets,
+                        auto_cap_servers,
+                        feedback_seal,
+                        feedback_whois_role: parse_env_opt("FEEDBACK_WHOIS_ROLE")
+                            .map(serenity::RoleId),
+                        steam_group,
+                        shutdown,
+                        restart_requested,
+                        disabled_commands: Arc::new(RwLock::new(disabled_commands)),
+                        new_account_gate,
+                        media_cooldown: Arc::new(RwLock::new(media_cooldown)),
                         guild_id: serenity::GuildId(guild_id),
                         private_channel: serenity::ChannelId(private_channel_id),
                         private_welcome_channel: serenity::ChannelId(private_welcome_channel_id),
+                        private_role: private_role_id.map(serenity::RoleId),
                         seeder_role: serenity::RoleId(seeder_role_id),
-                        msg_counts: Arc::new(RwLock::new(HashMap::new())),
                         deleted_message_log_channel: serenity::ChannelId(
                             deleted_messages_log_channel_id,
                         ),
+                        edited_message_log_channel: edited_message_log_channel_id
+                            .map(serenity::ChannelId),
                         trial_mod_channel: serenity::ChannelId(trial_mod_channel_id),
+                        mod_log_channel: mod_log_channel_id.map(serenity::ChannelId),
                         media_cooldown_thread: OnceCell::new(),
-                        seeder_cooldown: Arc::new(RwLock::new(HashMap::new())),
+                        seeder_cooldown: Arc::new(RwLock::new(seeder_cooldown)),
                         pool,
-                        client: SteamIDClient::new(
-                            parse_env("STEAMID_MYID"),
-                            parse_env("STEAMID_API_KEY"),
-                        ),
+                        client: steam_client,
                     })
                 })
             })
             .build()
-            .await
-            .expect("Failed to build girlpounder bot.")
+            .await?
     };
-    // launch alt threads
+    // launch alt threads, kept to wait on at shutdown
 
     let ctx = girlpounder.client().cache_and_http.clone();
+    let mut threads = vec![];
     for (_addr, server) in servers.iter() {
-        player_count::spawn_player_count_thread(server.clone(), ctx.clone());
+        let active_alert =
+            server
+                .active_threshold
+                .zip(active_alert_channel_id)
+                .map(|(threshold, channel)| {
+                    player_count::ActiveAlert::new(
+                        serenity::ChannelId(channel),
+                        server
+                            .seeder_role
+                            .unwrap_or(serenity::RoleId(seeder_role_id)),
+                        threshold,
+                    )
+                });
+        threads.push(player_count::spawn_player_count_thread(
+            server.clone(),
+            ctx.clone(),
+            pool.clone(),
+            active_alert,
+            auto_cap_servers.clone(),
+            shutdown.clone(),
+        ));
     }
 
-    log_handler::spawn_log_thread(
+    threads.push(connect_tokens::spawn_token_expiry_thread(
+        servers.clone(),
+        pool.clone(),
+        match_state.clone(),
+        shutdown.clone(),
+    ));
+    threads.push(map_schedule::spawn_map_schedule_thread(
+        servers.clone(),
+        pool.clone(),
+        shutdown.clone(),
+    ));
+    threads.push(deleted_messages::spawn_prune_thread(
+        pool.clone(),
+        shutdown.clone(),
+    ));
+    if let Some(sync) = steam_group {
+        threads.push(steam_group::spawn_group_sync_thread(
+            sync,
+            ctx.clone(),
+            serenity::GuildId(guild_id),
+            pool.clone(),
+            steam_client.clone(),
+            shutdown.clone(),
+        ));
+    }
+    if let Some(channel) = plugin_alert_channel_id {
+        threads.push(plugin_alerts::spawn_plugin_check_thread(
+            servers.clone(),
+            ctx.clone(),
+            serenity::ChannelId(channel),
+            shutdown.clone(),
+        ));
+    }
+
+    match sessions::close_orphaned_sessions(&pool).await {
+        Ok(0) => {}
+        Ok(n) => info!(
+            "Marked {} sessions left open by the last run as orphaned.",
+            n
+        ),
+        Err(e) => error!("Could not close orphaned sessions: {:?}", e),
+    }
+    threads.push(log_handler::spawn_log_thread(
         log_receiver.clone(),
         servers.clone(),
         pool.clone(),
         ctx.clone(),
-    );
+        log_handler::LogState {
+            chat_history,
+            map_stats,
+            map_votes,
+            chat_relay_channel,
+        },
+        shutdown.clone(),
+    ));
+
+    if let Some(addr) = parse_env_opt("METRICS_ADDR") {
+        threads.push(metrics::spawn_metrics_server(addr, shutdown.clone()));
+    }
+
+    {
+        let shard_manager = girlpounder.shard_manager().clone();
+        let shutdown = shutdown.clone();
+        tokio::spawn(async move {
+            shutdown_signal().await;
+            info!("Shutting down...");
+            shutdown.cancel();
+            shard_manager.lock().await.shutdown_all().await;
+        });
+    }
+
+    info!("Bot started!");
+    let stopped = shutdown.clone();
+    let result = girlpounder
+        .start_with(|mut client| async move {
+            // serenity reconnects dropped shards itself, this is for when it gives up
+            let mut backoff = std::time::Duration::from_secs(5);
+            loop {
+                match client.start().await {
+                    Ok(()) => return Ok(()),
+                    Err(e) if is_fatal_gateway_error(&e) => return Err(e),
+                    Err(_) if stopped.is_cancelled() => return Ok(()),
+                    Err(e) => {
+                        warn!(
+                            "Lost the discord connection: {:?}, retrying in {}s",
+                            e,
+                            backoff.as_secs()
+                        );
+                        tokio::time::sleep(backoff).await;
+                        backoff = (backoff * 2).min(std::time::Duration::from_secs(5 * 60));
+                    }
+                }
+            }
+        })
+        .await;
+
+    // let the threads finish what they're writing before the pool goes away
+    shutdown.cancel();
+    let wait = std::time::Duration::from_secs(10);
+    if tokio::time::timeout(wait, futures::future::join_all(threads))
+        .await
+        .is_err()
+    {
+        warn!("Background threads didn't stop in time.");
+    }
+    pool.close().await;
+    info!("Bot stopped.");
+    result?;
+    Ok(if restart_requested.load(Ordering::SeqCst) {
+        // non zero so systemd/docker on-failure policies bring us back up
+        ExitCode::from(parse_env_opt("RESTART_EXIT_CODE").unwrap_or(1u8))
+    } else {
+        ExitCode::SUCCESS
+    })
+}
+
+/// resolves on ctrl+c, or SIGTERM from docker and systemd
+async fn shutdown_signal() {
+    // a signal that can't be listened for never arrives, rather than shutting down straight away
+    let ctrl_c = async {
+        if let Err(e) = tokio::signal::ctrl_c().await {
+            warn!("Could not listen for ctrl+c: {:?}", e);
+            std::future::pending::<()>().await;
+        }
+    };
+    #[cfg(unix)]
+    let terminate = async {
+        use tokio::signal::unix::{signal, SignalKind};
+        match signal(SignalKind::terminate()) {
+            Ok(mut terminate) => {
+                terminate.recv().await;
+            }
+            Err(e) => {
+                warn!("Could not listen for SIGTERM: {:?}", e);
+                std::future::pending::<()>().await;
+            }
+        }
+    };
+    #[cfg(not(unix))]
+    let terminate = std::future::pending::<()>();
+    tokio::select! {
+        _ = ctrl_c => {}
+        _ = terminate => {}
+    }
+}
+
+/// errors that retrying won't fix, like a bad token
+fn is_fatal_gateway_error(e: &serenity::Error) -> bool {
+    matches!(
+        e,
+        serenity::Error::Gateway(
+            serenity::GatewayError::InvalidAuthentication
+                | serenity::GatewayError::InvalidGatewayIntents
+                | serenity::GatewayError::DisallowedGatewayIntents
+        )
+    )
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn seeded_at() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn never_seeded_servers_can_be_pinged() {
+        assert_eq!(seed_allowed(None, Duration::hours(4), seeded_at()), Ok(()));
+    }
+
+    #[test]
+    fn pinging_is_allowed_again_once_the_cooldown_is_over() {
+        let cooldown = Duration::hours(4);
+        let last = Some(seeded_at());
+        assert_eq!(
+            seed_allowed(
+                last,
+                cooldown,
+                seeded_at() + Duration::hours(4) - Duration::seconds(1)
+            ),
+            Err(Duration::seconds(1))
+        );
+        assert_eq!(
+            seed_allowed(last, cooldown, seeded_at() + Duration::hours(4)),
+            Ok(())
+        );
+        assert_eq!(
+            seed_allowed(
+                last,
+                cooldown,
+                seeded_at() + Duration::hours(4) + Duration::seconds(1)
+            ),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn a_shorter_cooldown_applies_to_earlier_seeds() {
+        let last = Some(seeded_at());
+        let now = seeded_at() + Duration::minutes(90);
+        assert!(seed_allowed(last, Duration::hours(4), now).is_err());
+        assert_eq!(seed_allowed(last, Duration::hours(1), now), Ok(()));
+    }
 
-    let fut = girlpounder.start();
-    println!("Bot started!");
-    fut.await.expect("Bot broke"); //
+    #[test]
+    fn a_loaded_timestamp_blocks_a_premature_ping() {
+        let seeded: SocketAddr = "127.0.0.1:27015".parse().unwrap();
+        let other: SocketAddr = "127.0.0.1:27016".parse().unwrap();
+        let loaded = load_seeder_cooldowns([
+            (seeded.to_string(), seeded_at()),
+            ("not an address".to_owned(), seeded_at()),
+        ]);
+        assert_eq!(loaded.len(), 1);
+
+        let now = seeded_at() + Duration::hours(1);
+        assert_eq!(
+            seed_allowed(loaded.get(&seeded).copied(), Duration::hours(4), now),
+            Err(Duration::hours(3))
+        );
+        assert_eq!(
+            seed_allowed(loaded.get(&other).copied(), Duration::hours(4), now),
+            Ok(())
+        );
+    }
+
+    #[test]
+    fn rehydrated_cooldowns_are_deleted_soonest_first() {
+        let now = seeded_at();
+        let row = |message: &str, delete_at| {
+            (
+                "1".to_owned(),
+                "2".to_owned(),
+                message.to_owned(),
+                delete_at,
+            )
+        };
+        // out of order, like rows that tie or come back unsorted
+        let queue = rehydrate([
+            row("30", now + Duration::minutes(30)),
+            row("not an id", now),
+            row("10", now + Duration::minutes(10)),
+            // due while the bot was down
+            row("0", now - Duration::minutes(5)),
+            row("20", now + Duration::minutes(20)),
+        ]);
+        let ids = |queue: &[PendingCooldown]| queue.iter().map(|p| p.message.0).collect::<Vec<_>>();
+        assert_eq!(ids(&queue), [0, 10, 20, 30]);
+
+        let (due, waiting) = take_due(queue, now);
+        assert_eq!(ids(&due), [0]);
+        let (due, waiting) = take_due(waiting, now + Duration::minutes(20));
+        assert_eq!(ids(&due), [10, 20]);
+        assert_eq!(ids(&waiting), [30]);
+    }
 }
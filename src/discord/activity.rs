@@ -0,0 +1,62 @@
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{info_span, Instrument};
+
+use crate::{parse_env_opt, Server};
+
+/// the status messages to rotate through, from BOT_ACTIVITY separated by |
+fn activities() -> Vec<String> {
+    parse_env_opt::<String>("BOT_ACTIVITY")
+        .unwrap_or("tf2.fluffycat.gay".to_owned())
+        .split('|')
+        .map(|a| a.trim().to_owned())
+        .filter(|a| !a.is_empty())
+        .collect()
+}
+
+/// total players online on every server
+async fn player_count(servers: &HashMap<SocketAddr, Server>) -> usize {
+    let mut count = 0;
+    for server in servers.values() {
+        if let Ok(state) = server.controller.write().await.status().await {
+            count += state.players.len();
+        }
+    }
+    count
+}
+
+/// sets the bot activity, cycling through BOT_ACTIVITY every ACTIVITY_INTERVAL_SECS.
+/// {players} is replaced with the live player count
+pub fn spawn_activity_thread(
+    ctx: serenity::Context,
+    servers: HashMap<SocketAddr, Server>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let activities = activities();
+    let secs: u64 = parse_env_opt("ACTIVITY_INTERVAL_SECS").unwrap_or(120);
+    let mut interval = time::interval(time::Duration::from_secs(secs.max(15)));
+    let thread = async move {
+        for activity in activities.iter().cycle() {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            let activity = if activity.contains("{players}") {
+                activity.replace("{players}", &player_count(&servers).await.to_string())
+            } else {
+                activity.clone()
+            };
+            ctx.set_activity(serenity::Activity::playing(activity))
+                .await;
+            // nothing to rotate to
+            if activities.len() == 1 && !activities[0].contains("{players}") {
+                break;
+            }
+        }
+    };
+    tokio::spawn(thread.instrument(info_span!("activity")))
+}
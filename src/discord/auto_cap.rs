@@ -0,0 +1,128 @@
+use std::str::FromStr;
+
+use chrono::{Timelike, Utc};
+use regex::Regex;
+
+use crate::{parse_env_opt, Error, Server};
+
+/// the lowest cap /playercap sets by hand
+pub const MIN_CAP: u8 = 24;
+
+/// a server's visible player cap through the day, from AUTO_CAP_N as
+/// `cap:off_peak_cap:start-end`. off peak hours are in UTC and may wrap past midnight
+#[derive(Clone, Debug)]
+pub struct CapSchedule {
+    cap: u8,
+    off_peak_cap: u8,
+    off_peak: (u32, u32),
+}
+
+impl FromStr for CapSchedule {
+    type Err = Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut parts = s.trim().split(':');
+        let (Some(cap), Some(off_peak_cap), Some(hours), None) =
+            (parts.next(), parts.next(), parts.next(), parts.next())
+        else {
+            return Err("Expected cap:off_peak_cap:start-end".into());
+        };
+        let (start, end) = hours.split_once('-').ok_or("Expected start-end hours")?;
+        let (start, end): (u32, u32) = (start.trim().parse()?, end.trim().parse()?);
+        if start > 23 || end > 23 {
+            return Err("Hours go from 0 to 23".into());
+        }
+        Ok(Self {
+            cap: cap.trim().parse()?,
+            off_peak_cap: off_peak_cap.trim().parse()?,
+            off_peak: (start, end),
+        })
+    }
+}
+
+impl CapSchedule {
+    fn is_off_peak(&self, hour: u32) -> bool {
+        let (start, end) = self.off_peak;
+        if start <= end {
+            (start..end).contains(&hour)
+        } else {
+            hour >= start || hour < end
+        }
+    }
+}
+
+/// the highest visible cap the server allows, one under maxplayers
+pub async fn hard_max(server: &Server) -> Result<u8, Error> {
+    let re = Regex::new(r#""maxplayers" is "(\d+)""#).unwrap();
+    let max = server.controller.write().await.query("maxplayers").await?;
+    Ok(re
+        .captures(&max)
+        .and_then(|caps| caps[1].parse::<u8>().ok())
+        .unwrap_or(25)
+        - 1)
+}
+
+/// the console line that shows `visible` slots, reserving the rest up to `max`
+pub fn cap_command(max: u8, visible: u8) -> String {
+    format!(
+        "sm_reserved_slots {}; sv_visiblemaxplayers {};",
+        max - visible,
+        visible
+    )
+}
+
+/// raises and lowers a server's cap with its population, for the player count thread
+pub struct AutoCap {
+    schedule: CapSchedule,
+    /// slots opened at a time once the server is nearly full
+    step: u8,
+    /// how close to the cap counts as nearly full
+    margin: u8,
+}
+
+impl AutoCap {
+    /// the step and margin come from AUTO_CAP_STEP and AUTO_CAP_MARGIN
+    pub fn new(schedule: CapSchedule) -> Self {
+        Self {
+            schedule,
+            step: parse_env_opt("AUTO_CAP_STEP").unwrap_or(8u8).max(1),
+            margin: parse_env_opt("AUTO_CAP_MARGIN").unwrap_or(2),
+        }
+    }
+
+    /// the cap for the time of day, opened a step at a time while the server is close to full.
+    /// never below the players already on
+    fn target(&self, players: usize, hour: u32, max: u8) -> u8 {
+        let mut cap = if self.schedule.is_off_peak(hour) {
+            self.schedule.off_peak_cap
+        } else {
+            self.schedule.cap
+        };
+        let players = players.min(max as usize) as u8;
+        while players.saturating_add(self.margin) >= cap && cap < max {
+            cap = cap.saturating_add(self.step);
+        }
+        cap.min(max).max(players)
+    }
+
+    /// sets the server's cap for its population if it isn't already. returns the new cap
+    pub async fn apply(
+        &self,
+        server: &Server,
+        players: usize,
+        current: i32,
+    ) -> Result<Option<u8>, Error> {
+        let max = hard_max(server).await?;
+        let cap = self.target(players, Utc::now().hour(), max);
+        if cap as i32 == current {
+            return Ok(None);
+        }
+        server
+            .controller
+            .write()
+            .await
+            .run(&cap_command(max, cap))
+            .await?;
+        Ok(Some(cap))
+    }
+}
@@ -0,0 +1,182 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude as serenity;
+use sqlx::{MySql, Pool};
+use std::net::SocketAddr;
+use tracing::warn;
+
+use super::commands::util::escape_rcon_arg;
+use crate::steamid;
+use crate::{Error, Server};
+
+/// when a ban of this many minutes runs out, None for permanent (0) bans
+pub fn ban_expiry(minutes: u32, now: DateTime<Utc>) -> Option<DateTime<Utc>> {
+    (minutes > 0).then(|| now + Duration::minutes(minutes as i64))
+}
+
+/// whole minutes left on a ban, rounded up so it never ends early. 0 is permanent
+fn remaining_minutes(expires_at: Option<DateTime<Utc>>, now: DateTime<Utc>) -> u32 {
+    let Some(expires_at) = expires_at else {
+        return 0;
+    };
+    let secs = (expires_at - now).num_seconds().max(1);
+    ((secs + 59) / 60).min(u32::MAX as i64) as u32
+}
+
+/// the command that bans the steamid again for the time the ban has left.
+/// sm_addban on someone who is already banned just replaces the ban, so it's safe to repeat
+fn reapply_command(
+    steamid: &str,
+    reason: &str,
+    expires_at: Option<DateTime<Utc>>,
+    now: DateTime<Utc>,
+) -> String {
+    format!(
+        "sm_addban {} {} {}",
+        remaining_minutes(expires_at, now),
+        escape_rcon_arg(steamid),
+        escape_rcon_arg(reason)
+    )
+}
+
+/// records a ban so it can be put back if the game server forgets it.
+/// `server` is None for bans on every server. the steamid is stored as steam3
+pub async fn insert_ban(
+    pool: &Pool<MySql>,
+    target: &str,
+    steamid: Option<&str>,
+    server: Option<SocketAddr>,
+    issued_by: serenity::UserId,
+    reason: &str,
+    minutes: u32,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		INSERT INTO `bans` (`target`, `steamid`, `server_addr`, `issued_by`, `reason`, `expires_at`)
+		VALUES (?, ?, ?, ?, ?, ?)
+	"#,
+        target,
+        steamid.map(steamid::normalize),
+        server.map(|s| s.to_string()),
+        issued_by.0.to_string(),
+        reason,
+        ban_expiry(minutes, Utc::now())
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// marks the active bans on the steamid, in any format, or username as lifted.
+/// with a server, only bans on that server are lifted and bans on every server stay
+pub async fn lift_bans(
+    pool: &Pool<MySql>,
+    target: &str,
+    server: Option<SocketAddr>,
+) -> Result<u64, Error> {
+    let server = server.map(|s| s.to_string());
+    let result = sqlx::query!(
+        r#"
+		UPDATE `bans` SET `lifted` = true
+		WHERE `lifted` = false AND (`steamid` = ? OR `target` = ?)
+		AND (? IS NULL OR `server_addr` = ?)
+	"#,
+        steamid::normalize(target),
+        target,
+        server,
+        server
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// bans every steamid that should still be banned on the server again, for the time they have left.
+/// banning someone who is already banned just refreshes it, so this is safe to repeat.
+/// returns how many bans were put back, ones that failed are logged and skipped
+pub async fn reapply_bans(pool: &Pool<MySql>, server: &Server) -> Result<usize, Error> {
+    let now = Utc::now();
+    let active = sqlx::query!(
+        r#"
+        SELECT `steamid`, `reason`, `expires_at` FROM `bans`
+        WHERE `lifted` = false AND `steamid` IS NOT NULL
+        AND (`server_addr` IS NULL OR `server_addr` = ?)
+        AND (`expires_at` IS NULL OR `expires_at` > ?)
+    "#,
+        server.addr.to_string(),
+        now
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let mut applied = 0;
+    for ban in &active {
+        let Some(steamid) = &ban.steamid else {
+            continue;
+        };
+        let cmd = reapply_command(steamid, &ban.reason, ban.expires_at, now);
+        // locked per ban, so commands from discord aren't stuck behind a long list
+        let result = server.controller.write().await.run(&cmd).await;
+        match result {
+            Ok(_) => applied += 1,
+            Err(e) => warn!(
+                "Could not reapply the ban on {} to {}: {:?}",
+                steamid, server.name, e
+            ),
+        }
+    }
+    Ok(applied)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap()
+    }
+
+    #[test]
+    fn expiry_is_minutes_from_now() {
+        assert_eq!(ban_expiry(0, now()), None);
+        assert_eq!(ban_expiry(90, now()), Some(now() + Duration::minutes(90)));
+    }
+
+    #[test]
+    fn remaining_rounds_up() {
+        let expires = ban_expiry(90, now());
+        assert_eq!(remaining_minutes(expires, now()), 90);
+        assert_eq!(
+            remaining_minutes(expires, now() + Duration::seconds(30)),
+            90
+        );
+        assert_eq!(remaining_minutes(expires, now() + Duration::minutes(89)), 1);
+        assert_eq!(remaining_minutes(None, now()), 0);
+    }
+
+    #[test]
+    fn nearly_expired_ban_never_becomes_permanent() {
+        let expires = ban_expiry(1, now());
+        assert_eq!(remaining_minutes(expires, now() + Duration::seconds(59)), 1);
+        assert_eq!(remaining_minutes(expires, now() + Duration::minutes(5)), 1);
+    }
+
+    #[test]
+    fn reapplying_twice_gives_the_same_ban() {
+        let expires = ban_expiry(60, now());
+        let first = reapply_command("[U:1:22202]", "cheating", expires, now());
+        let again = reapply_command("[U:1:22202]", "cheating", expires, now());
+        assert_eq!(first, again);
+        assert!(first.starts_with("sm_addban 60 "));
+        // later on the ban is put back for what's left, not the full length again
+        let later = reapply_command(
+            "[U:1:22202]",
+            "cheating",
+            expires,
+            now() + Duration::minutes(45),
+        );
+        assert!(later.starts_with("sm_addban 15 "));
+        let permanent = reapply_command("[U:1:22202]", "cheating", None, now());
+        assert!(permanent.starts_with("sm_addban 0 "));
+    }
+}
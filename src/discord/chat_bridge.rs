@@ -0,0 +1,79 @@
+use poise::serenity_prelude as serenity;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::sync::RwLock;
+use tracing::warn;
+
+use super::commands::util::{escape_rcon_arg, rcon_each};
+use super::media_cooldown::LeakyBucket;
+use crate::{parse_env_opt, Error, Server};
+
+/// longest message sent into the game, the chat box cuts off around here anyway
+const MAX_LEN: usize = 120;
+
+/// the discord to game half of the chat relay
+pub struct ChatBridge {
+    pub channel: serenity::ChannelId,
+    per_minute: f64,
+    /// only locked to check the author's bucket, never across rcon
+    limits: RwLock<HashMap<serenity::UserId, LeakyBucket>>,
+}
+
+impl ChatBridge {
+    /// each user can send CHAT_BRIDGE_PER_MINUTE messages a minute into the game, 6 by default
+    pub fn new(channel: serenity::ChannelId) -> Self {
+        Self {
+            channel,
+            per_minute: parse_env_opt("CHAT_BRIDGE_PER_MINUTE").unwrap_or(6.),
+            limits: RwLock::new(HashMap::new()),
+        }
+    }
+
+    /// says the message on every server, if it's in the relay channel and the author isn't too fast
+    pub async fn relay_to_game(
+        &self,
+        ctx: &serenity::Context,
+        servers: &HashMap<SocketAddr, Server>,
+        msg: &serenity::Message,
+    ) -> Result<(), Error> {
+        // bots include our own relay webhook
+        if msg.channel_id != self.channel || msg.author.bot {
+            return Ok(());
+        }
+        let content: String = escape_rcon_arg(&msg.content.replace(['\n', '\r'], " "))
+            .trim()
+            .chars()
+            .take(MAX_LEN)
+            .collect();
+        // attachments alone have nothing to say
+        if content.is_empty() {
+            return Ok(());
+        }
+        let per_minute = self.per_minute;
+        let too_fast = self
+            .limits
+            .write()
+            .await
+            .entry(msg.author.id)
+            .or_insert_with(|| LeakyBucket::new(per_minute, per_minute, 1.))
+            .try_afford_one()
+            .is_err();
+        if too_fast {
+            msg.react(ctx, '⏳').await?;
+            return Ok(());
+        }
+
+        let cmd = format!(
+            "say \"[Discord] {}: {}\"",
+            escape_rcon_arg(&msg.author.name),
+            content
+        );
+        let servers: Vec<&Server> = servers.values().collect();
+        for (addr, result) in rcon_each(&servers, &cmd).await {
+            if let Err(e) = result {
+                warn!("Could not relay discord chat to {}: {}", addr, e);
+            }
+        }
+        Ok(())
+    }
+}
@@ -0,0 +1,70 @@
+use std::collections::HashMap;
+use std::sync::Mutex;
+use std::time::Duration;
+
+use poise::{CooldownConfig, CooldownTracker};
+use tracing::warn;
+
+use super::PoiseData;
+use crate::{parse_env_opt, Error};
+
+/// cooldowns every command starts with, as `command:scope=secs:scope=secs`.
+/// overridden per command by COMMAND_COOLDOWNS in the same format
+const DEFAULT_COOLDOWNS: &str =
+    "lookup:global=10,meow:channel=4:user=10,seeder:user=300,feedback:user=60";
+
+/// parses `command:scope=secs:...` entries, skipping any that don't make sense
+fn parse_cooldowns(list: &str) -> HashMap<String, CooldownConfig> {
+    let mut cooldowns = HashMap::new();
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let mut parts = entry.split(':');
+        let Some(command) = parts.next() else {
+            continue;
+        };
+        let mut config = CooldownConfig::default();
+        for limit in parts {
+            let Some((scope, secs)) = limit.split_once('=') else {
+                warn!(
+                    "Ignoring cooldown `{}` for {}, expected scope=secs",
+                    limit, command
+                );
+                continue;
+            };
+            let Ok(secs) = secs.trim().parse::<u64>() else {
+                warn!("Ignoring cooldown `{}` for {}, bad seconds", limit, command);
+                continue;
+            };
+            let secs = Some(Duration::from_secs(secs));
+            match scope.trim() {
+                "global" => config.global = secs,
+                "user" => config.user = secs,
+                "guild" => config.guild = secs,
+                "channel" => config.channel = secs,
+                "member" => config.member = secs,
+                other => warn!(
+                    "Ignoring unknown cooldown scope `{}` for {}",
+                    other, command
+                ),
+            }
+        }
+        cooldowns.insert(command.trim().to_owned(), config);
+    }
+    cooldowns
+}
+
+/// sets every command's cooldowns from DEFAULT_COOLDOWNS and COMMAND_COOLDOWNS,
+/// so they're tuned in one place instead of on each command
+pub fn apply(commands: &mut [poise::Command<PoiseData, Error>]) {
+    let mut cooldowns = parse_cooldowns(DEFAULT_COOLDOWNS);
+    cooldowns.extend(parse_cooldowns(
+        &parse_env_opt::<String>("COMMAND_COOLDOWNS").unwrap_or_default(),
+    ));
+    for command in commands {
+        if let Some(config) = cooldowns.remove(&command.name) {
+            command.cooldowns = Mutex::new(CooldownTracker::new(config));
+        }
+    }
+    for command in cooldowns.keys() {
+        warn!("Cooldown set for unknown command /{}", command);
+    }
+}
@@ -1,6 +1,10 @@
 use std::env;
 use std::net::SocketAddr;
 
+use super::auto_cap;
+use super::links;
+use super::rcon_policy::rcon_check;
+use super::settings;
 use super::Context;
 use crate::{Error, Server};
 
@@ -8,53 +12,145 @@ pub mod util;
 use util::*;
 
 mod map;
-pub use map::map;
+pub use map::{changelevel, map, MapCache};
 
+mod connect;
+pub use connect::connect;
+
+mod announce;
+pub use announce::announce;
+
+mod motd;
+pub use motd::setmotd;
+
+mod demo;
+pub use demo::demo;
+
+mod alias;
+pub use alias::alias;
+
+mod schedule;
+pub use schedule::mapschedule;
+
+mod deleted;
+pub use deleted::deletedsearch;
+
+mod media;
+pub use media::{mediacooldown, mediaexempt};
+
+mod owner;
+pub use owner::{rcon_as, restart_bot, steamid_cache, togglecommand};
+
+mod health;
+pub use health::health;
+
+mod stats;
+pub use stats::{playtime, population, top};
+
+mod link;
+pub use link::{forcelink, forceunlink, steamlink, syncgroup, unlink};
+
+mod feedback;
+pub use feedback::{feedback_list, feedback_reply, feedback_whois};
+
+mod vote;
+pub use vote::{nominate, rtv};
+
+mod respawn;
+pub use respawn::{parse_respawn_presets, respawntimes, RespawnPreset};
+
+mod scrim;
+pub use scrim::{matchend, matchsetup, MatchState};
+
+mod mod_action;
+use mod_action::{format_minutes, parse_duration};
+pub use mod_action::{parse_ban_presets, BanPreset};
 mod mods;
 pub use mods::*;
 
-use poise;
 use poise::serenity_prelude as serenity;
 use rand::prelude::*;
-use regex::Regex;
+use tracing::{error, info, warn};
 
-/// Sets the server player limit
+/// Sets the server player limit, or has it follow the population
 #[poise::command(slash_command)]
 pub async fn playercap(
     ctx: Context<'_>,
     #[description = "The server to query"]
     #[autocomplete = "servers_autocomplete"]
     server: SocketAddr,
-    #[description = "The player cap 24 <= p <= 32"] count: u8,
+    #[description = "The player cap 24 <= p <= 32, turns auto off"] count: Option<u8>,
+    #[description = "Move the cap with the population and time of day"] auto: Option<bool>,
 ) -> Result<(), Error> {
-    let re = Regex::new(r#""maxplayers" is "(\d+)""#).unwrap();
-
-    let min = 24;
-    let max = ctx
-        .data()
-        .server(server)?
-        .controller
-        .write()
-        .await
-        .run("maxplayers")
+    let server = ctx.data().server(server)?;
+    let auto_servers = &ctx.data().auto_cap_servers;
+    if let Some(auto) = auto {
+        if auto && server.auto_cap.is_none() {
+            ctx.say(format!("{} has no auto cap schedule set up.", server.name))
+                .await?;
+            return Ok(());
+        }
+        settings::set(
+            &ctx.data().pool,
+            &settings::auto_cap(server.addr),
+            &auto.to_string(),
+        )
         .await?;
-    let max = re
-        .captures(&max)
-        .and_then(|caps| caps[1].parse::<u8>().ok())
-        .unwrap_or(25)
-        - 1;
-
-    let visible = count.max(min).min(max);
-    let reserved = max - visible;
-    let cmd = format!(
-        "sm_reserved_slots {}; sv_visiblemaxplayers {};",
-        reserved, visible
-    );
-    rcon_and_reply(ctx, Some(server), cmd).await
+        if auto {
+            auto_servers.write().await.insert(server.addr);
+        } else {
+            auto_servers.write().await.remove(&server.addr);
+        }
+        info!(
+            "{} turned {} auto cap {}",
+            ctx.author().tag(),
+            server.name,
+            if auto { "on" } else { "off" }
+        );
+        if count.is_none() {
+            ctx.say(if auto {
+                format!(
+                    "Auto cap is on for {}, it follows the player count from the next check.",
+                    server.name
+                )
+            } else {
+                format!("Auto cap is off for {}.", server.name)
+            })
+            .await?;
+            return Ok(());
+        }
+    }
+    let Some(count) = count else {
+        let auto = auto_servers.read().await.contains(&server.addr);
+        ctx.say(format!(
+            "Auto cap is {} for {}. Give a count to set the cap by hand.",
+            if auto { "on" } else { "off" },
+            server.name
+        ))
+        .await?;
+        return Ok(());
+    };
+
+    ctx.defer().await?;
+    // a cap set by hand would be undone on the next check otherwise
+    let was_auto = auto_servers.write().await.remove(&server.addr);
+    if was_auto {
+        settings::set(&ctx.data().pool, &settings::auto_cap(server.addr), "false").await?;
+    }
+    let max = auto_cap::hard_max(server).await?;
+    let visible = count.max(auto_cap::MIN_CAP).min(max);
+    let note = was_auto.then(|| format!("Auto cap turned off for {}", server.name));
+    rcon_and_reply_with_note(
+        ctx,
+        Some(server.addr),
+        auto_cap::cap_command(max, visible),
+        note,
+    )
+    .await
 }
 
 /// Sends an RCON command to the server.
-#[poise::command(slash_command)]
+#[poise::command(slash_command, check = "rcon_check")]
 pub async fn rcon(
     ctx: Context<'_>,
     #[description = "The server to query"]
@@ -63,9 +159,50 @@ pub async fn rcon(
     #[description = "The command to send."] cmd: String,
     #[description = "Hide the reply?"] hide_reply: Option<bool>,
 ) -> Result<(), Error> {
-    let reply = rcon_user_output(&output_servers(ctx, server)?, cmd).await;
+    defer_and_reply(ctx, server, cmd, hide_reply.unwrap_or(false)).await
+}
+
+/// Sends an RCON command to several servers, e.g. to restart them together
+#[poise::command(slash_command, check = "rcon_check")]
+pub async fn rcon_multi(
+    ctx: Context<'_>,
+    #[description = "The servers, separated by commas"]
+    #[autocomplete = "server_list_autocomplete"]
+    servers: String,
+    #[description = "The command to send."] cmd: String,
+    #[description = "Hide the reply?"] hide_reply: Option<bool>,
+) -> Result<(), Error> {
+    let servers = listed_servers(ctx, &servers)?;
     let hide_reply = hide_reply.unwrap_or(false);
-    ctx.send(|m| m.ephemeral(hide_reply).content(reply)).await?;
+    if hide_reply {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
+    let headers: Vec<String> = servers
+        .iter()
+        .map(|s| format!("**{} {}**\n", s.emoji, s.name))
+        .collect();
+    // every server gets its header, the outputs share what's left of the message
+    let fixed: usize = headers
+        .iter()
+        .map(|h| h.chars().count() + "``````\n".len())
+        .sum();
+    let per_server = MAX_MESSAGE_LEN.saturating_sub(fixed) / servers.len().max(1);
+    let sections: Vec<String> = headers
+        .into_iter()
+        .zip(rcon_each(&servers, &cmd).await)
+        .map(|(header, (_, result))| {
+            let output = match result {
+                Ok(output) if output.trim().is_empty() => ":white_check_mark:".to_owned(),
+                Ok(output) => format!("```{}```", truncate(&output, per_server)),
+                Err(e) => format!(":x: {}", truncate(&e.to_string(), per_server)),
+            };
+            format!("{}{}", header, output)
+        })
+        .collect();
+    ctx.send(|m| m.ephemeral(hide_reply).content(sections.join("\n")))
+        .await?;
     Ok(())
 }
 
@@ -83,76 +220,167 @@ pub async fn snipers(
         "sm_classrestrict_blu_snipers {0}; sm_classrestrict_red_snipers {0}",
         limit
     );
-    let reply = rcon_user_output(&output_servers(ctx, server)?, cmd).await;
-    let hide_reply = hide_reply.unwrap_or(false);
-    ctx.send(|m| m.ephemeral(hide_reply).content(reply)).await?;
+    defer_and_reply(ctx, server, cmd, hide_reply.unwrap_or(false)).await
+}
+
+/// posts feedback to a channel, as a thread of its own if `thread` is set.
+/// if the thread can't be started on a text channel the message is left as is.
+/// returns the channel and id of the posted message
+async fn post_feedback(
+    ctx: Context<'_>,
+    channel: serenity::ChannelId,
+    thread: bool,
+    embed: serenity::CreateEmbed,
+    title: String,
+) -> Result<(serenity::ChannelId, serenity::MessageId), Error> {
+    let kind = channel.to_channel(ctx).await?.guild().map(|c| c.kind);
+    // serenity 0.11 doesn't know forum channels, they come through as Unknown.
+    // it has no forum post builder either, but a forum post is just a thread
+    // created on the channel with a starter message
+    if thread && kind == Some(serenity::ChannelType::Unknown) {
+        let post = serde_json::json!({
+            "name": title,
+            "message": { "embeds": [serenity::json::hashmap_to_json_map(embed.0)] },
+        });
+        let post = post.as_object().ok_or("Malformed forum post")?;
+        let post = ctx
+            .serenity_context()
+            .http
+            .create_private_thread(channel.0, post)
+            .await?;
+        // the starter message of a forum post has the post's id
+        return Ok((post.id, serenity::MessageId(post.id.0)));
+    }
 
+    let msg = channel.send_message(ctx, |m| m.set_embed(embed)).await?;
+    if thread {
+        if let Err(e) = channel
+            .create_public_thread(ctx, msg.id, |t| t.name(&title))
+            .await
+        {
+            warn!("Could not start a feedback thread: {:?}", e);
+        }
+    }
+    Ok((channel, msg.id))
+}
+
+/// adds the vote reactions to posted feedback and remembers the message they're on
+async fn open_feedback_vote(
+    ctx: Context<'_>,
+    id: u64,
+    channel: serenity::ChannelId,
+    message: serenity::MessageId,
+) -> Result<(), Error> {
+    super::feedback::set_message(&ctx.data().pool, id, message).await?;
+    for vote in [super::feedback::UPVOTE, super::feedback::DOWNVOTE] {
+        ctx.http()
+            .create_reaction(
+                channel.0,
+                message.0,
+                &serenity::ReactionType::Unicode(vote.to_owned()),
+            )
+            .await?;
+    }
     Ok(())
 }
 
-/// Sends anonymous feedback to the server owner.
+/// Sends feedback to the server owner, anonymously unless you choose otherwise.
 #[poise::command(slash_command)]
 pub async fn feedback(
     ctx: Context<'_>,
     #[description = "The feedback to share."] msg: String,
     #[description = "An optional attachment"] attachment: Option<serenity::Attachment>,
+    #[description = "Hide who sent it, on by default"] anonymous: Option<bool>,
 ) -> Result<(), Error> {
-    // get the owner id in the env file
-    let Ok(owner_id) = env::var("FEEDBACK_USER") else {
-        poise::send_reply(ctx, |m| {
-            m.ephemeral(true)
-                .content("Feedback is not configured properly! Message an admin.")
-        })
-        .await?;
-        return Ok(());
+    let anonymous = anonymous.unwrap_or(true);
+    let title: String = format!("feedback: {}", msg.lines().next().unwrap_or_default())
+        .chars()
+        .take(100)
+        .collect();
+    // sealed so staff can reply with /feedback_reply, they never see who sent it
+    let sealed_author = match &ctx.data().feedback_seal {
+        Some(seal) => seal.seal(ctx.author().id).map_or_else(
+            |e| {
+                error!("Could not seal a feedback author: {:?}", e);
+                None
+            },
+            Some,
+        ),
+        None => None,
     };
+    let id = match super::feedback::insert_feedback(&ctx.data().pool, sealed_author, &msg).await {
+        Ok(id) => Some(id),
+        Err(e) => {
+            error!("Could not store feedback: {:?}", e);
+            None
+        }
+    };
+    let mut embed = serenity::CreateEmbed::default();
+    if anonymous {
+        embed.title("anon feedback");
+    } else {
+        let author = ctx.author();
+        embed
+            .title("feedback")
+            .author(|a| a.name(author.tag()).icon_url(author.face()));
+    }
+    embed.description(msg);
+    if let Some(id) = id {
+        embed.footer(|f| f.text(format!("#{}, reply with /feedback_reply", id)));
+    }
+    if let Some(attachment) = attachment {
+        embed.image(attachment.url);
+    }
 
-    // get the owner
-    let recip = serenity::UserId(owner_id.parse()?);
-    let dm_channel = recip.create_dm_channel(ctx).await?;
-    dm_channel
-        .send_message(ctx, |m| {
-            m.embed(|e| {
-                let mut r = e.title("anon feedback").description(msg);
-
-                if let Some(attachment) = attachment {
-                    r = r.image(attachment.url);
+    // a feedback channel takes priority over DMing the owner
+    if let Some(channel) = crate::parse_env_opt::<u64>("FEEDBACK_CHANNEL_ID") {
+        let thread = crate::parse_env_opt("FEEDBACK_THREADS").unwrap_or(false);
+        match post_feedback(ctx, serenity::ChannelId(channel), thread, embed, title).await {
+            Ok((channel, message)) => {
+                if let Some(id) = id {
+                    if let Err(e) = open_feedback_vote(ctx, id, channel, message).await {
+                        warn!("Could not set up voting on feedback #{}: {:?}", id, e);
+                    }
                 }
-
-                r
+            }
+            Err(e) => {
+                warn!("Could not post feedback: {:?}", e);
+                poise::send_reply(ctx, |m| {
+                    m.ephemeral(true)
+                        .content("Feedback could not be delivered! Message an admin.")
+                })
+                .await?;
+                return Ok(());
+            }
+        }
+    } else {
+        // get the owner id in the env file
+        let Ok(owner_id) = env::var("FEEDBACK_USER") else {
+            poise::send_reply(ctx, |m| {
+                m.ephemeral(true)
+                    .content("Feedback is not configured properly! Message an admin.")
             })
-        })
-        .await?;
+            .await?;
+            return Ok(());
+        };
+
+        // get the owner
+        let recip = serenity::UserId(owner_id.parse()?);
+        let dm_channel = recip.create_dm_channel(ctx).await?;
+        dm_channel.send_message(ctx, |m| m.set_embed(embed)).await?;
+    }
 
     poise::send_reply(ctx, |m| {
-        m.ephemeral(true).content("Feedback anonymously sent!")
+        m.ephemeral(true).content(if anonymous {
+            "Feedback anonymously sent!"
+        } else {
+            "Feedback sent!"
+        })
     })
     .await?;
     Ok(())
 }
 
-/// Set / Get the status of the respawn timers ( resets on map change )
-#[poise::command(slash_command)]
-pub async fn respawntimes(
-    ctx: Context<'_>,
-    #[description = "The server to query"]
-    #[autocomplete = "servers_autocomplete"]
-    server: Option<SocketAddr>,
-    #[description = "Set to instant respawn"] instant: Option<bool>,
-) -> Result<(), Error> {
-    let cmd: String = match instant {
-        None => format!("mp_disable_respawn_times"),
-        Some(instant) => format!(
-            "mp_disable_respawn_times {}",
-            if instant { "1" } else { "0" }
-        ),
-    };
-    let reply = rcon_user_output(&output_servers(ctx, server)?, cmd).await;
-    ctx.send(|m| m.content(reply)).await?;
-
-    Ok(())
-}
-
 /// Request that people join you in a server
 #[poise::command(slash_command)]
 pub async fn seeder(
@@ -165,6 +393,7 @@ pub async fn seeder(
     // check cooldown
     match ctx.data().can_seed(server).await {
         Ok(()) => (),
+        Err(_) if is_exempt(ctx).await => (),
         Err(time_left) => {
             let now = chrono::Utc::now();
             ctx.send(|m| {
@@ -180,6 +409,7 @@ pub async fn seeder(
 
     let server_addr = server;
     let server = ctx.data().server(server)?;
+    ctx.defer().await?;
 
     let mut rcon = server.controller.write().await;
     let status = rcon.status().await?;
@@ -196,7 +426,7 @@ pub async fn seeder(
         return Ok(());
     }
 
-    let seeder_role = ctx.data().seeder_role;
+    let seeder_role = ctx.data().seeder_role(server_addr);
 
     // send seed
     ctx.send(|m| {
@@ -216,28 +446,130 @@ pub async fn seeder(
     })
     .await?;
     // reset cooldown
-    ctx.data().reset_seed_cooldown(server_addr).await;
+    if let Err(e) = ctx.data().reset_seed_cooldown(server_addr).await {
+        error!("Could not save seeder cooldown: {:?}", e);
+    }
+
+    Ok(())
+}
 
+/// Change how long servers wait between seeder pings
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+pub async fn seeder_cooldown(
+    ctx: Context<'_>,
+    #[description = "How long, e.g. 90m, 4h or 1d"] duration: String,
+) -> Result<(), Error> {
+    let minutes = parse_duration(&duration)?;
+    if minutes == 0 {
+        return Err("The cooldown has to be longer than that.".into());
+    }
+    settings::set(
+        &ctx.data().pool,
+        settings::SEED_COOLDOWN_MINUTES,
+        &minutes.to_string(),
+    )
+    .await?;
+    *ctx.data().seed_cooldown.write().await = chrono::Duration::minutes(minutes.into());
+    info!(
+        "{} set the seeder cooldown to {}",
+        ctx.author().tag(),
+        format_minutes(minutes)
+    );
+    ctx.say(format!(
+        "Servers can now be seeded every {}.",
+        format_minutes(minutes)
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Toggle the seeder role on yourself to get (or stop getting) seed pings
+#[poise::command(slash_command, ephemeral)]
+pub async fn seedrole(
+    ctx: Context<'_>,
+    #[description = "Only get pings for this server. Leave empty for the general seeder role"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+) -> Result<(), Error> {
+    let seeder_role = match server {
+        Some(server) => ctx.data().seeder_role(server),
+        None => ctx.data().seeder_role,
+    };
+    let guild = ctx.data().guild_id;
+    let user = ctx.author();
+    if has_role(ctx, seeder_role).await {
+        ctx.http()
+            .remove_member_role(guild.0, user.id.0, seeder_role.0, Some("/seedrole"))
+            .await?;
+        info!("{} opted out of seeder pings", user.tag());
+        ctx.say("You won't get seeder pings anymore.").await?;
+    } else {
+        ctx.http()
+            .add_member_role(guild.0, user.id.0, seeder_role.0, Some("/seedrole"))
+            .await?;
+        info!("{} opted into seeder pings", user.tag());
+        ctx.say("You'll now get pinged when a server needs seeding :3")
+            .await?;
+    }
     Ok(())
 }
 
 /// SteamID.uk discord command.
-#[poise::command(slash_command, global_cooldown = 10)]
+#[poise::command(slash_command)]
 pub async fn lookup(
     ctx: Context<'_>,
-    #[description = "SteamID, Steam2, Steam3, or vanity URL. Separate multiple by commas."]
+    #[description = "SteamID, Steam2, Steam3, vanity or profile URL. Separate multiple by commas."]
     #[autocomplete = "steam_id_autocomplete"]
-    query: String,
+    query: Option<String>,
+    #[description = "A discord user with a linked steam account"] user: Option<serenity::User>,
+    #[description = "Skip cached results"] refresh: Option<bool>,
 ) -> Result<(), Error> {
     ctx.defer_ephemeral().await?;
+    let query = match (query, user) {
+        (Some(query), _) => query,
+        (None, Some(user)) => {
+            let Some(steamid) = links::linked_steamid(&ctx.data().pool, user.id).await? else {
+                ctx.say(format!("{} hasn't linked a steam account.", user.tag()))
+                    .await?;
+                return Ok(());
+            };
+            steamid
+        }
+        (None, None) => {
+            ctx.say("Give a steam id or a user to look up.").await?;
+            return Ok(());
+        }
+    };
     let client = &ctx.data().client;
-    let data = client.lookup(&query).await?;
+    let refresh = refresh.unwrap_or(false);
+    let data = match client.lookup_cached(&query, refresh).await {
+        Ok(data) => data,
+        Err(e) => {
+            ctx.say(format!("No steam account found for `{}`: {}", query, e))
+                .await?;
+            return Ok(());
+        }
+    };
     // fetch important info
+    let ids: Vec<&str> = data.iter().map(|u| u.steamid64.as_str()).collect();
+    let accounts = match client.accounts_cached(&ids, refresh).await {
+        Ok(accounts) => accounts,
+        Err(e) => {
+            warn!("Could not fetch steam profiles: {:?}", e);
+            Default::default()
+        }
+    };
 
     ctx.send(|m| {
         m.content(format!("Results for query: `{}`", query));
         for user in &data {
-            m.embed(|e| user.populate_embed(e));
+            m.embed(|e| {
+                user.populate_embed(e);
+                if let Some(account) = accounts.get(&user.steamid64) {
+                    account.populate_embed(e);
+                }
+                e
+            });
         }
         m.ephemeral(true)
     })
@@ -264,6 +596,11 @@ pub async fn status(
     servers.sort_by_key(|s| &s.name);
 
     let show_uids = show_uids.unwrap_or(false);
+    if show_uids {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
 
     let mut output = String::new();
     for server in servers {
@@ -289,6 +626,470 @@ pub async fn status(
     Ok(())
 }
 
+/// Shows the hostname, map, players, tickrate and uptime of servers, with how to join
+#[poise::command(slash_command)]
+pub async fn serverinfo(
+    ctx: Context<'_>,
+    #[description = "The server to show. Leave empty for all"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+) -> Result<(), Error> {
+    let mut servers = output_servers(ctx, server)?;
+    servers.sort_by_key(|s| &s.name);
+    ctx.defer().await?;
+
+    let infos = futures::future::join_all(servers.iter().map(|server| async move {
+        let info: Result<_, Error> = async { lock_rcon(server).await?.info().await }.await;
+        info
+    }))
+    .await;
+
+    ctx.send(|m| {
+        for (server, info) in servers.iter().zip(infos) {
+            m.embed(|e| {
+                let Ok(info) = info else {
+                    return e
+                        .title(format!("{} {}", server.emoji, server.name))
+                        .description("unreachable :(")
+                        .color(serenity::Color::RED);
+                };
+                let players = match info.max_players {
+                    Some(max) => format!("{}/{}", info.players, max),
+                    None => info.players.to_string(),
+                };
+                let join = if server.private {
+                    "private, use /connect".to_owned()
+                } else {
+                    format!("steam://connect/{}\n`connect {}`", server.addr, server.addr)
+                };
+                e.title(format!("{} {}", server.emoji, info.hostname))
+                    .field("Map", format!("`{}`", info.map), true)
+                    .field("Players", players, true)
+                    .field(
+                        "Tickrate",
+                        info.tickrate
+                            .map_or("?".to_owned(), |t| format!("{:.0}", t)),
+                        true,
+                    )
+                    .field(
+                        "Uptime",
+                        info.uptime
+                            .as_ref()
+                            .map_or("?".to_owned(), crate::tf2_rcon::hhmmss),
+                        true,
+                    )
+                    .field("Join", join, false)
+            });
+        }
+        m
+    })
+    .await?;
+    Ok(())
+}
+
+/// a `status` player table lined up for a code block
+fn player_table(rows: &[crate::tf2_rcon::PlayerRow]) -> String {
+    let mut table = format!(
+        "{:>4} {:<20} {:<20} {:>8} {:>4} {}\n",
+        "id", "name", "steamid", "time", "ping", "state"
+    );
+    for row in rows {
+        // backticks would end the code block
+        let name: String = row.name.replace('`', "'").chars().take(20).collect();
+        table += &format!(
+            "{:>4} {:<20} {:<20} {:>8} {:>4} {}\n",
+            row.userid,
+            name,
+            row.steamid.as_deref().unwrap_or("BOT"),
+            row.connected
+                .as_ref()
+                .map(crate::tf2_rcon::hhmmss)
+                .unwrap_or_default(),
+            row.ping.map(|p| p.to_string()).unwrap_or_default(),
+            row.state
+        );
+    }
+    table
+}
+
+/// Shows everyone on a server in a table, bots included
+#[poise::command(slash_command)]
+pub async fn players(
+    ctx: Context<'_>,
+    #[description = "The server to list. Leave empty for all"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+) -> Result<(), Error> {
+    let mut servers = output_servers(ctx, server)?;
+    servers.sort_by_key(|s| &s.name);
+    ctx.defer().await?;
+    let results = rcon_each(&servers, "status").await;
+
+    ctx.send(|m| {
+        for (server, (_, result)) in servers.iter().zip(&results) {
+            m.embed(|e| {
+                e.title(format!("{} {}", server.emoji, server.name));
+                match result {
+                    Ok(output) => {
+                        let rows = crate::tf2_rcon::parse_status(output);
+                        let humans = rows.iter().filter(|r| !r.is_bot()).count();
+                        e.description(format!("```\n{}```", player_table(&rows)))
+                            .footer(|f| {
+                                f.text(format!("{} players, {} bots", humans, rows.len() - humans))
+                            })
+                    }
+                    Err(err) => e.description(err.to_string()).color(serenity::Color::RED),
+                }
+            });
+        }
+        m
+    })
+    .await?;
+    Ok(())
+}
+
+/// Find which servers a player is on right now
+#[poise::command(slash_command)]
+pub async fn find(
+    ctx: Context<'_>,
+    #[description = "Part of their name, or their steam id"] player: String,
+) -> Result<(), Error> {
+    let needle = player.trim().trim_start_matches('#').to_lowercase();
+    ctx.defer().await?;
+    let mut servers: Vec<&Server> = ctx.data().servers.values().collect();
+    servers.sort_by_key(|s| &s.name);
+
+    // every server is asked at once so an offline one doesn't hold up the rest
+    let states = futures::future::join_all(servers.iter().map(|server| async move {
+        let state: Result<_, Error> = async { lock_rcon(server).await?.status().await }.await;
+        state
+    }))
+    .await;
+
+    let mut lines = vec![];
+    let mut found = false;
+    for (server, state) in servers.iter().zip(states) {
+        let Ok(state) = state else {
+            lines.push(format!("{} {} unreachable", server.emoji, server.name));
+            continue;
+        };
+        for p in state.players {
+            if p.id.to_lowercase() == needle || p.name.to_lowercase().contains(&needle) {
+                found = true;
+                lines.push(format!(
+                    "{} {}: `{}` {}",
+                    server.emoji,
+                    server.name,
+                    crate::logs::safe_strip(&p.name),
+                    p.id
+                ));
+            }
+        }
+    }
+    if !found {
+        lines.insert(0, format!("Nobody matching `{}` is online.", player));
+    }
+    ctx.say(lines.join("\n")).await?;
+    Ok(())
+}
+
+/// Quick look at one online player: steamid, K/D on this map, time connected and ping
+#[poise::command(slash_command)]
+pub async fn playerinfo(
+    ctx: Context<'_>,
+    #[description = "The server to look on. Leave empty for all"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "The player to look up"]
+    #[autocomplete = "users_autocomplete"]
+    username: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let servers = output_servers(ctx, server)?;
+    // every server is asked at once so an offline one doesn't hold up the rest
+    let states = futures::future::join_all(servers.iter().map(|server| async move {
+        let state: Result<_, Error> = async { lock_rcon(server).await?.status().await }.await;
+        state
+    }))
+    .await;
+    for (server, state) in servers.iter().zip(states) {
+        let Ok(state) = state else {
+            continue;
+        };
+        let Some(player) = state.players.into_iter().find(|p| p.name == username) else {
+            continue;
+        };
+        let kd = match ctx
+            .data()
+            .map_stats
+            .read()
+            .await
+            .kd(server.addr, &player.id)
+        {
+            Some((kills, deaths)) => format!(
+                "{}/{} ({:.2})",
+                kills,
+                deaths,
+                kills as f64 / deaths.max(1) as f64
+            ),
+            None => "0/0".to_owned(),
+        };
+        ctx.send(|m| {
+            m.embed(|e| {
+                e.title(crate::logs::safe_strip(&player.name))
+                    .field("Server", format!("{} {}", server.emoji, server.name), true)
+                    .field("SteamID", &player.id, true)
+                    .field("K/D", kd, true)
+                    .field(
+                        "Connected",
+                        crate::tf2_rcon::hhmmss(&player.connected),
+                        true,
+                    )
+                    .field(
+                        "Ping",
+                        player
+                            .ping
+                            .map(|p| format!("{}ms", p))
+                            .unwrap_or("?".to_owned()),
+                        true,
+                    )
+            })
+        })
+        .await?;
+        return Ok(());
+    }
+    ctx.say(format!("`{}` is not online.", username)).await?;
+    Ok(())
+}
+
+/// Shows the latest in-game chat on a server, for context on reports
+#[poise::command(
+    slash_command,
+    ephemeral,
+    default_member_permissions = "MANAGE_MESSAGES"
+)]
+pub async fn serverchat(
+    ctx: Context<'_>,
+    #[description = "The server to look at"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "How many lines to show, 20 by default"]
+    #[min = 1]
+    #[max = 100]
+    lines: Option<usize>,
+) -> Result<(), Error> {
+    let server = ctx.data().server(server)?;
+    let lines = ctx
+        .data()
+        .chat_history
+        .read()
+        .await
+        .recent(server.addr, lines.unwrap_or(20));
+    if lines.is_empty() {
+        ctx.say(format!(
+            "{} No chat seen since the bot started.",
+            server.emoji
+        ))
+        .await?;
+        return Ok(());
+    }
+    let lines: Vec<String> = lines
+        .into_iter()
+        .map(|(at, line)| format!("{} {}", at.format("%H:%M:%S"), line))
+        .collect();
+    // newest lines win if it doesn't all fit
+    let mut shown: Vec<String> = vec![];
+    let mut len = 0;
+    for line in lines.into_iter().rev() {
+        len += line.len() + 1;
+        if len > 1900 {
+            break;
+        }
+        shown.push(line);
+    }
+    shown.reverse();
+    ctx.say(format!("{} ```\n{}```", server.emoji, shown.join("\n")))
+        .await?;
+    Ok(())
+}
+
+/// Lists the SourceMod plugins on a server, flagging any that failed to load
+#[poise::command(slash_command)]
+pub async fn plugininfo(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let server = ctx.data().server(server)?;
+    let mut plugins = server.controller.write().await.plugins().await?;
+    if plugins.is_empty() {
+        ctx.say(format!("{} No plugins found.", server.emoji))
+            .await?;
+        return Ok(());
+    }
+    // broken ones first so they can't be missed
+    plugins.sort_by_key(|p| (!p.failed(), p.index));
+
+    let failed = plugins.iter().filter(|p| p.failed()).count();
+    let header = if failed > 0 {
+        format!(
+            "{} {} plugins, :x: {} failed to load",
+            server.emoji,
+            plugins.len(),
+            failed
+        )
+    } else {
+        format!("{} {} plugins, all loaded", server.emoji, plugins.len())
+    };
+
+    let lines: Vec<String> = plugins
+        .iter()
+        .map(|p| {
+            format!(
+                "{} {} {}{}",
+                if p.failed() { "❌" } else { "  " },
+                p.name,
+                p.version.as_deref().unwrap_or(""),
+                if p.status == "Running" || p.failed() {
+                    "".to_owned()
+                } else {
+                    format!(" <{}>", p.status)
+                }
+            )
+        })
+        .collect();
+
+    let mut pages: Vec<String> = vec![];
+    for chunk in lines.chunks(20) {
+        pages.push(format!("{}\n```{}```", header, chunk.join("\n")));
+    }
+    let pages: Vec<&str> = pages.iter().map(|p| p.as_str()).collect();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
+
+/// cvars audited by /cvardiff unless CVAR_AUDIT lists others
+const DEFAULT_AUDITED_CVARS: &[&str] = &[
+    "sv_cheats",
+    "sv_gravity",
+    "sv_allow_wait_command",
+    "sv_pure",
+    "sv_alltalk",
+    "mp_friendlyfire",
+    "mp_timelimit",
+    "mp_winlimit",
+    "mp_maxrounds",
+    "mp_autoteambalance",
+    "mp_teams_unbalance_limit",
+    "mp_disable_respawn_times",
+    "tf_weapon_criticals",
+    "tf_use_fixed_weaponspreads",
+    "tf_damage_disablespread",
+    "host_timescale",
+    "phys_timescale",
+];
+
+/// Lists audited cvars that differ from their defaults
+#[poise::command(slash_command)]
+pub async fn cvardiff(
+    ctx: Context<'_>,
+    #[description = "The server to audit"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let server = ctx.data().server(server)?;
+    let cvars: Vec<String> = match env::var("CVAR_AUDIT") {
+        Ok(list) if !list.trim().is_empty() => list
+            .split(',')
+            .map(|c| c.trim().to_owned())
+            .filter(|c| !c.is_empty())
+            .collect(),
+        _ => DEFAULT_AUDITED_CVARS
+            .iter()
+            .map(|c| c.to_string())
+            .collect(),
+    };
+
+    let mut changed: Vec<String> = vec![];
+    let mut unknown: Vec<String> = vec![];
+    let mut rcon = server.controller.write().await;
+    for cvar in &cvars {
+        match rcon.convar_with_default(&escape_rcon_arg(cvar)).await {
+            Ok((value, Some(default))) if value != default => {
+                changed.push(format!("{} = {} (default {})", cvar, value, default))
+            }
+            Ok((_, Some(_))) => (),
+            _ => unknown.push(cvar.clone()),
+        }
+    }
+    drop(rcon);
+
+    let mut msg = if changed.is_empty() {
+        format!(
+            "{} All {} audited cvars are at their defaults.",
+            server.emoji,
+            cvars.len() - unknown.len()
+        )
+    } else {
+        format!(
+            "{} {} cvars differ from default:\n```{}```",
+            server.emoji,
+            changed.len(),
+            changed.join("\n")
+        )
+    };
+    if !unknown.is_empty() {
+        msg += &format!("\nCouldn't read a default for: `{}`", unknown.join("`, `"));
+    }
+    ctx.say(msg).await?;
+    Ok(())
+}
+
+/// Dumps the cvars a server exposes over the A2S query protocol
+#[poise::command(slash_command)]
+pub async fn serverquery(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "Only show cvars containing this"] filter: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let server = ctx.data().server(server)?;
+    let filter = filter.unwrap_or_default().to_lowercase();
+    let lines: Vec<String> = crate::a2s::query_rules(server.addr)
+        .await?
+        .into_iter()
+        .filter(|(name, _)| name.to_lowercase().contains(&filter))
+        .map(|(name, value)| format!("{} = {}", name, value))
+        .collect();
+    if lines.is_empty() {
+        ctx.say(format!("{} No matching cvars.", server.emoji))
+            .await?;
+        return Ok(());
+    }
+
+    // keep each page well under the message limit
+    let mut pages: Vec<String> = vec![];
+    let mut page = String::new();
+    for line in lines {
+        if page.len() + line.len() > 1800 {
+            pages.push(format!("{} ```{}```", server.emoji, page));
+            page.clear();
+        }
+        page += &line;
+        page += "\n";
+    }
+    pages.push(format!("{} ```{}```", server.emoji, page));
+
+    let pages: Vec<&str> = pages.iter().map(|p| p.as_str()).collect();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
+
 /// Pick a random user with the given role
 #[poise::command(slash_command)]
 pub async fn reacted_users(
@@ -309,7 +1110,7 @@ pub async fn reacted_users(
         {
             Ok(users) => users,
             Err(e) => {
-                println!("Error fetching users: {:?}", e);
+                warn!("Error fetching users: {:?}", e);
                 break;
             }
         };
@@ -395,7 +1196,7 @@ pub async fn private_add(ctx: Context<'_>, user: serenity::User) -> Result<(), E
 }
 
 /// Meow (suppawters only)
-#[poise::command(slash_command, channel_cooldown = 4)]
+#[poise::command(slash_command)]
 pub async fn meow(ctx: Context<'_>) -> Result<(), Error> {
     let meows = [
         "meow!! :revolving_hearts:",
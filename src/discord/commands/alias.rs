@@ -0,0 +1,187 @@
+use std::net::SocketAddr;
+
+use poise::AutocompleteChoice;
+use regex::{Captures, Regex};
+use tracing::info;
+
+use crate::discord::rcon_policy::allow_rcon;
+use crate::discord::Context;
+use crate::Error;
+
+use super::util::{escape_rcon_arg, rcon_and_reply, servers_autocomplete};
+
+/// fills `{0}`, `{1}`, ... in the alias with the given args. errors if one is missing
+fn expand_alias(template: &str, args: &[&str]) -> Result<String, String> {
+    let placeholder = Regex::new(r"\{(\d+)\}").unwrap();
+    let mut missing = None;
+    let cmd = placeholder.replace_all(template, |caps: &Captures| {
+        let index: usize = caps[1].parse().unwrap_or(usize::MAX);
+        match args.get(index) {
+            Some(arg) => escape_rcon_arg(arg),
+            None => {
+                missing = missing.max(Some(index));
+                String::new()
+            }
+        }
+    });
+    match missing {
+        Some(index) => Err(format!(
+            "This alias needs at least {} arguments.",
+            index.saturating_add(1)
+        )),
+        None => Ok(cmd.into_owned()),
+    }
+}
+
+async fn aliases_autocomplete(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice<String>> {
+    let Ok(aliases) = sqlx::query!(r#"SELECT `name` FROM `rcon_aliases`"#)
+        .fetch_all(&ctx.data().pool)
+        .await
+    else {
+        return vec![];
+    };
+    aliases
+        .into_iter()
+        .filter(|a| a.name.contains(partial))
+        .take(25)
+        .map(|a| AutocompleteChoice {
+            name: a.name.clone(),
+            value: a.name,
+        })
+        .collect()
+}
+
+/// Named shortcuts for RCON commands you run often
+#[poise::command(
+    slash_command,
+    subcommands("run", "add", "remove", "list"),
+    subcommand_required,
+    default_member_permissions = "BAN_MEMBERS"
+)]
+pub async fn alias(_: Context<'_>) -> Result<(), Error> {
+    Ok(()) // never run
+}
+
+/// runs an alias, filling its placeholders with the args
+#[poise::command(slash_command)]
+async fn run(
+    ctx: Context<'_>,
+    #[description = "The alias to run"]
+    #[autocomplete = "aliases_autocomplete"]
+    name: String,
+    #[description = "The server to run it on. Leave empty for all"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "Space separated values for {0}, {1}, ..."] args: Option<String>,
+) -> Result<(), Error> {
+    let Some(alias) = sqlx::query!(
+        r#"
+        SELECT `command` FROM `rcon_aliases` WHERE `name` = ?
+    "#,
+        name
+    )
+    .fetch_optional(&ctx.data().pool)
+    .await?
+    else {
+        ctx.say(format!("There's no alias called `{}`.", name))
+            .await?;
+        return Ok(());
+    };
+    let args = args.unwrap_or_default();
+    let args: Vec<&str> = args.split_whitespace().collect();
+    let cmd = match expand_alias(&alias.command, &args) {
+        Ok(cmd) => cmd,
+        Err(why) => {
+            ctx.say(why).await?;
+            return Ok(());
+        }
+    };
+    // args can change what runs, so the policy sees exactly what gets sent
+    if !allow_rcon(ctx, &cmd).await? {
+        return Ok(());
+    }
+    info!("{} ran alias {}: {}", ctx.author().tag(), name, cmd);
+    rcon_and_reply(ctx, server, cmd).await
+}
+
+/// saves an alias, replacing any with the same name
+#[poise::command(slash_command)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "Short name for the alias"] name: String,
+    #[description = "The RCON command(s), with {0}, {1}, ... for arguments"] command: String,
+) -> Result<(), Error> {
+    let name = name.trim().to_lowercase();
+    if name.is_empty() || name.len() > 64 || name.contains(char::is_whitespace) {
+        ctx.say("Alias names are one word, up to 64 characters.")
+            .await?;
+        return Ok(());
+    }
+    // checked again with the args filled in when it's run
+    if !allow_rcon(ctx, &command).await? {
+        return Ok(());
+    }
+    sqlx::query!(
+        r#"
+		INSERT INTO `rcon_aliases` (`name`, `command`, `created_by`)
+		VALUES (?, ?, ?)
+		ON DUPLICATE KEY UPDATE `command` = ?, `created_by` = ?
+	"#,
+        name,
+        command,
+        ctx.author().id.0.to_string(),
+        command,
+        ctx.author().id.0.to_string()
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+    ctx.say(format!("Saved `{}`: `{}`", name, command)).await?;
+    Ok(())
+}
+
+/// deletes an alias
+#[poise::command(slash_command)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "The alias to delete"]
+    #[autocomplete = "aliases_autocomplete"]
+    name: String,
+) -> Result<(), Error> {
+    let result = sqlx::query!(
+        r#"
+		DELETE FROM `rcon_aliases` WHERE `name` = ?
+	"#,
+        name
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+    ctx.say(if result.rows_affected() > 0 {
+        format!("Deleted `{}`.", name)
+    } else {
+        format!("There's no alias called `{}`.", name)
+    })
+    .await?;
+    Ok(())
+}
+
+/// shows every alias
+#[poise::command(slash_command)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let aliases = sqlx::query!(
+        r#"
+        SELECT `name`, `command` FROM `rcon_aliases` ORDER BY `name`
+    "#
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if aliases.is_empty() {
+        ctx.say("No aliases yet.").await?;
+        return Ok(());
+    }
+    let lines: Vec<String> = aliases
+        .iter()
+        .map(|a| format!("`{}`: `{}`", a.name, a.command))
+        .collect();
+    ctx.say(lines.join("\n")).await?;
+    Ok(())
+}
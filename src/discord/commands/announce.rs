@@ -0,0 +1,74 @@
+use crate::discord::Context;
+use crate::Error;
+
+use poise::serenity_prelude as serenity;
+use tracing::debug;
+
+/// discord refuses to pin more than this many messages in a channel
+const MAX_PINS: usize = 50;
+
+/// Post an announcement, optionally replacing the last pinned one
+#[poise::command(slash_command, default_member_permissions = "MANAGE_MESSAGES")]
+pub async fn announce(
+    ctx: Context<'_>,
+    #[description = "The announcement text"] message: String,
+    #[description = "Channel to post in (defaults to this one)"]
+    #[channel_types("Text", "News")]
+    channel: Option<serenity::GuildChannel>,
+    #[description = "Pin it and unpin the previous announcement?"] pin: Option<bool>,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let channel_id = channel.map(|c| c.id).unwrap_or(ctx.channel_id());
+    let msg = channel_id.send_message(ctx, |m| m.content(message)).await?;
+
+    if !pin.unwrap_or(false) {
+        ctx.say(":white_check_mark:").await?;
+        return Ok(());
+    }
+
+    let pool = &ctx.data().pool;
+    let channel_key = channel_id.0.to_string();
+    let previous = sqlx::query!(
+        r#"
+        SELECT `message_id` FROM `pinned_announcements`
+        WHERE `channel_id` = ?
+    "#,
+        channel_key
+    )
+    .fetch_optional(pool)
+    .await?;
+
+    // unpin the old one first so it frees up a pin slot
+    if let Some(mid) = previous.and_then(|r| r.message_id.parse::<u64>().ok()) {
+        // the old announcement may have been deleted or unpinned by hand
+        if let Err(e) = channel_id.unpin(ctx, mid).await {
+            debug!("Could not unpin the previous announcement: {:?}", e);
+        }
+    }
+
+    if channel_id.pins(ctx).await?.len() >= MAX_PINS {
+        ctx.say(format!(
+            "Posted, but <#{}> already has {} pins so it could not be pinned. Unpin something and pin it manually.",
+            channel_id.0, MAX_PINS
+        ))
+        .await?;
+        return Ok(());
+    }
+    msg.pin(ctx).await?;
+
+    sqlx::query!(
+        r#"
+		INSERT INTO `pinned_announcements` (`channel_id`, `message_id`)
+		VALUES (?, ?)
+		ON DUPLICATE KEY UPDATE `message_id` = ?
+	"#,
+        channel_key,
+        msg.id.0.to_string(),
+        msg.id.0.to_string()
+    )
+    .execute(pool)
+    .await?;
+
+    ctx.say(":white_check_mark: posted & pinned").await?;
+    Ok(())
+}
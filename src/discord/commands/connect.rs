@@ -0,0 +1,71 @@
+use std::net::SocketAddr;
+use tracing::info;
+
+use crate::discord::{connect_tokens, Context};
+use crate::Error;
+
+use super::util::{has_role, servers_autocomplete};
+
+/// Get a temporary password to join a private server
+#[poise::command(slash_command, ephemeral)]
+pub async fn connect(
+    ctx: Context<'_>,
+    #[description = "The server to join"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+) -> Result<(), Error> {
+    let server = ctx.data().server(server)?;
+    if !server.private {
+        ctx.say(format!(
+            "{} is public, just join: `connect {}`",
+            server.emoji, server.addr
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let Some(private_role) = ctx.data().private_role else {
+        ctx.say("Private servers are not configured properly! Message an admin.")
+            .await?;
+        return Ok(());
+    };
+    if !has_role(ctx, private_role).await {
+        ctx.say("You don't have access to this server.").await?;
+        return Ok(());
+    }
+
+    // a new token would change the password out from under the match
+    if ctx
+        .data()
+        .match_state
+        .read()
+        .await
+        .contains_key(&server.addr)
+    {
+        ctx.say(
+            "There's a match on that server right now, ask whoever set it up for the password.",
+        )
+        .await?;
+        return Ok(());
+    }
+
+    let (token, expires_at) =
+        connect_tokens::issue_connect_token(&ctx.data().pool, server, ctx.author().id).await?;
+    info!(
+        "Issued {} a connect token for {}",
+        ctx.author().tag(),
+        server.name
+    );
+
+    ctx.say(format!(
+        "{} steam://connect/{}/{}\n`connect {}; password {}`\nexpires <t:{}:R>, and stops working if someone else requests one. join quick :3",
+        server.emoji,
+        server.addr,
+        token,
+        server.addr,
+        token,
+        expires_at.timestamp()
+    ))
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,54 @@
+use poise::serenity_prelude as serenity;
+
+use crate::discord::{deleted_messages, Context};
+use crate::Error;
+
+/// Search the messages a user deleted recently
+#[poise::command(
+    slash_command,
+    ephemeral,
+    default_member_permissions = "MANAGE_MESSAGES"
+)]
+pub async fn deletedsearch(
+    ctx: Context<'_>,
+    #[description = "Whose deleted messages to show"] user: serenity::User,
+) -> Result<(), Error> {
+    let rows = sqlx::query!(
+        r#"
+        SELECT `channel_id`, `content`, `deleted_at` FROM `deleted_messages`
+        WHERE `author_id` = ? ORDER BY `deleted_at` DESC
+    "#,
+        user.id.0.to_string()
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if rows.is_empty() {
+        ctx.say(format!(
+            "{} hasn't deleted anything in the last {} days.",
+            user.tag(),
+            deleted_messages::retention().num_days()
+        ))
+        .await?;
+        return Ok(());
+    }
+
+    let entries: Vec<String> = rows
+        .into_iter()
+        .map(|r| {
+            let content: String = r.content.chars().take(300).collect();
+            format!(
+                "<t:{}:f> in <#{}>\n> {}",
+                r.deleted_at.timestamp(),
+                r.channel_id,
+                content.replace('\n', "\n> ")
+            )
+        })
+        .collect();
+    let pages: Vec<String> = entries
+        .chunks(5)
+        .map(|chunk| format!("Deleted by **{}**\n{}", user.tag(), chunk.join("\n")))
+        .collect();
+    let pages: Vec<&str> = pages.iter().map(String::as_str).collect();
+    poise::builtins::paginate(ctx, &pages).await?;
+    Ok(())
+}
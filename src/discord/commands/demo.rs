@@ -0,0 +1,84 @@
+use std::net::SocketAddr;
+
+use chrono::Utc;
+
+use crate::discord::Context;
+use crate::Error;
+
+use super::util::{output_servers, rcon_and_reply, servers_autocomplete};
+
+/// SourceTV demo recording, for ban appeal evidence
+#[poise::command(
+    slash_command,
+    subcommands("status", "start", "stop"),
+    subcommand_required,
+    default_member_permissions = "BAN_MEMBERS"
+)]
+pub async fn demo(_: Context<'_>) -> Result<(), Error> {
+    Ok(()) // never run
+}
+
+/// shows whether sourcetv is up and what it is recording
+#[poise::command(slash_command)]
+async fn status(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+) -> Result<(), Error> {
+    let mut outputs: Vec<String> = vec![];
+    for server in output_servers(ctx, server)? {
+        let tv = server.controller.write().await.tv_status().await;
+        outputs.push(match tv {
+            Ok(tv) if !tv.active => format!("{} SourceTV is not active.", server.emoji),
+            Ok(tv) => format!(
+                "{} SourceTV online, `{}` spectators. {}",
+                server.emoji,
+                tv.spectators
+                    .map(|s| s.to_string())
+                    .unwrap_or("?".to_owned()),
+                match tv.recording {
+                    Some(demo) => format!("Recording `{}`", demo),
+                    None => "Not recording.".to_owned(),
+                }
+            ),
+            Err(e) => format!("{} {}", server.emoji, e),
+        });
+    }
+    outputs.sort();
+    ctx.say(outputs.join("\n")).await?;
+    Ok(())
+}
+
+/// starts recording a demo
+#[poise::command(slash_command)]
+async fn start(
+    ctx: Context<'_>,
+    #[description = "The server to record"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "Demo file name. Defaults to the current time"] name: Option<String>,
+) -> Result<(), Error> {
+    // only characters that are safe in both a console argument and a file name
+    let name: String = name
+        .unwrap_or(format!("evidence-{}", Utc::now().format("%Y%m%d-%H%M%S")))
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_'))
+        .collect();
+    if name.is_empty() {
+        ctx.say("That demo name has no usable characters.").await?;
+        return Ok(());
+    }
+    rcon_and_reply(ctx, Some(server), format!("tv_record {}", name)).await
+}
+
+/// stops the demo being recorded
+#[poise::command(slash_command)]
+async fn stop(
+    ctx: Context<'_>,
+    #[description = "The server to stop recording"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+) -> Result<(), Error> {
+    rcon_and_reply(ctx, Some(server), "tv_stoprecord".to_owned()).await
+}
@@ -0,0 +1,138 @@
+use tracing::{info, warn};
+
+use crate::discord::{feedback, Context};
+use crate::Error;
+
+use super::util::has_role;
+
+/// List open feedback to resolve
+#[poise::command(slash_command, ephemeral, default_member_permissions = "BAN_MEMBERS")]
+pub async fn feedback_list(ctx: Context<'_>) -> Result<(), Error> {
+    let open = feedback::open_feedback(&ctx.data().pool).await?;
+    let (embed, components) = feedback::list_message(&open);
+    ctx.send(|m| {
+        m.embed(|e| {
+            *e = embed;
+            e
+        })
+        .components(|c| {
+            *c = components;
+            c
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// Reply to feedback, the submitter gets it in their DMs
+#[poise::command(slash_command, ephemeral, default_member_permissions = "BAN_MEMBERS")]
+pub async fn feedback_reply(
+    ctx: Context<'_>,
+    #[description = "The feedback number, from /feedback_list"] id: u64,
+    #[description = "Your reply"] msg: String,
+    #[description = "Also mark it resolved"] resolve: Option<bool>,
+) -> Result<(), Error> {
+    // authors are only recorded sealed
+    if ctx.data().feedback_seal.is_none() {
+        ctx.say("Replying needs FEEDBACK_KEY to be set up, ask an admin.")
+            .await?;
+        return Ok(());
+    }
+    let pool = &ctx.data().pool;
+    let Some(entry) = feedback::feedback_by_id(pool, id).await? else {
+        ctx.say(format!("There is no feedback #{}.", id)).await?;
+        return Ok(());
+    };
+
+    let Some(author) = entry.author(ctx.data().feedback_seal.as_ref())? else {
+        ctx.say("Who sent that feedback wasn't recorded, so it can't be replied to.")
+            .await?;
+        return Ok(());
+    };
+    let quoted: String = entry.message.chars().take(1000).collect();
+    let sent = match author.create_dm_channel(ctx).await {
+        Ok(dm) => {
+            dm.send_message(ctx, |m| {
+                m.embed(|e| {
+                    e.title(format!("Reply to your feedback #{}", id))
+                        .field("Your feedback", quoted, false)
+                        .field("Reply", &msg, false)
+                })
+            })
+            .await
+        }
+        Err(e) => Err(e),
+    };
+    if let Err(e) = sent {
+        warn!("Could not DM a feedback reply: {:?}", e);
+        ctx.say("Couldn't DM the submitter, they may have DMs turned off.")
+            .await?;
+        return Ok(());
+    }
+
+    let resolved = resolve.unwrap_or(false) && feedback::resolve(pool, id, ctx.author().id).await?;
+    ctx.say(if resolved {
+        format!("Replied to #{} and marked it resolved.", id)
+    } else {
+        format!("Replied to #{}.", id)
+    })
+    .await?;
+    Ok(())
+}
+
+/// only FEEDBACK_WHOIS_ROLE may unmask feedback, and every use goes to the mod log
+async fn whois_check(ctx: Context<'_>) -> Result<bool, Error> {
+    let allowed = match ctx.data().feedback_whois_role {
+        Some(role) => has_role(ctx, role).await,
+        None => false,
+    };
+    if !allowed {
+        ctx.say("You can't see who sent feedback.").await?;
+    }
+    Ok(allowed)
+}
+
+/// Reveal who sent a piece of feedback, for cases of abuse
+#[poise::command(
+    slash_command,
+    ephemeral,
+    default_member_permissions = "ADMINISTRATOR",
+    check = "whois_check"
+)]
+pub async fn feedback_whois(
+    ctx: Context<'_>,
+    #[description = "The feedback number, from /feedback_list"] id: u64,
+    #[description = "Why it needs unmasking, for the mod log"] reason: String,
+) -> Result<(), Error> {
+    let Some(entry) = feedback::feedback_by_id(&ctx.data().pool, id).await? else {
+        ctx.say(format!("There is no feedback #{}.", id)).await?;
+        return Ok(());
+    };
+    let Some(author) = entry.author(ctx.data().feedback_seal.as_ref())? else {
+        ctx.say("Who sent that feedback wasn't recorded.").await?;
+        return Ok(());
+    };
+
+    info!(
+        "{} unmasked feedback #{}: {}",
+        ctx.author().tag(),
+        id,
+        reason
+    );
+    if let Some(channel) = ctx.data().mod_log_channel {
+        channel
+            .send_message(ctx, |m| {
+                m.content(format!(
+                    "<@{}> unmasked feedback #{}: {}",
+                    ctx.author().id.0,
+                    id,
+                    reason
+                ))
+                .allowed_mentions(|a| a.empty_parse())
+            })
+            .await?;
+    }
+    ctx.say(format!("Feedback #{} was sent by <@{}>.", id, author.0))
+        .await?;
+    Ok(())
+}
@@ -0,0 +1,110 @@
+use std::time::{Duration, Instant};
+
+use poise::serenity_prelude as serenity;
+
+use crate::discord::Context;
+use crate::tf2_rcon::RconController;
+use crate::{Error, Server};
+
+/// how long the database gets to answer
+const DB_TIMEOUT: Duration = Duration::from_secs(5);
+
+/// how a subsystem answered, or didn't
+enum Check {
+    Ok(Duration),
+    Failed(String),
+    TimedOut,
+}
+
+impl Check {
+    async fn run<F>(timeout: Duration, check: F) -> Self
+    where
+        F: std::future::Future<Output = Result<(), Error>>,
+    {
+        let started = Instant::now();
+        match tokio::time::timeout(timeout, check).await {
+            Ok(Ok(())) => Self::Ok(started.elapsed()),
+            Ok(Err(e)) => Self::Failed(e.to_string()),
+            Err(_) => Self::TimedOut,
+        }
+    }
+
+    fn is_ok(&self) -> bool {
+        matches!(self, Self::Ok(_))
+    }
+
+    fn describe(&self) -> String {
+        match self {
+            Self::Ok(took) => format!("🟢 {}ms", took.as_millis()),
+            Self::Failed(e) => format!("🔴 {}", e),
+            Self::TimedOut => "🔴 timed out".to_owned(),
+        }
+    }
+}
+
+/// opens a new rcon connection, so a stuck command on the shared one doesn't hide a working server
+async fn check_rcon(server: &Server) -> Check {
+    Check::run(server.rcon_timeout, async {
+        let (addr, password) = {
+            let controller = server.controller.read().await;
+            (controller.address, controller.password.clone())
+        };
+        RconController::connect(addr, &password, server.rcon_timeout).await?;
+        Ok(())
+    })
+    .await
+}
+
+/// Check the bot's connection to discord, the database and each server
+#[poise::command(slash_command, ephemeral, default_member_permissions = "BAN_MEMBERS")]
+pub async fn health(ctx: Context<'_>) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+
+    let mut servers: Vec<&Server> = ctx.data().servers.values().collect();
+    servers.sort_by_key(|s| &s.name);
+    let pool = &ctx.data().pool;
+    let db = Check::run(DB_TIMEOUT, async {
+        sqlx::query!(r#"SELECT 1 AS `ok`"#).fetch_one(pool).await?;
+        Ok(())
+    });
+    let rcon = futures::future::join_all(servers.iter().map(|server| check_rcon(server)));
+    let (db, rcon) = tokio::join!(db, rcon);
+    // zero until the shard's first heartbeat is acknowledged
+    let gateway = ctx.ping().await;
+
+    let healthy = db.is_ok() && rcon.iter().all(Check::is_ok);
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.title(if healthy {
+                "Everything is up :3"
+            } else {
+                "Something is down"
+            })
+            .color(if healthy {
+                serenity::Color::DARK_GREEN
+            } else {
+                serenity::Color::RED
+            })
+            .field(
+                "Discord",
+                if gateway.is_zero() {
+                    "🟡 no heartbeat yet".to_owned()
+                } else {
+                    format!("🟢 {}ms", gateway.as_millis())
+                },
+                true,
+            )
+            .field("Database", db.describe(), true);
+            for (server, check) in servers.iter().zip(&rcon) {
+                e.field(
+                    format!("{} {}", server.emoji, server.name),
+                    check.describe(),
+                    true,
+                );
+            }
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
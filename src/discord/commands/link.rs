@@ -0,0 +1,142 @@
+use poise::serenity_prelude as serenity;
+use tracing::info;
+
+use crate::discord::{links, Context};
+use crate::Error;
+
+use super::util::steam_id_autocomplete;
+
+/// Link your discord account to your steam account
+#[poise::command(slash_command, ephemeral)]
+pub async fn steamlink(
+    ctx: Context<'_>,
+    #[description = "Your SteamID, Steam2, Steam3, or profile URL"]
+    #[autocomplete = "steam_id_autocomplete"]
+    steamid: String,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let profiles = ctx.data().client.lookup_cached(&steamid, false).await?;
+    let [profile] = &profiles[..] else {
+        ctx.say("That matches more than one steam account, give just yours.")
+            .await?;
+        return Ok(());
+    };
+
+    let pool = &ctx.data().pool;
+    match links::linked_user(pool, &profile.steam3).await? {
+        Some(user) if user != ctx.author().id => {
+            ctx.say("That steam account is already linked to someone else. Message a mod if it's yours.")
+                .await?;
+            return Ok(());
+        }
+        _ => {}
+    }
+    links::link(pool, ctx.author().id, &profile.steam3).await?;
+    info!("{} linked {}", ctx.author().tag(), profile.steam3);
+    ctx.say(format!(
+        "Linked to {} `{}` :3",
+        profile.steamidurl, profile.steam3
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Unlink your steam account from your discord account
+#[poise::command(slash_command, ephemeral)]
+pub async fn unlink(ctx: Context<'_>) -> Result<(), Error> {
+    if links::unlink(&ctx.data().pool, ctx.author().id).await? {
+        ctx.say("Unlinked your steam account.").await?;
+    } else {
+        ctx.say("You don't have a steam account linked.").await?;
+    }
+    Ok(())
+}
+
+/// Link someone's discord account to a steam account, even if someone else already linked it
+#[poise::command(slash_command, ephemeral, default_member_permissions = "BAN_MEMBERS")]
+pub async fn forcelink(
+    ctx: Context<'_>,
+    #[description = "The discord user"] user: serenity::User,
+    #[description = "Their SteamID, Steam2, Steam3, or profile URL"]
+    #[autocomplete = "steam_id_autocomplete"]
+    steamid: String,
+) -> Result<(), Error> {
+    ctx.defer_ephemeral().await?;
+    let profiles = ctx.data().client.lookup_cached(&steamid, false).await?;
+    let [profile] = &profiles[..] else {
+        ctx.say("That matches more than one steam account, give just theirs.")
+            .await?;
+        return Ok(());
+    };
+
+    let previous = links::force_link(&ctx.data().pool, user.id, &profile.steam3).await?;
+    info!(
+        "{} force linked {} to {}",
+        ctx.author().tag(),
+        user.tag(),
+        profile.steam3
+    );
+    let mut reply = format!(
+        "Linked <@{}> to {} `{}`.",
+        user.id.0, profile.steamidurl, profile.steam3
+    );
+    if let Some(previous) = previous {
+        reply += &format!(" It was linked to <@{}> before.", previous.0);
+    }
+    ctx.say(reply).await?;
+    Ok(())
+}
+
+/// Unlink someone's steam account from their discord account
+#[poise::command(slash_command, ephemeral, default_member_permissions = "BAN_MEMBERS")]
+pub async fn forceunlink(
+    ctx: Context<'_>,
+    #[description = "The discord user"] user: serenity::User,
+) -> Result<(), Error> {
+    if links::unlink(&ctx.data().pool, user.id).await? {
+        info!("{} unlinked {}", ctx.author().tag(), user.tag());
+        ctx.say(format!("Unlinked <@{}>'s steam account.", user.id.0))
+            .await?;
+    } else {
+        ctx.say(format!(
+            "<@{}> doesn't have a steam account linked.",
+            user.id.0
+        ))
+        .await?;
+    }
+    Ok(())
+}
+
+/// Give or take the steam group role from everyone who linked their steam account
+#[poise::command(slash_command, default_member_permissions = "MANAGE_ROLES")]
+pub async fn syncgroup(ctx: Context<'_>) -> Result<(), Error> {
+    let Some(sync) = ctx.data().steam_group else {
+        ctx.say("No steam group is set up to sync.").await?;
+        return Ok(());
+    };
+    // a lookup per linked account takes a while
+    ctx.defer().await?;
+    let counts = sync
+        .sync(
+            ctx.http(),
+            ctx.data().guild_id,
+            &ctx.data().pool,
+            &ctx.data().client,
+        )
+        .await?;
+    info!(
+        "{} synced the steam group role: {} given, {} taken",
+        ctx.author().tag(),
+        counts.granted,
+        counts.removed
+    );
+    ctx.say(format!(
+        "Gave <@&{role}> to {} and took it from {}. {} linked accounts couldn't be checked, usually because their profile is private.",
+        counts.granted,
+        counts.removed,
+        counts.unknown,
+        role = sync.role.0
+    ))
+    .await?;
+    Ok(())
+}
@@ -1,10 +1,120 @@
 use std::borrow::Cow;
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 
 use crate::discord::Context;
-use crate::Error;
+use crate::{Error, Server};
 
-use poise;
 use poise::serenity_prelude as serenity;
+use poise::AutocompleteChoice;
+use tracing::info;
+
+use super::util::{has_role, lock_rcon, selected_server, servers_autocomplete};
+
+/// how long a server's map list is trusted before asking again
+const MAP_CACHE_TTL: Duration = Duration::from_secs(10 * 60);
+
+/// installed maps per server, they rarely change so they're kept a while
+#[derive(Default)]
+pub struct MapCache {
+    lists: HashMap<SocketAddr, (Instant, Vec<String>)>,
+}
+
+/// the maps installed on the server, from the cache unless it's stale or `refresh` is set
+async fn cached_maps(
+    ctx: Context<'_>,
+    server: &Server,
+    refresh: bool,
+) -> Result<Vec<String>, Error> {
+    let cache = &ctx.data().map_cache;
+    if !refresh {
+        if let Some((fetched_at, maps)) = cache.read().await.lists.get(&server.addr) {
+            if fetched_at.elapsed() < MAP_CACHE_TTL {
+                return Ok(maps.clone());
+            }
+        }
+    }
+    let maps = lock_rcon(server).await?.maps().await?;
+    cache
+        .write()
+        .await
+        .lists
+        .insert(server.addr, (Instant::now(), maps.clone()));
+    Ok(maps)
+}
+
+/// whether the map is installed on the server. asks again before saying no,
+/// in case it was added since the list was cached
+pub async fn map_installed(ctx: Context<'_>, server: &Server, map: &str) -> Result<bool, Error> {
+    if cached_maps(ctx, server, false)
+        .await?
+        .iter()
+        .any(|m| m == map)
+    {
+        return Ok(true);
+    }
+    Ok(cached_maps(ctx, server, true)
+        .await?
+        .iter()
+        .any(|m| m == map))
+}
+
+/// suggests maps installed on the selected server
+pub async fn installed_maps_autocomplete(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<AutocompleteChoice<String>> {
+    let Some(server) = selected_server(ctx).and_then(|addr| ctx.data().servers.get(&addr)) else {
+        return vec![];
+    };
+    let partial = partial.to_lowercase();
+    cached_maps(ctx, server, false)
+        .await
+        .unwrap_or_default()
+        .into_iter()
+        .filter(|m| m.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|m| AutocompleteChoice {
+            name: m.clone(),
+            value: m,
+        })
+        .collect()
+}
+
+/// Changes the map on a server right away
+#[poise::command(slash_command)]
+pub async fn changelevel(
+    ctx: Context<'_>,
+    #[description = "The server to change"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "The map to switch to"]
+    #[autocomplete = "installed_maps_autocomplete"]
+    map: String,
+) -> Result<(), Error> {
+    let server = ctx.data().server(server)?;
+    ctx.defer().await?;
+    if !map_installed(ctx, server, &map).await? {
+        ctx.say(format!("`{}` is not installed on {}.", map, server.name))
+            .await?;
+        return Ok(());
+    }
+    let mut controller = server.controller.write().await;
+    let old = controller.current_map().await?;
+    controller.run(&format!("changelevel {}", map)).await?;
+    drop(controller);
+    info!(
+        "{} changed {} from {} to {}",
+        ctx.author().tag(),
+        server.name,
+        old,
+        map
+    );
+    ctx.say(format!("{} `{}` -> `{}`", server.emoji, old, map))
+        .await?;
+    Ok(())
+}
 
 /// mapcycle.txt related configuration
 #[poise::command(slash_command, subcommands("add", "rm", "list"), subcommand_required)]
@@ -12,9 +122,50 @@ pub async fn map(_: Context<'_>) -> Result<(), Error> {
     Ok(()) // never run
 }
 
+/// whether the invoker is exempt from the allowed maps list
+async fn can_override_maps(ctx: Context<'_>) -> bool {
+    match ctx.data().map_override_role {
+        Some(role) => has_role(ctx, role).await,
+        None => false,
+    }
+}
+
+/// an empty allowed maps list means every map is allowed
+fn map_allowed(allowed_maps: &[String], map: &str) -> bool {
+    allowed_maps.is_empty() || allowed_maps.iter().any(|m| m == map)
+}
+
+/// suggests maps from the allowed maps list
+async fn allowed_maps_autocomplete(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<AutocompleteChoice<String>> {
+    let partial = partial.to_lowercase();
+    ctx.data()
+        .allowed_maps
+        .iter()
+        .filter(|m| m.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|m| AutocompleteChoice {
+            name: m.clone(),
+            value: m.clone(),
+        })
+        .collect()
+}
+
 /// adds a map to the mapcycle.txt of all servers
 #[poise::command(slash_command)]
-async fn add(ctx: Context<'_>, #[description = "The map to add"] map: String) -> Result<(), Error> {
+async fn add(
+    ctx: Context<'_>,
+    #[description = "The map to add"]
+    #[autocomplete = "allowed_maps_autocomplete"]
+    map: String,
+) -> Result<(), Error> {
+    if !map_allowed(&ctx.data().allowed_maps, &map) && !can_override_maps(ctx).await {
+        ctx.say(format!("`{}` is not on the allowed maps list.", map))
+            .await?;
+        return Ok(());
+    }
     let server = ctx.data().servers.values().next().ok_or("No servers")?;
     let mapcyclefile = "mapcycle.txt";
     let mut maps: Vec<String> = server
@@ -32,7 +183,7 @@ async fn add(ctx: Context<'_>, #[description = "The map to add"] map: String) ->
     });
     maps.dedup();
 
-    for (_addr, server) in &ctx.data().servers {
+    for server in ctx.data().servers.values() {
         server
             .ftp
             .upload_file(
@@ -62,7 +213,7 @@ async fn rm(
         .filter(|s| s != &map)
         .collect();
 
-    for (_addr, server) in &ctx.data().servers {
+    for server in ctx.data().servers.values() {
         server
             .ftp
             .upload_file(
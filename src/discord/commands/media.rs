@@ -0,0 +1,166 @@
+use chrono::{Duration, Utc};
+use poise::serenity_prelude as serenity;
+use tracing::info;
+
+use crate::discord::media_cooldown::MediaLimit;
+use crate::discord::Context;
+use crate::Error;
+
+/// Set how much media a channel allows, or put it back on the default cooldown
+#[poise::command(
+    slash_command,
+    ephemeral,
+    default_member_permissions = "MANAGE_MESSAGES"
+)]
+pub async fn mediacooldown(
+    ctx: Context<'_>,
+    #[description = "The media channel"]
+    #[channel_types("Text")]
+    channel: serenity::GuildChannel,
+    #[description = "Uploads allowed in a burst, leave both empty for the default"]
+    #[min = 1]
+    posts: Option<u32>,
+    #[description = "Minutes until another upload is allowed"]
+    #[min = 1]
+    minutes: Option<u32>,
+) -> Result<(), Error> {
+    let limit = match (posts, minutes) {
+        (Some(posts), Some(minutes)) => Some(MediaLimit { posts, minutes }),
+        (None, None) => None,
+        _ => return Err("Give both posts and minutes, or neither.".into()),
+    };
+    // no posts or minutes puts it on the default limit
+    sqlx::query!(
+        r#"
+		INSERT INTO `media_limits` (`channel_id`, `posts`, `minutes`) VALUES (?, ?, ?)
+		ON DUPLICATE KEY UPDATE `posts` = VALUES(`posts`), `minutes` = VALUES(`minutes`)
+	"#,
+        channel.id.0.to_string(),
+        posts,
+        minutes
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+    ctx.data()
+        .media_cooldown
+        .write()
+        .await
+        .set_limit(channel.id, limit);
+    info!(
+        "{} set the media cooldown of {} to {:?}",
+        ctx.author().tag(),
+        channel.name,
+        limit
+    );
+    ctx.say(match limit {
+        Some(l) => format!(
+            "<#{}> allows {} uploads, one more every {} minutes.",
+            channel.id.0, l.posts, l.minutes
+        ),
+        None => format!("<#{}> is on the default media cooldown.", channel.id.0),
+    })
+    .await?;
+    Ok(())
+}
+
+/// Let someone skip the media channel cooldown, e.g. event organizers
+#[poise::command(
+    slash_command,
+    subcommands("add", "remove", "list"),
+    subcommand_required,
+    default_member_permissions = "MANAGE_MESSAGES"
+)]
+pub async fn mediaexempt(_: Context<'_>) -> Result<(), Error> {
+    Ok(()) // never run
+}
+
+/// exempts a user from the media cooldown
+#[poise::command(slash_command)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "The user to exempt"] user: serenity::User,
+    #[description = "How long for, in minutes. Leave empty for forever"] minutes: Option<u32>,
+) -> Result<(), Error> {
+    let until = minutes.map(|m| Utc::now() + Duration::minutes(m as i64));
+    sqlx::query!(
+        r#"
+		INSERT INTO `media_exemptions` (`user_id`, `until`) VALUES (?, ?)
+		ON DUPLICATE KEY UPDATE `until` = VALUES(`until`)
+	"#,
+        user.id.0.to_string(),
+        until
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+    ctx.data()
+        .media_cooldown
+        .write()
+        .await
+        .exempt(user.id, until);
+    info!(
+        "{} exempted {} from media cooldown",
+        ctx.author().tag(),
+        user.tag()
+    );
+    ctx.say(match until {
+        Some(until) => format!(
+            "<@{}> can post media freely until <t:{}:f>.",
+            user.id.0,
+            until.timestamp()
+        ),
+        None => format!("<@{}> can post media freely.", user.id.0),
+    })
+    .await?;
+    Ok(())
+}
+
+/// removes a user's media cooldown exemption
+#[poise::command(slash_command)]
+async fn remove(
+    ctx: Context<'_>,
+    #[description = "The user to remove"] user: serenity::User,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		DELETE FROM `media_exemptions` WHERE `user_id` = ?
+	"#,
+        user.id.0.to_string()
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+    let removed = ctx.data().media_cooldown.write().await.unexempt(&user.id);
+    ctx.say(if removed {
+        format!("<@{}> is back on the media cooldown.", user.id.0)
+    } else {
+        format!("<@{}> wasn't exempt.", user.id.0)
+    })
+    .await?;
+    Ok(())
+}
+
+/// shows who is exempt from the media cooldown
+#[poise::command(slash_command)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let lines: Vec<String> = ctx
+        .data()
+        .media_cooldown
+        .write()
+        .await
+        .exemptions()
+        .iter()
+        .map(|(uid, until)| match until {
+            Some(until) => format!("<@{}> until <t:{}:f>", uid.0, until.timestamp()),
+            None => format!("<@{}> forever", uid.0),
+        })
+        .collect();
+    ctx.send(|m| {
+        m.content(if lines.is_empty() {
+            "Nobody is exempt.".to_owned()
+        } else {
+            lines.join("\n")
+        })
+        .allowed_mentions(|a| a.empty_parse())
+    })
+    .await?;
+    Ok(())
+}
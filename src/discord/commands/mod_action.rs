@@ -0,0 +1,737 @@
+use std::fmt::Display;
+use std::net::SocketAddr;
+
+use poise::serenity_prelude as serenity;
+use serenity::CreateEmbed;
+use tracing::{error, warn};
+
+use crate::discord::{bans, mod_actions, warnings, Context};
+use crate::steamid;
+use crate::tf2_rcon::{Player, PlayerRow};
+use crate::{Error, Server};
+
+use super::mods::notify_banned_user;
+use super::util::{
+    cached_players, confirm_action, escape_rcon_arg, last_players, output_servers, rcon_each,
+    reply_rcon_results,
+};
+
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum ModActionKind {
+    Ban,
+    BanId,
+    Unban,
+    Kick,
+    Mute,
+    Unmute,
+    Gag,
+    Ungag,
+    Noclip,
+    Spectate,
+    Warn,
+    /// with the damage dealt
+    Slap(u32),
+    Slay,
+}
+
+impl Display for ModActionKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        let name = match self {
+            Self::Ban => "Ban",
+            Self::BanId => "Ban (SteamID)",
+            Self::Unban => "Unban",
+            Self::Kick => "Kick",
+            Self::Mute => "Mute",
+            Self::Unmute => "Unmute",
+            Self::Gag => "Gag",
+            Self::Ungag => "Ungag",
+            Self::Noclip => "Noclip",
+            Self::Spectate => "Spectate",
+            Self::Warn => "Warn",
+            Self::Slap(_) => "Slap",
+            Self::Slay => "Slay",
+        };
+        write!(f, "{}", name)
+    }
+}
+
+impl ModActionKind {
+    /// reason used when the moderator doesn't give one
+    fn default_reason(&self) -> Option<&'static str> {
+        match self {
+            Self::Ban | Self::BanId => Some("undesirable"),
+            Self::Unban => Some("chill"),
+            Self::Kick | Self::Mute | Self::Gag => Some("1984"),
+            Self::Unmute => Some("vibin"),
+            Self::Ungag
+            | Self::Noclip
+            | Self::Spectate
+            | Self::Warn
+            | Self::Slap(_)
+            | Self::Slay => None,
+        }
+    }
+
+    /// whether the action takes a duration that caps apply to
+    fn timed(&self) -> bool {
+        matches!(self, Self::Ban | Self::BanId | Self::Mute | Self::Gag)
+    }
+
+    /// embed color, red for removals down to green for reversals
+    fn color(&self) -> serenity::Color {
+        match self {
+            Self::Ban | Self::BanId => serenity::Color::RED,
+            Self::Kick | Self::Slay => serenity::Color::ORANGE,
+            Self::Mute | Self::Gag | Self::Warn | Self::Slap(_) => serenity::Color::GOLD,
+            Self::Unban | Self::Unmute | Self::Ungag => serenity::Color::DARK_GREEN,
+            Self::Noclip | Self::Spectate => serenity::Color::BLURPLE,
+        }
+    }
+}
+
+/// a moderation action taken through the bot
+pub struct ModAction {
+    pub kind: ModActionKind,
+    /// None means every server
+    pub server: Option<SocketAddr>,
+    /// username or steamid the action applies to
+    pub target: String,
+    pub minutes: Option<u32>,
+    pub reason: Option<String>,
+    pub invoker: serenity::UserId,
+}
+
+impl ModAction {
+    /// the given reason, or the default one for this kind of action
+    pub fn reason(&self) -> &str {
+        self.reason
+            .as_deref()
+            .or(self.kind.default_reason())
+            .unwrap_or("")
+    }
+
+    /// the sourcemod command that carries out this action
+    pub fn rcon_command(&self) -> String {
+        let target = escape_rcon_arg(&self.target);
+        let minutes = self.minutes.unwrap_or(0);
+        let reason = escape_rcon_arg(self.reason());
+        match self.kind {
+            ModActionKind::Ban => format!("sm_ban \"{}\" {} {}", target, minutes, reason),
+            ModActionKind::BanId => format!("sm_addban {} {} {}", minutes, target, reason),
+            ModActionKind::Unban => format!("sm_unban {} {}", target, reason),
+            ModActionKind::Kick => format!("sm_kick \"{}\" {}", target, reason),
+            ModActionKind::Mute => format!("sm_mute \"{}\" {} {}", target, minutes, reason),
+            ModActionKind::Unmute => format!("sm_unmute \"{}\" {}", target, reason),
+            ModActionKind::Gag => format!("sm_gag \"{}\" {} {}", target, minutes, reason),
+            ModActionKind::Ungag => format!("sm_ungag \"{}\" {}", target, reason),
+            ModActionKind::Noclip => format!("sm_noclip \"{}\"", target),
+            ModActionKind::Spectate => format!("sm_spec \"{}\"", target),
+            ModActionKind::Warn => format!("sm_psay \"#{}\" \"Warning: {}\"", target, reason),
+            ModActionKind::Slap(damage) => format!("sm_slap \"{}\" {}", target, damage),
+            ModActionKind::Slay => format!("sm_slay \"{}\"", target),
+        }
+    }
+}
+
+/// a canned ban reason with its usual duration
+#[derive(Debug, Clone)]
+pub struct BanPreset {
+    pub name: String,
+    pub minutes: u32,
+    pub reason: String,
+}
+
+/// parses presets from `name:duration:reason|name:duration:reason`, skipping malformed ones
+pub fn parse_ban_presets(s: &str) -> Vec<BanPreset> {
+    s.split('|')
+        .filter_map(|preset| {
+            let mut parts = preset.trim().splitn(3, ':');
+            Some(BanPreset {
+                name: parts.next()?.trim().to_owned(),
+                minutes: parse_duration(parts.next()?).ok()?,
+                reason: parts.next()?.trim().to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// human readable sourcemod duration, where 0 minutes is forever
+pub fn format_minutes(minutes: u32) -> String {
+    match minutes {
+        0 => "permanent".to_owned(),
+        m if m % (60 * 24) == 0 => format!("{}d", m / (60 * 24)),
+        m if m % 60 == 0 => format!("{}h", m / 60),
+        m => format!("{}m", m),
+    }
+}
+
+/// parses durations like `90`, `90m`, `2h30m`, `1d` or `1w` into minutes.
+/// `perm`/`permanent` is 0, which sourcemod treats as forever
+pub fn parse_duration(s: &str) -> Result<u32, Error> {
+    let s = s.trim().to_lowercase();
+    if matches!(s.as_str(), "perm" | "permanent") {
+        return Ok(0);
+    }
+    // plain numbers are minutes, like the commands used to take.
+    // 0 would be permanent too, so that has to be asked for by name
+    if let Ok(minutes) = s.parse::<u32>() {
+        if minutes == 0 {
+            return Err("Use `permanent` for a permanent duration.".into());
+        }
+        return Ok(minutes);
+    }
+    let mut total: u32 = 0;
+    let mut number = String::new();
+    for c in s.chars() {
+        if c.is_ascii_digit() {
+            number.push(c);
+            continue;
+        }
+        let unit = match c {
+            'm' => 1,
+            'h' => 60,
+            'd' => 60 * 24,
+            'w' => 60 * 24 * 7,
+            _ => return Err(format!("`{}` isn't a duration, try 90m, 2h30m or 1w.", s).into()),
+        };
+        let n: u32 = number
+            .parse()
+            .map_err(|_| format!("`{}` isn't a duration, try 90m, 2h30m or 1w.", s))?;
+        number.clear();
+        total = n
+            .checked_mul(unit)
+            .and_then(|m| total.checked_add(m))
+            .ok_or("That duration is too long.")?;
+    }
+    if !number.is_empty() {
+        return Err(format!("`{}` is missing a unit at the end.", s).into());
+    }
+    if total == 0 {
+        return Err("Use `permanent` for a permanent duration.".into());
+    }
+    Ok(total)
+}
+
+/// the audit log entry for a mod action, so every command logs identically
+pub fn audit_embed(action: &ModAction, servers: &[&Server]) -> CreateEmbed {
+    let mut servers: Vec<String> = servers
+        .iter()
+        .map(|s| format!("{} {}", s.emoji, s.name))
+        .collect();
+    servers.sort();
+
+    let mut e = CreateEmbed::default();
+    e.title(action.kind.to_string())
+        .color(action.kind.color())
+        .field("Target", format!("`{}`", action.target), true)
+        .field("Moderator", format!("<@{}>", action.invoker.0), true)
+        .field("Server", servers.join("\n"), true);
+    if let Some(minutes) = action.minutes {
+        e.field("Duration", format_minutes(minutes), true);
+    }
+    if !action.reason().is_empty() {
+        e.field("Reason", action.reason(), false);
+    }
+    e.timestamp(serenity::Timestamp::now());
+    e
+}
+
+/// a player's warnings, oldest first
+pub fn warning_history_embed(steamid: &str, history: &[warnings::Warning]) -> CreateEmbed {
+    let lines: Vec<String> = history
+        .iter()
+        .enumerate()
+        .map(|(i, w)| {
+            format!(
+                "{}. <t:{}:d> by <@{}>: {}",
+                i + 1,
+                w.created_at.timestamp(),
+                w.issued_by,
+                w.reason
+            )
+        })
+        .collect();
+    // keep the newest ones if they don't all fit
+    let mut description = String::new();
+    for line in lines.iter().rev() {
+        if description.len() + line.len() + 1 > 4000 {
+            break;
+        }
+        description = format!("{}\n{}", line, description);
+    }
+    let mut e = CreateEmbed::default();
+    e.title(format!("Warnings for {} ({})", steamid, history.len()))
+        .description(description)
+        .color(
+            if warnings::should_escalate(history.len(), warnings::escalation_threshold()) {
+                serenity::Color::RED
+            } else {
+                serenity::Color::GOLD
+            },
+        );
+    e
+}
+
+/// posts the action to the mod log channel, if one is configured.
+/// failing to log never blocks the action itself.
+pub async fn log_mod_action(ctx: Context<'_>, action: &ModAction) {
+    let Some(channel) = ctx.data().mod_log_channel else {
+        return;
+    };
+    let servers = match output_servers(ctx, action.server) {
+        Ok(servers) => servers,
+        Err(e) => {
+            warn!("Could not log mod action: {:?}", e);
+            return;
+        }
+    };
+    let embed = audit_embed(action, &servers);
+    if let Err(e) = channel.send_message(ctx, |m| m.set_embed(embed)).await {
+        warn!("Could not send message to mod log channel: {:?}", e);
+    }
+}
+
+/// checks the invoker may take this action. returns why not if they can't
+async fn check_allowed(ctx: Context<'_>, action: &ModAction) -> Result<(), String> {
+    let data = ctx.data();
+    let roles = ctx
+        .author_member()
+        .await
+        .map(|m| m.roles.clone())
+        .unwrap_or_default();
+
+    if let Some(mod_role) = data.mod_role {
+        if !roles.contains(&mod_role) {
+            return Err("You need the mod role to do that.".to_owned());
+        }
+    }
+
+    if !action.kind.timed() {
+        return Ok(());
+    }
+    // the most generous cap among the invoker's capped roles applies
+    let Some(cap) = data
+        .duration_caps
+        .iter()
+        .filter(|(role, _)| roles.contains(role))
+        .map(|(_, cap)| *cap)
+        .max()
+    else {
+        return Ok(());
+    };
+    let minutes = action.minutes.unwrap_or(0);
+    if minutes == 0 || minutes > cap {
+        return Err(format!(
+            "You can only {} for up to {}.",
+            action.kind.to_string().to_lowercase(),
+            format_minutes(cap)
+        ));
+    }
+    Ok(())
+}
+
+/// checks permissions, logs the action and runs it on the target server(s), replying with the output
+pub async fn execute_mod_action(ctx: Context<'_>, action: ModAction) -> Result<(), Error> {
+    if let Err(why) = check_allowed(ctx, &action).await {
+        ctx.say(why).await?;
+        return Ok(());
+    }
+    // a stray 0 shouldn't ban someone forever
+    let permanent_ban = matches!(action.kind, ModActionKind::Ban | ModActionKind::BanId)
+        && action.minutes == Some(0);
+    if permanent_ban
+        && !confirm_action(ctx, &format!("Permanently ban `{}`?", action.target)).await?
+    {
+        return Ok(());
+    }
+    // looking up the steamid for a ban can ask the servers too
+    ctx.defer().await?;
+    let servers = output_servers(ctx, action.server)?;
+    // bans are reapplied by steamid, and once banned the player can't be looked up anymore
+    let ban_steamid = match action.kind {
+        ModActionKind::Ban | ModActionKind::BanId => match target_steamid(ctx, &action).await? {
+            Some(steamid) => Some(steamid),
+            None => {
+                ctx.say(format!(
+                    "Couldn't find `{}` online, ban their steamid with /tf2banid instead.",
+                    action.target
+                ))
+                .await?;
+                return Ok(());
+            }
+        },
+        _ => None,
+    };
+
+    let results = rcon_each(&servers, &action.rcon_command()).await;
+    let succeeded = results
+        .iter()
+        .any(|(_, r)| r.as_ref().is_ok_and(|output| !targeted_nobody(output)));
+    if succeeded {
+        log_mod_action(ctx, &action).await;
+        if let Err(e) = record_action(ctx, &action, ban_steamid.clone()).await {
+            error!("Could not record {}: {:?}", action.kind, e);
+        }
+        if let Some(steamid) = &ban_steamid {
+            notify_banned_user(ctx, steamid, &action).await;
+        }
+    }
+    // say what duration was understood, so mods can check it
+    let note = action
+        .minutes
+        .filter(|_| action.kind.timed())
+        .map(|m| format!("{} `{}`: {}", action.kind, action.target, format_minutes(m)));
+    reply_rcon_results(ctx, &servers, &results, note).await
+}
+
+/// whether sourcemod answered that the name or steamid matched no one
+fn targeted_nobody(output: &str) -> bool {
+    output.contains("No matching client")
+}
+
+/// the steamid the action is against. names are looked up in the player lists the autocomplete
+/// already fetched, and only if the name isn't in any of them are the servers asked
+async fn target_steamid(ctx: Context<'_>, action: &ModAction) -> Result<Option<String>, Error> {
+    let target = action.target.trim_start_matches('#');
+    if action.kind == ModActionKind::BanId
+        || target.starts_with("[U:")
+        || target.starts_with("STEAM_")
+    {
+        return Ok(Some(steamid::normalize(target)));
+    }
+    let find = |rows: Vec<PlayerRow>| {
+        rows.into_iter()
+            .find(|p| p.name == action.target)
+            .and_then(|p| p.steamid)
+    };
+    let servers = output_servers(ctx, action.server)?;
+    for server in &servers {
+        if let Some(steamid) = last_players(ctx, server).await.and_then(find) {
+            return Ok(Some(steamid));
+        }
+    }
+    // each server is asked on its own, so a slow one doesn't hold up the rest
+    let lists = futures::future::join_all(servers.iter().map(|s| cached_players(ctx, s))).await;
+    Ok(lists.into_iter().find_map(find))
+}
+
+/// keeps the bans table in step with bans and unbans, so bans can be reapplied later,
+/// and records everything else for /history. run once the action went through.
+/// `steamid` is the target's, if it was already looked up
+async fn record_action(
+    ctx: Context<'_>,
+    action: &ModAction,
+    steamid: Option<String>,
+) -> Result<(), Error> {
+    let pool = &ctx.data().pool;
+    if action.kind == ModActionKind::Unban {
+        bans::lift_bans(pool, &action.target, action.server).await?;
+    }
+    let steamid = match steamid {
+        Some(steamid) => Some(steamid),
+        None => target_steamid(ctx, action).await?,
+    };
+    match action.kind {
+        ModActionKind::Ban | ModActionKind::BanId => {
+            bans::insert_ban(
+                pool,
+                &action.target,
+                steamid.as_deref(),
+                action.server,
+                action.invoker,
+                action.reason(),
+                action.minutes.unwrap_or(0),
+            )
+            .await
+        }
+        // history is by steamid, so there's nothing to keep without one
+        _ => match steamid {
+            Some(steamid) => {
+                mod_actions::insert_action(
+                    pool,
+                    &action.kind.to_string(),
+                    &steamid,
+                    action.server,
+                    action.invoker,
+                    action.reason(),
+                    action.minutes.filter(|_| action.kind.timed()),
+                )
+                .await
+            }
+            None => Ok(()),
+        },
+    }
+}
+
+/// records a warning and, if they're online and `notify` is set, tells the player in-game.
+/// once they reach the escalation threshold the full history is shown
+pub async fn execute_warning(
+    ctx: Context<'_>,
+    server: Option<SocketAddr>,
+    steamid: String,
+    reason: String,
+    notify: bool,
+) -> Result<(), Error> {
+    let action = ModAction {
+        kind: ModActionKind::Warn,
+        server,
+        target: steamid.trim_start_matches('#').to_owned(),
+        minutes: None,
+        reason: Some(reason),
+        invoker: ctx.author().id,
+    };
+    if let Err(why) = check_allowed(ctx, &action).await {
+        ctx.say(why).await?;
+        return Ok(());
+    }
+    ctx.defer().await?;
+    log_mod_action(ctx, &action).await;
+    let pool = &ctx.data().pool;
+    warnings::insert_warning(pool, &action.target, action.reason(), action.invoker).await?;
+
+    let mut told_on = None;
+    if notify {
+        for s in output_servers(ctx, server)? {
+            let Ok(state) = s.controller.write().await.status().await else {
+                continue;
+            };
+            if state.players.iter().any(|p| p.id == action.target) {
+                if rcon_each(&[s], &action.rcon_command()).await[0].1.is_ok() {
+                    told_on = Some(s);
+                }
+                break;
+            }
+        }
+    }
+
+    let history = warnings::warnings_for(pool, &action.target).await?;
+    let mut reply = format!(
+        "Warned `{}`: {}\nThey have {} warnings.",
+        action.target,
+        action.reason(),
+        history.len()
+    );
+    if let Some(s) = told_on {
+        reply += &format!(" Told them in-game on {} {}.", s.emoji, s.name);
+    }
+    if !warnings::should_escalate(history.len(), warnings::escalation_threshold()) {
+        ctx.say(reply).await?;
+        return Ok(());
+    }
+    reply += "\nThat's a lot of warnings, consider a gag, mute or ban.";
+    let embed = warning_history_embed(&action.target, &history);
+    ctx.send(|m| {
+        m.content(reply).embed(|e| {
+            *e = embed;
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// runs the action on every online player whose name contains the filter (everyone if empty),
+/// once the invoker confirms the list
+pub async fn execute_mass_mod_action(
+    ctx: Context<'_>,
+    kind: ModActionKind,
+    server: Option<SocketAddr>,
+    filter: Option<String>,
+    minutes: Option<u32>,
+    reason: Option<String>,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let filter = filter.unwrap_or_default().to_lowercase();
+    let mut targets: Vec<(&Server, Player)> = vec![];
+    for s in output_servers(ctx, server)? {
+        let Ok(state) = s.controller.write().await.status().await else {
+            continue;
+        };
+        targets.extend(
+            state
+                .players
+                .into_iter()
+                .filter(|p| p.name.to_lowercase().contains(&filter))
+                .map(|p| (s, p)),
+        );
+    }
+    if targets.is_empty() {
+        ctx.say("Nobody online matches that.").await?;
+        return Ok(());
+    }
+
+    // one audit entry listing everyone affected
+    let action = ModAction {
+        kind,
+        server,
+        target: targets
+            .iter()
+            .map(|(_, p)| p.id.clone())
+            .collect::<Vec<String>>()
+            .join("\n"),
+        minutes,
+        reason,
+        invoker: ctx.author().id,
+    };
+    if let Err(why) = check_allowed(ctx, &action).await {
+        ctx.say(why).await?;
+        return Ok(());
+    }
+    let prompt = format!(
+        "{} {} players?\n`{}`",
+        kind,
+        targets.len(),
+        targets
+            .iter()
+            .map(|(_, p)| p.name.as_str())
+            .collect::<Vec<&str>>()
+            .join(" | ")
+    );
+    if !confirm_action(ctx, &prompt).await? {
+        return Ok(());
+    }
+    log_mod_action(ctx, &action).await;
+
+    let mut done = 0;
+    for (s, p) in &targets {
+        let cmd = ModAction {
+            kind,
+            server: Some(s.addr),
+            target: format!("#{}", p.id),
+            minutes,
+            reason: action.reason.clone(),
+            invoker: action.invoker,
+        }
+        .rcon_command();
+        // a player who left since the list was made isn't done
+        let succeeded = rcon_each(&[*s], &cmd)
+            .await
+            .iter()
+            .all(|(_, r)| r.as_ref().is_ok_and(|output| !targeted_nobody(output)));
+        if succeeded {
+            done += 1;
+            if let Err(e) = mod_actions::insert_action(
+                &ctx.data().pool,
+                &kind.to_string(),
+                &p.id,
+                Some(s.addr),
+                action.invoker,
+                action.reason(),
+                minutes,
+            )
+            .await
+            {
+                error!("Could not record {}: {:?}", kind, e);
+            }
+        }
+    }
+    ctx.say(format!(
+        "{}{}: done for {}/{} players.",
+        kind,
+        minutes
+            .map(|m| format!(" ({})", format_minutes(m)))
+            .unwrap_or_default(),
+        done,
+        targets.len()
+    ))
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const KINDS: [ModActionKind; 13] = [
+        ModActionKind::Ban,
+        ModActionKind::BanId,
+        ModActionKind::Unban,
+        ModActionKind::Kick,
+        ModActionKind::Mute,
+        ModActionKind::Unmute,
+        ModActionKind::Gag,
+        ModActionKind::Ungag,
+        ModActionKind::Noclip,
+        ModActionKind::Spectate,
+        ModActionKind::Warn,
+        ModActionKind::Slap(5),
+        ModActionKind::Slay,
+    ];
+
+    fn action(kind: ModActionKind, target: &str, reason: &str) -> ModAction {
+        ModAction {
+            kind,
+            server: None,
+            target: target.to_owned(),
+            minutes: Some(60),
+            reason: Some(reason.to_owned()),
+            invoker: serenity::UserId(1),
+        }
+    }
+
+    #[test]
+    fn adversarial_input_cannot_break_out_of_the_command() {
+        let targets = [
+            r#"evil" ; sm_rcon quit"#,
+            r#"evil"; exec server"#,
+            "evil\nquit",
+            r#"""#,
+        ];
+        for kind in KINDS {
+            // the quotes the command itself puts around arguments
+            let quotes = action(kind, "cat", "meow")
+                .rcon_command()
+                .matches('"')
+                .count();
+            for target in targets {
+                let cmd = action(kind, target, r#"bye" ; quit"#).rcon_command();
+                assert_eq!(cmd.matches('"').count(), quotes, "{}", cmd);
+                assert!(!cmd.contains([';', '\n', '\r']), "{}", cmd);
+            }
+        }
+    }
+
+    #[test]
+    fn commands_quote_the_target() {
+        assert_eq!(
+            action(ModActionKind::Ban, "some guy", "cheating").rcon_command(),
+            r#"sm_ban "some guy" 60 cheating"#
+        );
+        assert_eq!(
+            action(ModActionKind::Kick, r#"a"b"#, "bye").rcon_command(),
+            r#"sm_kick "ab" bye"#
+        );
+    }
+
+    #[test]
+    fn durations_with_units() {
+        assert_eq!(parse_duration("90").unwrap(), 90);
+        assert_eq!(parse_duration("90m").unwrap(), 90);
+        assert_eq!(parse_duration("2h30m").unwrap(), 150);
+        assert_eq!(parse_duration("1d").unwrap(), 60 * 24);
+        assert_eq!(parse_duration(" 1W ").unwrap(), 60 * 24 * 7);
+        assert_eq!(parse_duration("1h1h").unwrap(), 120);
+    }
+
+    #[test]
+    fn permanent_has_to_be_named() {
+        assert_eq!(parse_duration("perm").unwrap(), 0);
+        assert_eq!(parse_duration("Permanent").unwrap(), 0);
+        assert!(parse_duration("0").is_err());
+        assert!(parse_duration("0m").is_err());
+        assert!(parse_duration("0h0m").is_err());
+    }
+
+    #[test]
+    fn garbage_durations_are_rejected() {
+        for s in ["", "abc", "10x", "h", "1h30", "-5", "1.5h", "99999999999w"] {
+            assert!(parse_duration(s).is_err(), "{}", s);
+        }
+    }
+
+    #[test]
+    fn no_matching_client_means_nobody_was_targeted() {
+        assert!(targeted_nobody("[SM] No matching client was found.\n"));
+        assert!(!targeted_nobody("[SM] Kicked player.\n"));
+    }
+}
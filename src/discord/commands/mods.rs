@@ -1,11 +1,85 @@
 use std::net::SocketAddr;
 
-use crate::{discord::Context, Error};
-use poise;
+use chrono::Utc;
+use poise::serenity_prelude as serenity;
+use tracing::{info, warn};
 
+use crate::{discord::Context, steamid, Error};
+
+use super::mod_action::{
+    execute_mass_mod_action, execute_mod_action, execute_warning, format_minutes, parse_duration,
+    warning_history_embed, ModAction, ModActionKind,
+};
 use super::util::{
-    output_servers, rcon_and_reply, rcon_user_output, servers_autocomplete, users_autocomplete,
+    ban_presets_autocomplete, banned_players_autocomplete, paginate_embeds, reasons_autocomplete,
+    servers_autocomplete, steam_id_autocomplete, users_autocomplete,
 };
+use crate::discord::{bans, links, mod_actions, warnings};
+
+/// DMs the discord account linked to a banned steamid why they were banned and for how long,
+/// if NOTIFY_BANNED_USERS is set. closed DMs and missing links are logged and otherwise ignored
+pub async fn notify_banned_user(ctx: Context<'_>, steamid: &str, action: &ModAction) {
+    if !ctx.data().notify_banned_users {
+        return;
+    }
+    match try_notify_banned_user(ctx, steamid, action).await {
+        Ok(true) => info!("Told the linked account of {} about their ban", steamid),
+        Ok(false) => {}
+        Err(e) => warn!("Could not DM {} about their ban: {:?}", steamid, e),
+    }
+}
+
+/// false if the steamid isn't linked to anyone
+async fn try_notify_banned_user(
+    ctx: Context<'_>,
+    steamid: &str,
+    action: &ModAction,
+) -> Result<bool, Error> {
+    // links are by steam3, bans by id can be in any format
+    let steam3 = if steamid.starts_with("[U:") {
+        steamid.to_owned()
+    } else {
+        match ctx
+            .data()
+            .client
+            .lookup_cached(steamid, false)
+            .await?
+            .first()
+        {
+            Some(profile) => profile.steam3.clone(),
+            None => return Ok(false),
+        }
+    };
+    let Some(user) = links::linked_user(&ctx.data().pool, &steam3).await? else {
+        return Ok(false);
+    };
+    let server = match action.server {
+        Some(addr) => ctx.data().server(addr)?.name.clone(),
+        None => "our tf2 servers".to_owned(),
+    };
+    let expires = match bans::ban_expiry(action.minutes.unwrap_or(0), Utc::now()) {
+        Some(at) => format!("<t:{}:R>", at.timestamp()),
+        None => "never".to_owned(),
+    };
+    let dm = user.create_dm_channel(ctx).await?;
+    dm.send_message(ctx, |m| {
+        m.embed(|e| {
+            e.title(format!("You were banned from {}", server))
+                .field(
+                    "Reason",
+                    match action.reason() {
+                        "" => "none given",
+                        reason => reason,
+                    },
+                    false,
+                )
+                .field("Expires", expires, false)
+                .color(serenity::Color::RED)
+        })
+    })
+    .await?;
+    Ok(true)
+}
 
 /// Ban a user from the tf2 server
 #[poise::command(slash_command)]
@@ -17,12 +91,238 @@ pub async fn tf2ban(
     #[description = "The username to ban."]
     #[autocomplete = "users_autocomplete"]
     username: String,
-    #[description = "Time to ban them for, in minutes"] minutes: u32,
-    #[description = "The reason for the ban"] reason: Option<String>,
+    #[description = "How long, e.g. 90m, 2h30m, 1w or permanent"] duration: Option<String>,
+    #[description = "The reason for the ban"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: Option<String>,
+    #[description = "Fills in the reason and duration, unless given"]
+    #[autocomplete = "ban_presets_autocomplete"]
+    preset: Option<String>,
+) -> Result<(), Error> {
+    let preset = match preset {
+        Some(name) => match ctx.data().ban_presets.iter().find(|p| p.name == name) {
+            Some(preset) => Some(preset),
+            None => {
+                ctx.say(format!("There's no preset called {}.", name))
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => None,
+    };
+    let minutes = duration.map(|d| parse_duration(&d)).transpose()?;
+    let Some(minutes) = minutes.or(preset.map(|p| p.minutes)) else {
+        ctx.say("Give a duration or pick a preset.").await?;
+        return Ok(());
+    };
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::Ban,
+            server,
+            target: username,
+            minutes: Some(minutes),
+            reason: reason.or(preset.map(|p| p.reason.clone())),
+            invoker: ctx.author().id,
+        },
+    )
+    .await
+}
+
+/// List the bans the bot has recorded that haven't been lifted
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn bans(
+    ctx: Context<'_>,
+    #[description = "Only bans on this server"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "Only bans whose username or steamid contains this"] search: Option<String>,
 ) -> Result<(), Error> {
-    let reason = reason.unwrap_or("undesirable".to_owned());
-    let cmd = format!("sm_ban \"{}\" {} {}", username, minutes, reason);
-    rcon_and_reply(ctx, server, cmd).await
+    let search = search.unwrap_or_default().to_lowercase();
+    let now = Utc::now();
+    let bans: Vec<_> = sqlx::query!(
+        r#"
+        SELECT `target`, `steamid`, `server_addr`, `issued_by`, `reason`, `expires_at`
+        FROM `bans` WHERE `lifted` = false ORDER BY `created_at` DESC
+    "#
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?
+    .into_iter()
+    .filter(|b| {
+        // bans on every server show up under each of them
+        server.is_none_or(|s| b.server_addr.is_none() || b.server_addr == Some(s.to_string()))
+    })
+    .filter(|b| {
+        b.target.to_lowercase().contains(&search)
+            || b.steamid
+                .as_ref()
+                .is_some_and(|id| id.to_lowercase().contains(&search))
+    })
+    .collect();
+    if bans.is_empty() {
+        ctx.say("No bans found.").await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = bans
+        .iter()
+        .map(|b| {
+            let who = match &b.steamid {
+                Some(id) if *id != b.target => format!("`{}` ({})", b.target, id),
+                _ => format!("`{}`", b.target),
+            };
+            let reason: String = b.reason.chars().take(60).collect();
+            match b.expires_at {
+                None => format!("{} {}, by <@{}>, permanent", who, reason, b.issued_by),
+                Some(at) if at > now => format!(
+                    "{} {}, by <@{}>, {} left (<t:{}:R>)",
+                    who,
+                    reason,
+                    b.issued_by,
+                    format_minutes(((at - now).num_minutes() as u32).max(1)),
+                    at.timestamp()
+                ),
+                // expired but never cleaned up
+                Some(at) => format!(
+                    "~~{} {}, by <@{}>~~ expired <t:{}:R>",
+                    who,
+                    reason,
+                    b.issued_by,
+                    at.timestamp()
+                ),
+            }
+        })
+        .collect();
+    let page_count = lines.len().div_ceil(25);
+    let pages: Vec<serenity::CreateEmbed> = lines
+        .chunks(25)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut e = serenity::CreateEmbed::default();
+            e.title(format!("Bans ({})", bans.len()))
+                .description(chunk.join("\n"))
+                .color(serenity::Color::RED)
+                .footer(|f| f.text(format!("page {}/{}", i + 1, page_count)));
+            e
+        })
+        .collect();
+    paginate_embeds(ctx, pages).await
+}
+
+/// Show the latest bans on every server, lifted and expired ones included
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn recentbans(
+    ctx: Context<'_>,
+    #[description = "How many bans to show, 50 if not given"]
+    #[min = 1]
+    #[max = 500]
+    count: Option<u32>,
+    #[description = "Only bans by this moderator"] moderator: Option<serenity::User>,
+    #[description = "Only bans on this server"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+) -> Result<(), Error> {
+    let moderator = moderator.map(|m| m.id.0.to_string());
+    let server = server.map(|s| s.to_string());
+    // bans on every server show up under each of them
+    let bans = sqlx::query!(
+        r#"
+        SELECT `target`, `steamid`, `server_addr`, `issued_by`, `reason`, `created_at`, `expires_at`, `lifted`
+        FROM `bans`
+        WHERE (? IS NULL OR `issued_by` = ?)
+        AND (? IS NULL OR `server_addr` IS NULL OR `server_addr` = ?)
+        ORDER BY `created_at` DESC LIMIT ?
+    "#,
+        moderator,
+        moderator,
+        server,
+        server,
+        count.unwrap_or(50)
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if bans.is_empty() {
+        ctx.say("No bans found.").await?;
+        return Ok(());
+    }
+
+    let now = Utc::now();
+    let lines: Vec<String> = bans
+        .iter()
+        .map(|b| {
+            let who = match &b.steamid {
+                Some(id) if *id != b.target => format!("`{}` ({})", b.target, id),
+                _ => format!("`{}`", b.target),
+            };
+            let reason: String = b.reason.chars().take(60).collect();
+            let duration = match b.expires_at {
+                None => "permanent".to_owned(),
+                Some(at) => format_minutes(((at - b.created_at).num_minutes() as u32).max(1)),
+            };
+            let on = b
+                .server_addr
+                .as_ref()
+                .and_then(|addr| ctx.data().servers.get(&addr.parse().ok()?))
+                .map_or("every server".to_owned(), |s| {
+                    format!("{} {}", s.emoji, s.name)
+                });
+            let line = format!(
+                "<t:{}:R> {} {}, {}, by <@{}> on {}",
+                b.created_at.timestamp(),
+                who,
+                reason,
+                duration,
+                b.issued_by,
+                on
+            );
+            if b.lifted != 0 || b.expires_at.is_some_and(|at| at <= now) {
+                format!("~~{}~~", line)
+            } else {
+                line
+            }
+        })
+        .collect();
+    let page_count = lines.len().div_ceil(25);
+    let pages: Vec<serenity::CreateEmbed> = lines
+        .chunks(25)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut e = serenity::CreateEmbed::default();
+            e.title(format!("Latest {} bans", bans.len()))
+                .description(chunk.join("\n"))
+                .color(serenity::Color::RED)
+                .footer(|f| f.text(format!("page {}/{}, struck out if over", i + 1, page_count)));
+            e
+        })
+        .collect();
+    paginate_embeds(ctx, pages).await
+}
+
+/// Ban a steam id on every server at once, for cheaters who hop between them
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn ban_all(
+    ctx: Context<'_>,
+    #[description = "The steam id to ban"] id: String,
+    #[description = "How long, e.g. 90m, 2h30m, 1w or permanent"] duration: String,
+    #[description = "The reason for the ban"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    let minutes = parse_duration(&duration)?;
+    // no server means every server, and each one reports back on its own
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::BanId,
+            server: None,
+            target: id,
+            minutes: Some(minutes),
+            reason,
+            invoker: ctx.author().id,
+        },
+    )
+    .await
 }
 
 /// Ban a steam id from the tf2 server
@@ -33,15 +333,24 @@ pub async fn tf2banid(
     #[autocomplete = "servers_autocomplete"]
     server: Option<SocketAddr>,
     #[description = "The steam id to ban"] id: String,
-    #[description = "Time to ban them for, in minutes"] minutes: u32,
-    #[description = "The reason for the ban"] reason: Option<String>,
+    #[description = "How long, e.g. 90m, 2h30m, 1w or permanent"] duration: String,
+    #[description = "The reason for the ban"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: Option<String>,
 ) -> Result<(), Error> {
-    let reason = reason.unwrap_or("undesirable".to_owned());
-    let cmd = format!("sm_addban {} {} {}", minutes, id, reason);
-    let reply = rcon_user_output(&output_servers(ctx, server)?, cmd).await;
-    ctx.send(|m| m.content(reply)).await?;
-
-    Ok(())
+    let minutes = parse_duration(&duration)?;
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::BanId,
+            server,
+            target: id,
+            minutes: Some(minutes),
+            reason,
+            invoker: ctx.author().id,
+        },
+    )
+    .await
 }
 
 /// Unban a user from the tf2 server
@@ -51,11 +360,23 @@ pub async fn tf2unban(
     #[description = "The server to query"]
     #[autocomplete = "servers_autocomplete"]
     server: Option<SocketAddr>,
-    #[description = "The steamid / ip to unban."] steamid: String,
+    #[description = "The steamid / ip to unban."]
+    #[autocomplete = "banned_players_autocomplete"]
+    steamid: String,
     #[description = "The reason for the unban"] reason: Option<String>,
 ) -> Result<(), Error> {
-    let reason = reason.unwrap_or("chill".to_owned());
-    rcon_and_reply(ctx, server, format!("sm_unban {} {}", steamid, reason)).await
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::Unban,
+            server,
+            target: steamid,
+            minutes: None,
+            reason,
+            invoker: ctx.author().id,
+        },
+    )
+    .await
 }
 
 /// Kick a user from the tf2 server
@@ -68,10 +389,82 @@ pub async fn tf2kick(
     #[description = "The username to kick."]
     #[autocomplete = "users_autocomplete"]
     username: String,
-    #[description = "The reason for the kick"] reason: Option<String>,
+    #[description = "The reason for the kick"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::Kick,
+            server,
+            target: username,
+            minutes: None,
+            reason,
+            invoker: ctx.author().id,
+        },
+    )
+    .await
+}
+
+/// most damage /slap will deal, anything more is just a slay
+const MAX_SLAP_DAMAGE: u32 = 300;
+
+/// Slap a player, optionally dealing damage
+#[poise::command(slash_command)]
+pub async fn slap(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "The username to slap."]
+    #[autocomplete = "users_autocomplete"]
+    username: String,
+    #[description = "How much damage to deal, 0 by default"]
+    #[max = 300]
+    damage: Option<u32>,
 ) -> Result<(), Error> {
-    let reason = reason.unwrap_or("1984".to_owned());
-    rcon_and_reply(ctx, server, format!("sm_kick \"{}\" {}", username, reason)).await
+    let damage = damage.unwrap_or(0);
+    if damage > MAX_SLAP_DAMAGE {
+        return Err(format!("Slaps can deal at most {} damage.", MAX_SLAP_DAMAGE).into());
+    }
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::Slap(damage),
+            server,
+            target: username,
+            minutes: None,
+            reason: None,
+            invoker: ctx.author().id,
+        },
+    )
+    .await
+}
+
+/// Slay a player
+#[poise::command(slash_command)]
+pub async fn slay(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "The username to slay."]
+    #[autocomplete = "users_autocomplete"]
+    username: String,
+) -> Result<(), Error> {
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::Slay,
+            server,
+            target: username,
+            minutes: None,
+            reason: None,
+            invoker: ctx.author().id,
+        },
+    )
+    .await
 }
 
 /// Mute a user's vc on the tf2 server
@@ -84,15 +477,26 @@ pub async fn tf2mute(
     #[description = "The username to mute."]
     #[autocomplete = "users_autocomplete"]
     username: String,
-    #[description = "Time to mute them for, in minutes"] minutes: Option<u32>,
-    #[description = "The reason for the mute"] reason: Option<String>,
+    #[description = "How long, e.g. 90m, 2h30m, 1w or permanent"] duration: Option<String>,
+    #[description = "The reason for the mute"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: Option<String>,
 ) -> Result<(), Error> {
-    let reason = reason.unwrap_or("1984".to_owned());
-    let minutes = minutes.unwrap_or(0);
-    rcon_and_reply(
+    execute_mod_action(
         ctx,
-        server,
-        format!("sm_mute \"{}\" {} {}", username, minutes, reason),
+        ModAction {
+            kind: ModActionKind::Mute,
+            server,
+            target: username,
+            minutes: Some(
+                duration
+                    .map(|d| parse_duration(&d))
+                    .transpose()?
+                    .unwrap_or(0),
+            ),
+            reason,
+            invoker: ctx.author().id,
+        },
     )
     .await
 }
@@ -109,11 +513,16 @@ pub async fn tf2unmute(
     username: String,
     #[description = "The reason for the unmute"] reason: Option<String>,
 ) -> Result<(), Error> {
-    let reason = reason.unwrap_or("vibin".to_owned());
-    rcon_and_reply(
+    execute_mod_action(
         ctx,
-        server,
-        format!("sm_unmute \"{}\" {}", username, reason),
+        ModAction {
+            kind: ModActionKind::Unmute,
+            server,
+            target: username,
+            minutes: None,
+            reason,
+            invoker: ctx.author().id,
+        },
     )
     .await
 }
@@ -128,15 +537,26 @@ pub async fn tf2gag(
     #[description = "The username to gag."]
     #[autocomplete = "users_autocomplete"]
     username: String,
-    #[description = "Time to gag them for, in minutes"] minutes: Option<u32>,
-    #[description = "The reason for the gag"] reason: Option<String>,
+    #[description = "How long, e.g. 90m, 2h30m, 1w or permanent"] duration: Option<String>,
+    #[description = "The reason for the gag"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: Option<String>,
 ) -> Result<(), Error> {
-    let reason = reason.unwrap_or("1984".to_owned());
-    let minutes = minutes.unwrap_or(0);
-    rcon_and_reply(
+    execute_mod_action(
         ctx,
-        server,
-        format!("sm_gag \"{}\" {} {}", username, minutes, reason),
+        ModAction {
+            kind: ModActionKind::Gag,
+            server,
+            target: username,
+            minutes: Some(
+                duration
+                    .map(|d| parse_duration(&d))
+                    .transpose()?
+                    .unwrap_or(0),
+            ),
+            reason,
+            invoker: ctx.author().id,
+        },
     )
     .await
 }
@@ -153,6 +573,323 @@ pub async fn tf2ungag(
     username: String,
     #[description = "The reason for the ungag"] reason: Option<String>,
 ) -> Result<(), Error> {
-    let reason = reason.unwrap_or("".to_owned());
-    rcon_and_reply(ctx, server, format!("sm_ungag \"{}\" {}", username, reason)).await
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::Ungag,
+            server,
+            target: username,
+            minutes: None,
+            reason,
+            invoker: ctx.author().id,
+        },
+    )
+    .await
+}
+
+/// the invoking mod as a sourcemod target, by the steam account they linked
+async fn own_player(ctx: Context<'_>) -> Result<Option<String>, Error> {
+    Ok(links::linked_steamid(&ctx.data().pool, ctx.author().id)
+        .await?
+        .map(|steamid| format!("#{}", steamid)))
+}
+
+/// Toggle noclip on yourself to review a suspect up close
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn tf2noclip(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "Who you're reviewing, for the mod log"]
+    #[autocomplete = "users_autocomplete"]
+    target: Option<String>,
+) -> Result<(), Error> {
+    let Some(me) = own_player(ctx).await? else {
+        ctx.say(
+            "You're found in-game by your steam account, link your account with /steamlink first.",
+        )
+        .await?;
+        return Ok(());
+    };
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::Noclip,
+            server,
+            target: me,
+            minutes: None,
+            reason: target.map(|t| format!("reviewing {}", t)),
+            invoker: ctx.author().id,
+        },
+    )
+    .await
+}
+
+/// Move yourself to spectator to watch a suspect
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn tf2spectate(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "Who you're reviewing, for the mod log"]
+    #[autocomplete = "users_autocomplete"]
+    target: Option<String>,
+) -> Result<(), Error> {
+    let Some(me) = own_player(ctx).await? else {
+        ctx.say(
+            "You're found in-game by your steam account, link your account with /steamlink first.",
+        )
+        .await?;
+        return Ok(());
+    };
+    execute_mod_action(
+        ctx,
+        ModAction {
+            kind: ModActionKind::Spectate,
+            server,
+            target: me,
+            minutes: None,
+            reason: target.map(|t| format!("reviewing {}", t)),
+            invoker: ctx.author().id,
+        },
+    )
+    .await
+}
+
+/// Warn a player, keeping track of how many warnings they've had
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn warn(
+    ctx: Context<'_>,
+    #[description = "The server they're on. Leave empty to look on all"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "The steam id to warn"]
+    #[autocomplete = "steam_id_autocomplete"]
+    steamid: String,
+    #[description = "What they're being warned for"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: String,
+    #[description = "Tell them in-game? Defaults to yes"] notify: Option<bool>,
+) -> Result<(), Error> {
+    execute_warning(ctx, server, steamid, reason, notify.unwrap_or(true)).await
+}
+
+/// Look up every warning a player has had
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn warnings(
+    ctx: Context<'_>,
+    #[description = "The steam id to look up"]
+    #[autocomplete = "steam_id_autocomplete"]
+    steamid: String,
+) -> Result<(), Error> {
+    let steamid = steamid.trim_start_matches('#');
+    let history = warnings::warnings_for(&ctx.data().pool, steamid).await?;
+    if history.is_empty() {
+        ctx.say(format!("`{}` has no warnings.", steamid)).await?;
+        return Ok(());
+    }
+    let embed = warning_history_embed(steamid, &history);
+    ctx.send(|m| {
+        m.embed(|e| {
+            *e = embed;
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// Show every ban, warning and other action taken against a player
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn history(
+    ctx: Context<'_>,
+    #[description = "The steam id to look up"]
+    #[autocomplete = "steam_id_autocomplete"]
+    steamid: Option<String>,
+    #[description = "A discord user with a linked steam account"] user: Option<serenity::User>,
+) -> Result<(), Error> {
+    let pool = &ctx.data().pool;
+    let steamid = match (steamid, user) {
+        (Some(steamid), _) => steamid::normalize(steamid.trim_start_matches('#')),
+        (None, Some(user)) => match links::linked_steamid(pool, user.id).await? {
+            Some(steamid) => steamid,
+            None => {
+                ctx.say(format!("{} hasn't linked a steam account.", user.tag()))
+                    .await?;
+                return Ok(());
+            }
+        },
+        (None, None) => {
+            ctx.say("Give a steam id or a user.").await?;
+            return Ok(());
+        }
+    };
+    let history = mod_actions::history_for(pool, &steamid).await?;
+    if history.is_empty() {
+        ctx.say(format!("`{}` has a clean record.", steamid))
+            .await?;
+        return Ok(());
+    }
+
+    // most common first, e.g. 3 warns, 1 ban
+    let mut counts: Vec<(&str, usize)> = vec![];
+    for entry in &history {
+        match counts.iter_mut().find(|(k, _)| *k == entry.kind) {
+            Some((_, n)) => *n += 1,
+            None => counts.push((&entry.kind, 1)),
+        }
+    }
+    counts.sort_by_key(|(_, n)| std::cmp::Reverse(*n));
+    let summary = counts
+        .iter()
+        .map(|(kind, n)| {
+            format!(
+                "{} {}{}",
+                n,
+                kind.to_lowercase(),
+                if *n == 1 { "" } else { "s" }
+            )
+        })
+        .collect::<Vec<String>>()
+        .join(", ");
+
+    let lines: Vec<String> = history
+        .iter()
+        .map(|e| {
+            let duration = e
+                .minutes
+                .map(|m| format!(" ({})", format_minutes(m)))
+                .unwrap_or_default();
+            let reason: String = e.reason.chars().take(60).collect();
+            format!(
+                "<t:{}:d> **{}**{} by <@{}>: {}",
+                e.created_at.timestamp(),
+                e.kind,
+                duration,
+                e.issued_by,
+                reason
+            )
+        })
+        .collect();
+    let page_count = lines.len().div_ceil(15);
+    let pages: Vec<serenity::CreateEmbed> = lines
+        .chunks(15)
+        .enumerate()
+        .map(|(i, chunk)| {
+            let mut e = serenity::CreateEmbed::default();
+            e.title(format!("History for {}", steamid))
+                .description(format!("{}\n\n{}", summary, chunk.join("\n")))
+                .color(serenity::Color::GOLD)
+                .footer(|f| f.text(format!("page {}/{}", i + 1, page_count)));
+            e
+        })
+        .collect();
+    paginate_embeds(ctx, pages).await
+}
+
+/// Gag every player matching a filter, for chat spam from a group
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn massgag(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "Only names containing this, empty for everyone"] filter: Option<String>,
+    #[description = "How long, e.g. 90m, 2h30m, 1w or permanent"] duration: Option<String>,
+    #[description = "The reason for the gag"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    execute_mass_mod_action(
+        ctx,
+        ModActionKind::Gag,
+        server,
+        filter,
+        Some(
+            duration
+                .map(|d| parse_duration(&d))
+                .transpose()?
+                .unwrap_or(0),
+        ),
+        reason,
+    )
+    .await
+}
+
+/// Mute every player matching a filter, for mic spam from a group
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn massmute(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "Only names containing this, empty for everyone"] filter: Option<String>,
+    #[description = "How long, e.g. 90m, 2h30m, 1w or permanent"] duration: Option<String>,
+    #[description = "The reason for the mute"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: Option<String>,
+) -> Result<(), Error> {
+    execute_mass_mod_action(
+        ctx,
+        ModActionKind::Mute,
+        server,
+        filter,
+        Some(
+            duration
+                .map(|d| parse_duration(&d))
+                .transpose()?
+                .unwrap_or(0),
+        ),
+        reason,
+    )
+    .await
+}
+
+/// Add a canned reason to the suggestions on mod commands
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+pub async fn addreason(
+    ctx: Context<'_>,
+    #[description = "The reason, e.g. mic spam"] reason: String,
+) -> Result<(), Error> {
+    let reason = reason.trim().to_owned();
+    if reason.is_empty() || reason.chars().count() > 255 {
+        return Err("Reasons have to be 1 to 255 characters.".into());
+    }
+    sqlx::query!(
+        r#"INSERT IGNORE INTO `mod_reasons` (`reason`) VALUES (?)"#,
+        reason
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+    let mut reasons = ctx.data().mod_reasons.write().await;
+    if !reasons.contains(&reason) {
+        reasons.push(reason.clone());
+        reasons.sort();
+    }
+    ctx.say(format!("Added `{}` to the reasons.", reason))
+        .await?;
+    Ok(())
+}
+
+/// Remove a canned reason from the suggestions on mod commands
+#[poise::command(slash_command, default_member_permissions = "ADMINISTRATOR")]
+pub async fn rmreason(
+    ctx: Context<'_>,
+    #[description = "The reason to remove"]
+    #[autocomplete = "reasons_autocomplete"]
+    reason: String,
+) -> Result<(), Error> {
+    sqlx::query!(r#"DELETE FROM `mod_reasons` WHERE `reason` = ?"#, reason)
+        .execute(&ctx.data().pool)
+        .await?;
+    ctx.data()
+        .mod_reasons
+        .write()
+        .await
+        .retain(|r| *r != reason);
+    ctx.say(format!("Removed `{}` from the reasons.", reason))
+        .await?;
+    Ok(())
 }
@@ -0,0 +1,53 @@
+use std::net::SocketAddr;
+
+use crate::discord::Context;
+use crate::Error;
+
+use super::util::{output_servers, servers_autocomplete};
+
+/// Replace the server MOTD shown when players join
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn setmotd(
+    ctx: Context<'_>,
+    #[description = "The server to update. Leave empty for all"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "The new MOTD. Use \\n for new lines"] text: String,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    // slash command options are single line
+    let text = text.replace("\\n", "\n");
+
+    let mut outputs: Vec<String> = vec![];
+    for server in output_servers(ctx, server)? {
+        // the file the server reads its motd from
+        let motdfile = server
+            .controller
+            .write()
+            .await
+            .convar("motdfile")
+            .await
+            .unwrap_or("motd.txt".to_owned());
+        let path = format!("tf/{}", motdfile);
+        let previous = server.ftp.fetch_file(&path).await.unwrap_or_default();
+        server.ftp.upload_file(&path, text.as_bytes()).await?;
+
+        let previous: String = String::from_utf8_lossy(&previous)
+            .replace("```", "")
+            .chars()
+            .take(800)
+            .collect();
+        outputs.push(format!(
+            "{} :white_check_mark: previous MOTD:\n```{}```",
+            server.emoji,
+            if previous.trim().is_empty() {
+                "(empty)"
+            } else {
+                &previous
+            }
+        ));
+    }
+    outputs.sort();
+    ctx.say(outputs.join("\n")).await?;
+    Ok(())
+}
@@ -0,0 +1,207 @@
+use std::net::SocketAddr;
+
+use poise::AutocompleteChoice;
+use tracing::{info, warn};
+
+use crate::discord::rcon_policy::rcon_check;
+use crate::discord::Context;
+use crate::Error;
+
+use super::util::{
+    confirm_action, escape_rcon_arg, rcon_and_reply, servers_autocomplete, steam_id_autocomplete,
+};
+
+/// Shows how often /lookup is answered from the SteamID cache
+#[poise::command(slash_command, owners_only, ephemeral, rename = "steamid-cache")]
+pub async fn steamid_cache(ctx: Context<'_>) -> Result<(), Error> {
+    let stats = ctx.data().client.cache_stats().await;
+    let total = stats.hits + stats.misses;
+    ctx.say(format!(
+        "{} hits, {} misses ({}% hit rate), {} cached lookups",
+        stats.hits,
+        stats.misses,
+        (stats.hits * 100).checked_div(total).unwrap_or(0),
+        stats.entries
+    ))
+    .await?;
+    Ok(())
+}
+
+/// Shut the bot down cleanly so the supervisor restarts it
+#[poise::command(slash_command, owners_only, rename = "restart-bot")]
+pub async fn restart_bot(ctx: Context<'_>) -> Result<(), Error> {
+    if !confirm_action(ctx, "Restart the bot?").await? {
+        return Ok(());
+    }
+    info!("Restart requested by {}", ctx.author().tag());
+
+    ctx.data().flush_cooldowns().await;
+    ctx.data()
+        .restart_requested
+        .store(true, std::sync::atomic::Ordering::SeqCst);
+    // start_bot returns once the shards are down, after the threads finished
+    ctx.data().shutdown.cancel();
+    ctx.framework()
+        .shard_manager()
+        .lock()
+        .await
+        .shutdown_all()
+        .await;
+    Ok(())
+}
+
+/// top level command names, for picking one to toggle
+async fn commands_autocomplete(ctx: Context<'_>, partial: &str) -> Vec<AutocompleteChoice<String>> {
+    ctx.framework()
+        .options()
+        .commands
+        .iter()
+        .filter(|c| c.name.contains(partial))
+        .take(25)
+        .map(|c| AutocompleteChoice {
+            name: c.name.clone(),
+            value: c.name.clone(),
+        })
+        .collect()
+}
+
+/// Enable or disable commands without a redeploy
+#[poise::command(
+    slash_command,
+    owners_only,
+    subcommands("toggle", "list"),
+    subcommand_required
+)]
+pub async fn togglecommand(_: Context<'_>) -> Result<(), Error> {
+    Ok(()) // never run
+}
+
+/// flips a command between enabled and disabled
+#[poise::command(slash_command, owners_only)]
+async fn toggle(
+    ctx: Context<'_>,
+    #[description = "The command to toggle"]
+    #[autocomplete = "commands_autocomplete"]
+    command: String,
+) -> Result<(), Error> {
+    if command == "togglecommand" {
+        ctx.say("Can't disable the command that turns commands back on.")
+            .await?;
+        return Ok(());
+    }
+    if !ctx
+        .framework()
+        .options()
+        .commands
+        .iter()
+        .any(|c| c.name == command)
+    {
+        ctx.say(format!("There is no `/{}` command.", command))
+            .await?;
+        return Ok(());
+    }
+
+    let pool = &ctx.data().pool;
+    let mut disabled = ctx.data().disabled_commands.write().await;
+    let now_disabled = if disabled.remove(&command) {
+        sqlx::query!(
+            r#"
+		DELETE FROM `disabled_commands` WHERE `name` = ?
+	"#,
+            command
+        )
+        .execute(pool)
+        .await?;
+        false
+    } else {
+        sqlx::query!(
+            r#"
+		INSERT IGNORE INTO `disabled_commands` (`name`) VALUES (?)
+	"#,
+            command
+        )
+        .execute(pool)
+        .await?;
+        disabled.insert(command.clone());
+        true
+    };
+    info!(
+        "{} {} /{}",
+        ctx.author().tag(),
+        if now_disabled { "disabled" } else { "enabled" },
+        command
+    );
+    ctx.say(format!(
+        "`/{}` is now {}.",
+        command,
+        if now_disabled { "disabled" } else { "enabled" }
+    ))
+    .await?;
+    Ok(())
+}
+
+/// lists disabled commands
+#[poise::command(slash_command, owners_only)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let mut disabled: Vec<String> = ctx
+        .data()
+        .disabled_commands
+        .read()
+        .await
+        .iter()
+        .map(|c| format!("`/{}`", c))
+        .collect();
+    disabled.sort();
+    ctx.say(if disabled.is_empty() {
+        "Every command is enabled.".to_owned()
+    } else {
+        disabled.join("\n")
+    })
+    .await?;
+    Ok(())
+}
+
+/// Make an online player's client run a command, for testing admin-scoped plugins
+// sm_cexec runs it from their client, so it only has the in-game access they already have
+#[poise::command(slash_command, owners_only, rename = "rcon-as", check = "rcon_check")]
+pub async fn rcon_as(
+    ctx: Context<'_>,
+    #[description = "The server the player is on"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "The player to run it as"]
+    #[autocomplete = "steam_id_autocomplete"]
+    steamid: String,
+    #[description = "The command their client should run"] command: String,
+) -> Result<(), Error> {
+    let steamid = escape_rcon_arg(steamid.trim_start_matches('#'));
+    let command = escape_rcon_arg(&command);
+    info!(
+        "{} ran `{}` as {} through /rcon-as",
+        ctx.author().tag(),
+        command,
+        steamid
+    );
+    if let Some(channel) = ctx.data().mod_log_channel {
+        let sent = channel
+            .send_message(ctx, |m| {
+                m.content(format!(
+                    "<@{}> used /rcon-as on `{}`: `{}`",
+                    ctx.author().id.0,
+                    steamid,
+                    command
+                ))
+                .allowed_mentions(|a| a.empty_parse())
+            })
+            .await;
+        if let Err(e) = sent {
+            warn!("Could not send message to mod log channel: {:?}", e);
+        }
+    }
+    rcon_and_reply(
+        ctx,
+        Some(server),
+        format!("sm_cexec \"#{}\" \"{}\"", steamid, command),
+    )
+    .await
+}
@@ -0,0 +1,127 @@
+use std::net::SocketAddr;
+
+use poise::AutocompleteChoice;
+
+use crate::discord::Context;
+use crate::Error;
+
+use super::util::{escape_rcon_arg, output_servers, rcon_user_output, servers_autocomplete};
+
+/// used when RESPAWN_PRESETS isn't set
+const DEFAULT_PRESETS: &str = "instant:mp_disable_respawn_times=1|fast:mp_disable_respawn_times=0 mp_respawnwavetime=5|default:mp_disable_respawn_times=0 mp_respawnwavetime=10";
+/// the cvars /respawntimes sets and shows
+const RESPAWN_CVARS: &str = "mp_disable_respawn_times; mp_respawnwavetime";
+
+/// a named set of respawn cvars for /respawntimes
+pub struct RespawnPreset {
+    pub name: String,
+    pub cvars: Vec<(String, String)>,
+}
+
+impl RespawnPreset {
+    /// the console line that applies the preset
+    fn command(&self) -> String {
+        self.cvars
+            .iter()
+            .map(|(cvar, value)| format!("{} \"{}\"", cvar, escape_rcon_arg(value)))
+            .collect::<Vec<String>>()
+            .join("; ")
+    }
+}
+
+/// parses presets from `name:cvar=value cvar=value|name:...`, skipping malformed ones.
+/// falls back to the built in presets if there are none
+pub fn parse_respawn_presets(s: &str) -> Vec<RespawnPreset> {
+    let parse = |s: &str| -> Vec<RespawnPreset> {
+        s.split('|')
+            .filter_map(|preset| {
+                let (name, cvars) = preset.trim().split_once(':')?;
+                let cvars: Vec<(String, String)> = cvars
+                    .split_whitespace()
+                    .map(|cvar| {
+                        let (cvar, value) = cvar.split_once('=')?;
+                        let valid = !cvar.is_empty()
+                            && cvar.chars().all(|c| c.is_ascii_alphanumeric() || c == '_');
+                        valid.then(|| (cvar.to_owned(), value.to_owned()))
+                    })
+                    .collect::<Option<_>>()?;
+                (!cvars.is_empty()).then(|| RespawnPreset {
+                    name: name.trim().to_owned(),
+                    cvars,
+                })
+            })
+            .collect()
+    };
+    let presets = parse(s);
+    if presets.is_empty() {
+        parse(DEFAULT_PRESETS)
+    } else {
+        presets
+    }
+}
+
+/// Returns the configured respawn presets
+async fn respawn_presets_autocomplete(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<AutocompleteChoice<String>> {
+    let partial = partial.to_lowercase();
+    ctx.data()
+        .respawn_presets
+        .iter()
+        .filter(|p| p.name.to_lowercase().contains(&partial))
+        .map(|p| AutocompleteChoice {
+            name: format!("{}: {}", p.name, p.command()),
+            value: p.name.clone(),
+        })
+        .collect()
+}
+
+/// Set / Get the status of the respawn timers ( resets on map change )
+#[poise::command(slash_command)]
+pub async fn respawntimes(
+    ctx: Context<'_>,
+    #[description = "The server to query"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "A set of respawn settings to apply"]
+    #[autocomplete = "respawn_presets_autocomplete"]
+    preset: Option<String>,
+    #[description = "Custom: set to instant respawn"] instant: Option<bool>,
+    #[description = "Custom: seconds between respawn waves"] wavetime: Option<u32>,
+) -> Result<(), Error> {
+    let cmd = match preset {
+        Some(name) => match ctx.data().respawn_presets.iter().find(|p| p.name == name) {
+            Some(preset) => preset.command(),
+            None => {
+                ctx.say(format!("There's no preset called {}.", name))
+                    .await?;
+                return Ok(());
+            }
+        },
+        None => {
+            let mut cvars = vec![];
+            if let Some(instant) = instant {
+                cvars.push(format!(
+                    "mp_disable_respawn_times {}",
+                    if instant { "1" } else { "0" }
+                ));
+            }
+            if let Some(wavetime) = wavetime {
+                cvars.push(format!("mp_respawnwavetime {}", wavetime));
+            }
+            cvars.join("; ")
+        }
+    };
+
+    let servers = output_servers(ctx, server)?;
+    ctx.defer().await?;
+    let reply = if cmd.is_empty() {
+        rcon_user_output(&servers, RESPAWN_CVARS.to_owned()).await
+    } else {
+        let output = rcon_user_output(&servers, cmd.clone()).await;
+        format!("Applied `{}`\n{}", cmd, output)
+    };
+    ctx.say(reply).await?;
+    Ok(())
+}
@@ -0,0 +1,124 @@
+use std::net::SocketAddr;
+
+use crate::discord::Context;
+use crate::Error;
+
+use super::util::servers_autocomplete;
+
+/// Time-based map rotations, for themed event hours
+#[poise::command(
+    slash_command,
+    subcommands("add", "list", "clear"),
+    subcommand_required,
+    default_member_permissions = "BAN_MEMBERS"
+)]
+pub async fn mapschedule(_: Context<'_>) -> Result<(), Error> {
+    Ok(()) // never run
+}
+
+/// switches a server to a map every day at the given hour
+#[poise::command(slash_command)]
+async fn add(
+    ctx: Context<'_>,
+    #[description = "The server to schedule"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "Hour of the day, in UTC"]
+    #[min = 0]
+    #[max = 23]
+    hour: u8,
+    #[description = "The map to change to"] map: String,
+) -> Result<(), Error> {
+    let server = ctx.data().server(server)?;
+    let map: String = map
+        .chars()
+        .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '_' | '-' | '/'))
+        .collect();
+    if map.is_empty() {
+        ctx.say("That isn't a valid map name.").await?;
+        return Ok(());
+    }
+    sqlx::query!(
+        r#"
+		INSERT INTO `map_schedule` (`server_addr`, `hour`, `map`)
+		VALUES (?, ?, ?)
+		ON DUPLICATE KEY UPDATE `map` = ?
+	"#,
+        server.addr.to_string(),
+        hour as i32,
+        map,
+        map
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+    ctx.say(format!(
+        "{} will change to `{}` at {:02}:00 UTC.",
+        server.emoji, map, hour
+    ))
+    .await?;
+    Ok(())
+}
+
+/// shows the map schedule
+#[poise::command(slash_command)]
+async fn list(ctx: Context<'_>) -> Result<(), Error> {
+    let entries = sqlx::query!(
+        r#"
+        SELECT `server_addr`, `hour`, `map` FROM `map_schedule`
+        ORDER BY `server_addr`, `hour`
+    "#
+    )
+    .fetch_all(&ctx.data().pool)
+    .await?;
+    if entries.is_empty() {
+        ctx.say("Nothing scheduled.").await?;
+        return Ok(());
+    }
+    let lines: Vec<String> = entries
+        .iter()
+        .map(|e| {
+            let emoji = e
+                .server_addr
+                .parse::<SocketAddr>()
+                .ok()
+                .and_then(|addr| ctx.data().server(addr).ok())
+                .map(|s| s.emoji.clone())
+                .unwrap_or(e.server_addr.clone());
+            format!("{} `{:02}:00` `{}`", emoji, e.hour, e.map)
+        })
+        .collect();
+    ctx.say(lines.join("\n")).await?;
+    Ok(())
+}
+
+/// removes scheduled maps
+#[poise::command(slash_command)]
+async fn clear(
+    ctx: Context<'_>,
+    #[description = "Only clear this server"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "Only clear this hour"]
+    #[min = 0]
+    #[max = 23]
+    hour: Option<u8>,
+) -> Result<(), Error> {
+    let result = sqlx::query!(
+        r#"
+		DELETE FROM `map_schedule`
+		WHERE (? IS NULL OR `server_addr` = ?) AND (? IS NULL OR `hour` = ?)
+	"#,
+        server.map(|s| s.to_string()),
+        server.map(|s| s.to_string()),
+        hour.map(|h| h as i32),
+        hour.map(|h| h as i32)
+    )
+    .execute(&ctx.data().pool)
+    .await?;
+    ctx.say(format!(
+        "Cleared {} scheduled maps.",
+        result.rows_affected()
+    ))
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,151 @@
+use std::net::SocketAddr;
+
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use rand::distributions::{Alphanumeric, DistString};
+
+use crate::discord::{connect_tokens, Context};
+use crate::Error;
+
+use super::util::{escape_rcon_arg, servers_autocomplete};
+
+/// a server currently set up for a match
+pub struct MatchState {
+    pub password: String,
+    pub started_by: serenity::UserId,
+    pub started_at: DateTime<Utc>,
+}
+
+/// Exec the competitive config, lock the server, and post connect info
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn matchsetup(
+    ctx: Context<'_>,
+    #[description = "The server to host the match on"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "Server password (random if not given)"] password: Option<String>,
+) -> Result<(), Error> {
+    // the reply contains the password
+    ctx.defer_ephemeral().await?;
+    let data = ctx.data();
+    let server_addr = server;
+    let server = data.server(server)?;
+
+    if let Some(state) = data.match_state.read().await.get(&server_addr) {
+        ctx.send(|m| {
+            m.content(format!(
+                "{} is already in match mode (started by <@{}> <t:{}:R>), run /matchend first.\n`connect {}; password \"{}\"`",
+                server.emoji,
+                state.started_by.0,
+                state.started_at.timestamp(),
+                server.addr,
+                state.password
+            ))
+            .ephemeral(true)
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let password = password
+        .map(|p| escape_rcon_arg(&p))
+        .unwrap_or_else(|| Alphanumeric.sample_string(&mut rand::thread_rng(), 8));
+    server
+        .controller
+        .write()
+        .await
+        .run(&format!(
+            "exec {}; sv_password \"{}\"",
+            data.match_cfg, password
+        ))
+        .await?;
+
+    data.match_state.write().await.insert(
+        server_addr,
+        MatchState {
+            password: password.clone(),
+            started_by: ctx.author().id,
+            started_at: Utc::now(),
+        },
+    );
+
+    let connect = format!("connect {}; password \"{}\"", server.addr, password);
+    if let Some(channel) = data.match_channel {
+        channel
+            .send_message(ctx, |m| {
+                m.embed(|e| {
+                    e.title(format!("{} Match server ready", server.emoji))
+                        .description(format!("`{}`", connect))
+                        .field(
+                            "Join",
+                            format!("steam://connect/{}/{}", server.addr, password),
+                            false,
+                        )
+                })
+            })
+            .await?;
+    }
+    ctx.send(|m| {
+        m.content(format!(
+            "{} executed `{}`, match mode on.\n`{}`",
+            server.emoji, data.match_cfg, connect
+        ))
+        .ephemeral(true)
+    })
+    .await?;
+    Ok(())
+}
+
+/// Revert a match server back to the public config
+#[poise::command(slash_command, default_member_permissions = "BAN_MEMBERS")]
+pub async fn matchend(
+    ctx: Context<'_>,
+    #[description = "The server hosting the match"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+) -> Result<(), Error> {
+    ctx.defer().await?;
+    let data = ctx.data();
+    let server_addr = server;
+    let server = data.server(server)?;
+
+    // private servers go back to a password only connect tokens hand out
+    let password = if server.private {
+        connect_tokens::random_password()
+    } else {
+        String::new()
+    };
+    server
+        .controller
+        .write()
+        .await
+        .run(&format!(
+            "exec {}; sv_password \"{}\"",
+            data.public_cfg, password
+        ))
+        .await?;
+    let was_match = data
+        .match_state
+        .write()
+        .await
+        .remove(&server_addr)
+        .is_some();
+
+    ctx.say(format!(
+        "{} executed `{}` and {}.{}",
+        server.emoji,
+        data.public_cfg,
+        if server.private {
+            "locked it again, use /connect to join"
+        } else {
+            "cleared the password"
+        },
+        if was_match {
+            ""
+        } else {
+            " (it wasn't in match mode)"
+        }
+    ))
+    .await?;
+    Ok(())
+}
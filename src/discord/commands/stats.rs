@@ -0,0 +1,211 @@
+use std::borrow::Cow;
+use std::net::SocketAddr;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude as serenity;
+
+use crate::discord::{population, sessions, Context};
+use crate::logs::safe_strip;
+use crate::Error;
+
+use super::util::{servers_autocomplete, steam_id_autocomplete};
+
+/// sessions started within this many days count as recent playtime
+const RECENT_DAYS: i64 = 7;
+
+/// hours and minutes, like `12h 5m`
+fn format_playtime(d: Duration) -> String {
+    let minutes = d.num_minutes();
+    if minutes < 60 {
+        format!("{}m", minutes)
+    } else {
+        format!("{}h {}m", minutes / 60, minutes % 60)
+    }
+}
+
+/// how far back the leaderboard looks
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum TopWindow {
+    #[name = "Today"]
+    Today,
+    #[name = "This week"]
+    Week,
+    #[name = "All time"]
+    AllTime,
+}
+
+impl TopWindow {
+    /// the earliest session start that counts, None for all time
+    fn since(&self) -> Option<DateTime<Utc>> {
+        let now = Utc::now();
+        match self {
+            Self::Today => now.date_naive().and_hms_opt(0, 0, 0).map(|d| d.and_utc()),
+            Self::Week => Some(now - Duration::days(RECENT_DAYS)),
+            Self::AllTime => None,
+        }
+    }
+
+    fn label(&self) -> &'static str {
+        match self {
+            Self::Today => "today",
+            Self::Week => "this week",
+            Self::AllTime => "of all time",
+        }
+    }
+}
+
+/// Show who has played the most
+#[poise::command(slash_command)]
+pub async fn top(
+    ctx: Context<'_>,
+    #[description = "How far back to look, defaults to this week"] window: Option<TopWindow>,
+    #[description = "Only count playtime on this server"]
+    #[autocomplete = "servers_autocomplete"]
+    server: Option<SocketAddr>,
+    #[description = "How many players to show, defaults to 10"]
+    #[min = 1]
+    #[max = 25]
+    count: Option<u32>,
+) -> Result<(), Error> {
+    let window = window.unwrap_or(TopWindow::Week);
+    let server = server.map(|addr| ctx.data().server(addr)).transpose()?;
+    let top = sessions::top_playtime(
+        &ctx.data().pool,
+        server.map(|s| s.addr),
+        window.since(),
+        count.unwrap_or(10).clamp(1, 25),
+    )
+    .await?;
+    if top.is_empty() {
+        ctx.say(format!("Nobody has played {}.", window.label()))
+            .await?;
+        return Ok(());
+    }
+
+    let lines: Vec<String> = top
+        .iter()
+        .enumerate()
+        .map(|(i, player)| {
+            format!(
+                "**{}.** `{}` {} {}",
+                i + 1,
+                safe_strip(&player.name),
+                player.steamid,
+                format_playtime(Duration::seconds(player.seconds))
+            )
+        })
+        .collect();
+    ctx.send(|m| {
+        m.embed(|e| {
+            e.title(format!("Top playtime {}", window.label()))
+                .description(lines.join("\n"));
+            if let Some(server) = server {
+                e.footer(|f| f.text(format!("{} only", server.name)));
+            }
+            e
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// how far back the population graph goes
+#[derive(Debug, poise::ChoiceParameter)]
+pub enum PopulationWindow {
+    #[name = "Last 24 hours"]
+    Day,
+    #[name = "Last 7 days"]
+    Week,
+}
+
+impl PopulationWindow {
+    /// (how far back, seconds per bucket, seconds per axis unit, axis unit)
+    fn shape(&self) -> (Duration, i64, i64, &'static str) {
+        match self {
+            Self::Day => (Duration::days(1), 30 * 60, 60 * 60, "h"),
+            Self::Week => (Duration::days(7), 3 * 60 * 60, 24 * 60 * 60, "d"),
+        }
+    }
+}
+
+/// Graph a server's player count over time
+#[poise::command(slash_command)]
+pub async fn population(
+    ctx: Context<'_>,
+    #[description = "The server to graph"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "How far back, defaults to the last 24 hours"] window: Option<PopulationWindow>,
+) -> Result<(), Error> {
+    let server = ctx.data().server(server)?;
+    let window = window.unwrap_or(PopulationWindow::Day);
+    let (length, bucket_secs, label_secs, label_unit) = window.shape();
+    ctx.defer().await?;
+
+    let since = Utc::now() - length;
+    let points = population::population_since(&ctx.data().pool, server, since, bucket_secs).await?;
+    if points.is_empty() {
+        ctx.say("No player counts recorded yet.").await?;
+        return Ok(());
+    }
+    let png = population::render_population(
+        &format!("{} peak players", server.name),
+        &points,
+        since,
+        label_secs,
+        label_unit,
+    )?;
+    ctx.send(|m| {
+        m.attachment(serenity::AttachmentType::Bytes {
+            data: Cow::Owned(png),
+            filename: "population.png".to_owned(),
+        })
+    })
+    .await?;
+    Ok(())
+}
+
+/// Show how long a player has spent on the servers
+#[poise::command(slash_command)]
+pub async fn playtime(
+    ctx: Context<'_>,
+    #[description = "The steam id, or the name they last played under"]
+    #[autocomplete = "steam_id_autocomplete"]
+    player: String,
+) -> Result<(), Error> {
+    let pool = &ctx.data().pool;
+    let player = player.trim().trim_start_matches('#');
+    let steamid = if player.starts_with("[U:") {
+        player.to_owned()
+    } else {
+        sessions::steamid_for_name(pool, player)
+            .await?
+            .ok_or("Nobody has played under that name.")?
+    };
+
+    let history = sessions::sessions_for(pool, &steamid).await?;
+    if history.is_empty() {
+        ctx.say(format!("`{}` hasn't played on the servers yet.", steamid))
+            .await?;
+        return Ok(());
+    }
+    let now = Utc::now();
+    let playtime = sessions::accumulate(&history, now - Duration::days(RECENT_DAYS), now);
+
+    let mut reply = format!(
+        "`{}` has played {} over {} sessions, {} in the last {} days.",
+        steamid,
+        format_playtime(playtime.total),
+        playtime.sessions,
+        format_playtime(playtime.recent),
+        RECENT_DAYS
+    );
+    if playtime.orphaned > 0 {
+        reply += &format!(
+            "\n*{} sessions were cut off by a bot restart and aren't counted.*",
+            playtime.orphaned
+        );
+    }
+    ctx.say(reply).await?;
+    Ok(())
+}
@@ -1,30 +1,324 @@
+use poise::serenity_prelude as serenity;
 use poise::AutocompleteChoice;
+use tokio::sync::RwLockWriteGuard;
+use tracing::warn;
 
 use crate::discord::Context;
+use crate::metrics::metrics;
+
+use super::mod_action::format_minutes;
 use crate::Error;
 use crate::Server;
+use std::collections::HashMap;
 use std::net::SocketAddr;
+use std::time::{Duration, Instant};
 
-pub async fn rcon_user_output(servers: &[&Server], cmd: String) -> String {
-    let mut outputs: Vec<String> = vec![];
-    for server in servers {
-        let mut rcon = server.controller.write().await;
-        let output = match rcon.run(&cmd).await {
-            Ok(output) => {
-                if output.is_empty() {
-                    ":white_check_mark:\n".to_owned()
-                } else {
-                    format!("\n```{output}```")
-                }
-            }
-            Err(e) => e.to_string(),
+use crate::tf2_rcon::{parse_status, PlayerRow, RconController, RconTimeout};
+
+/// how long a fetched player list is reused by autocompletes
+const PLAYER_CACHE_TTL: Duration = Duration::from_secs(5);
+
+/// recently fetched player lists, so autocompletes don't run status on every keystroke
+#[derive(Default)]
+pub struct PlayerCache {
+    lists: HashMap<SocketAddr, (Instant, Vec<PlayerRow>)>,
+}
+
+/// the players on the server, from the cache if it was asked recently.
+/// an unreachable server has nobody on it, and that is cached too so it isn't asked again right away
+pub async fn cached_players(ctx: Context<'_>, server: &Server) -> Vec<PlayerRow> {
+    let cache = &ctx.data().player_cache;
+    if let Some((fetched_at, rows)) = cache.read().await.lists.get(&server.addr) {
+        if fetched_at.elapsed() < PLAYER_CACHE_TTL {
+            return rows.clone();
+        }
+    }
+    let status: Result<String, Error> =
+        async { lock_rcon(server).await?.query("status").await }.await;
+    let rows = match status {
+        Ok(output) => parse_status(&output),
+        Err(_) => vec![],
+    };
+    cache
+        .write()
+        .await
+        .lists
+        .insert(server.addr, (Instant::now(), rows.clone()));
+    rows
+}
+
+/// the last player list fetched from the server, however old. players who were just banned or
+/// kicked are still in it
+pub async fn last_players(ctx: Context<'_>, server: &Server) -> Option<Vec<PlayerRow>> {
+    let cache = ctx.data().player_cache.read().await;
+    cache.lists.get(&server.addr).map(|(_, rows)| rows.clone())
+}
+
+/// the server picked in the command's `server` option, while another option is being autocompleted
+pub fn selected_server(ctx: Context<'_>) -> Option<SocketAddr> {
+    let poise::Context::Application(ctx) = ctx else {
+        return None;
+    };
+    ctx.args
+        .iter()
+        .find(|o| o.name == "server")?
+        .value
+        .as_ref()?
+        .as_str()?
+        .parse()
+        .ok()
+}
+
+/// why a command didn't run on a server
+#[derive(Debug)]
+pub enum RconError {
+    Timeout,
+    Failed(String),
+}
+
+impl std::fmt::Display for RconError {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            Self::Timeout => write!(f, "unreachable, timed out :("),
+            Self::Failed(e) => write!(f, "{}", e),
+        }
+    }
+}
+
+impl std::error::Error for RconError {}
+
+impl From<Error> for RconError {
+    fn from(e: Error) -> Self {
+        if e.is::<RconTimeout>() {
+            Self::Timeout
+        } else {
+            Self::Failed(e.to_string())
+        }
+    }
+}
+
+/// locks the server's rcon connection, unless another command has been stuck on it for a while.
+/// commands time out on their own, so only waiting for the lock is limited here
+pub async fn lock_rcon(server: &Server) -> Result<RwLockWriteGuard<'_, RconController>, RconError> {
+    tokio::time::timeout(server.rcon_timeout, server.controller.write())
+        .await
+        .map_err(|_| {
+            metrics().rcon_timeout(server.addr);
+            RconError::Timeout
+        })
+}
+
+/// runs the command on every server at once, keeping every server's result separate.
+/// results are in the same order as `servers`, and a slow server only times itself out
+pub async fn rcon_each(
+    servers: &[&Server],
+    cmd: &str,
+) -> Vec<(SocketAddr, Result<String, RconError>)> {
+    futures::future::join_all(servers.iter().map(|server| async move {
+        let result = match lock_rcon(server).await {
+            Ok(mut controller) => controller.run(cmd).await.map_err(RconError::from),
+            Err(e) => Err(e),
         };
-        outputs.push(format!("{}{}", server.emoji, output))
+        if let Err(e) = &result {
+            warn!("RCON `{}` on {} failed: {}", cmd, server.name, e);
+        }
+        (server.addr, result)
+    }))
+    .await
+}
+
+/// discord rejects messages longer than this
+pub const MAX_MESSAGE_LEN: usize = 2000;
+
+/// cuts the text to `max` characters, ending it with … if anything was cut
+pub fn truncate(text: &str, max: usize) -> String {
+    if text.chars().count() <= max {
+        return text.to_owned();
     }
+    let mut cut: String = text.chars().take(max.saturating_sub(1)).collect();
+    cut.push('…');
+    cut
+}
+
+/// formats rcon results for a discord reply, one server per line
+pub fn format_rcon_results(
+    servers: &[&Server],
+    results: &[(SocketAddr, Result<String, RconError>)],
+) -> String {
+    let mut outputs: Vec<String> = results
+        .iter()
+        .map(|(addr, result)| {
+            let emoji = servers
+                .iter()
+                .find(|s| s.addr == *addr)
+                .map_or("", |s| s.emoji.as_str());
+            let output = match result {
+                Ok(output) if output.is_empty() => ":white_check_mark:\n".to_owned(),
+                Ok(output) => format!("\n```{output}```"),
+                Err(e) => e.to_string(),
+            };
+            format!("{}{}", emoji, output)
+        })
+        .collect();
     outputs.sort();
     outputs.join("\n")
 }
 
+pub async fn rcon_user_output(servers: &[&Server], cmd: String) -> String {
+    format_rcon_results(servers, &rcon_each(servers, &cmd).await)
+}
+
+/// defers so slow servers don't run past discord's 3 seconds to answer,
+/// then replies with the command's output from each server
+pub async fn defer_and_reply(
+    ctx: Context<'_>,
+    server: Option<SocketAddr>,
+    cmd: String,
+    ephemeral: bool,
+) -> Result<(), Error> {
+    let servers = output_servers(ctx, server)?;
+    if ephemeral {
+        ctx.defer_ephemeral().await?;
+    } else {
+        ctx.defer().await?;
+    }
+    let reply = rcon_user_output(&servers, cmd).await;
+    ctx.send(|m| m.ephemeral(ephemeral).content(reply)).await?;
+    Ok(())
+}
+
+/// makes user input safe to put inside a quoted console argument.
+/// the source console has no escapes, so quotes, command separators and newlines are dropped
+pub fn escape_rcon_arg(arg: &str) -> String {
+    arg.chars()
+        .filter(|c| !matches!(c, '"' | ';' | '\n' | '\r' | '\0'))
+        .collect()
+}
+
+/// asks the invoker to confirm with buttons, only visible to them.
+/// false if they cancel or don't answer in 30s
+pub async fn confirm_action(ctx: Context<'_>, prompt: &str) -> Result<bool, Error> {
+    let uuid = ctx.id();
+    let reply = ctx
+        .send(|m| {
+            m.content(prompt).ephemeral(true).components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| {
+                        b.style(serenity::ButtonStyle::Danger)
+                            .label("Confirm")
+                            .custom_id(format!("{uuid}-confirm"))
+                    })
+                    .create_button(|b| {
+                        b.style(serenity::ButtonStyle::Secondary)
+                            .label("Cancel")
+                            .custom_id(format!("{uuid}-cancel"))
+                    })
+                })
+            })
+        })
+        .await?;
+
+    let Some(mci) = serenity::CollectComponentInteraction::new(ctx)
+        .author_id(ctx.author().id)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(30))
+        .filter(move |mci| mci.data.custom_id.starts_with(&uuid.to_string()))
+        .await
+    else {
+        reply
+            .edit(ctx, |m| {
+                m.content(format!("{prompt}\ntimed out, nothing done."))
+                    .components(|c| c)
+            })
+            .await?;
+        return Ok(false);
+    };
+
+    let confirmed = mci.data.custom_id.ends_with("-confirm");
+    mci.create_interaction_response(ctx, |r| {
+        r.kind(serenity::InteractionResponseType::UpdateMessage)
+            .interaction_response_data(|d| {
+                d.content(format!(
+                    "{prompt}\n{}",
+                    if confirmed {
+                        "confirmed."
+                    } else {
+                        "cancelled."
+                    }
+                ))
+                .components(|c| c)
+            })
+    })
+    .await?;
+    Ok(confirmed)
+}
+
+/// like poise's paginate, but for pages that are whole embeds. buttons stop working after 10 minutes
+pub async fn paginate_embeds(
+    ctx: Context<'_>,
+    pages: Vec<serenity::CreateEmbed>,
+) -> Result<(), Error> {
+    let uuid = ctx.id();
+    let prev_id = format!("{uuid}-prev");
+    let next_id = format!("{uuid}-next");
+    let mut page = 0;
+    ctx.send(|m| {
+        m.embeds.push(pages[page].clone());
+        if pages.len() > 1 {
+            m.components(|c| {
+                c.create_action_row(|r| {
+                    r.create_button(|b| b.custom_id(&prev_id).emoji('◀'))
+                        .create_button(|b| b.custom_id(&next_id).emoji('▶'))
+                })
+            });
+        }
+        m
+    })
+    .await?;
+    if pages.len() < 2 {
+        return Ok(());
+    }
+
+    while let Some(mci) = serenity::CollectComponentInteraction::new(ctx)
+        .channel_id(ctx.channel_id())
+        .timeout(Duration::from_secs(10 * 60))
+        .filter(move |mci| mci.data.custom_id.starts_with(&uuid.to_string()))
+        .await
+    {
+        if mci.data.custom_id == next_id {
+            page = (page + 1) % pages.len();
+        } else if mci.data.custom_id == prev_id {
+            page = page.checked_sub(1).unwrap_or(pages.len() - 1);
+        } else {
+            continue;
+        }
+        mci.create_interaction_response(ctx, |r| {
+            r.kind(serenity::InteractionResponseType::UpdateMessage)
+                .interaction_response_data(|d| d.set_embed(pages[page].clone()))
+        })
+        .await?;
+    }
+    Ok(())
+}
+
+/// checks if the invoking member has the given role
+pub async fn has_role(ctx: Context<'_>, role: serenity::RoleId) -> bool {
+    ctx.author_member()
+        .await
+        .is_some_and(|m| m.roles.contains(&role))
+}
+
+/// whether the invoker skips cooldowns: they have the mod role and MOD_COOLDOWN_EXEMPT isn't off
+pub async fn is_exempt(ctx: Context<'_>) -> bool {
+    if !ctx.data().mods_bypass_cooldowns {
+        return false;
+    }
+    match ctx.data().mod_role {
+        Some(role) => has_role(ctx, role).await,
+        None => false,
+    }
+}
+
 pub fn output_servers(ctx: Context<'_>, addr: Option<SocketAddr>) -> Result<Vec<&Server>, Error> {
     Ok(if let Some(addr) = addr {
         vec![ctx.data().server(addr)?]
@@ -33,13 +327,117 @@ pub fn output_servers(ctx: Context<'_>, addr: Option<SocketAddr>) -> Result<Vec<
     })
 }
 
+/// the servers named in a comma separated list, by name or address, in the order given
+pub fn listed_servers<'a>(ctx: Context<'a>, list: &str) -> Result<Vec<&'a Server>, Error> {
+    let mut servers: Vec<&Server> = vec![];
+    for entry in list.split(',').map(str::trim).filter(|e| !e.is_empty()) {
+        let server = ctx
+            .data()
+            .servers
+            .values()
+            .find(|s| s.name.eq_ignore_ascii_case(entry) || s.addr.to_string() == entry)
+            .ok_or_else(|| format!("No server called `{}`", entry))?;
+        if !servers.iter().any(|s| s.addr == server.addr) {
+            servers.push(server);
+        }
+    }
+    if servers.is_empty() {
+        return Err("Name at least one server.".into());
+    }
+    Ok(servers)
+}
+
+/// an embed saying which servers the command worked on, followed by any output
+pub fn rcon_summary_embed(
+    servers: &[&Server],
+    results: &[(SocketAddr, Result<String, RconError>)],
+) -> serenity::CreateEmbed {
+    let mut results: Vec<(&Server, &Result<String, RconError>)> = results
+        .iter()
+        .filter_map(|(addr, r)| Some((*servers.iter().find(|s| s.addr == *addr)?, r)))
+        .collect();
+    results.sort_by_key(|(s, _)| &s.name);
+
+    let summary: Vec<String> = results
+        .iter()
+        .map(|(s, r)| match r {
+            Ok(_) => format!("✅ {} {}", s.emoji, s.name),
+            Err(e) => format!("❌ {} {} ({})", s.emoji, s.name, e),
+        })
+        .collect();
+    let failed = results.iter().any(|(_, r)| r.is_err());
+
+    let mut e = serenity::CreateEmbed::default();
+    e.description(summary.join("\n")).color(if failed {
+        serenity::Color::RED
+    } else {
+        serenity::Color::DARK_GREEN
+    });
+    for (s, r) in results {
+        if let Ok(output) = r {
+            if !output.trim().is_empty() {
+                let output: String = output.chars().take(1000).collect();
+                e.field(
+                    format!("{} {}", s.emoji, s.name),
+                    format!("```{}```", output),
+                    false,
+                );
+            }
+        }
+    }
+    e
+}
+
+/// runs the command and replies with the output. several servers get a summary embed
 pub async fn rcon_and_reply(
     ctx: Context<'_>,
     server: Option<SocketAddr>,
     cmd: String,
 ) -> Result<(), Error> {
-    ctx.say(rcon_user_output(&output_servers(ctx, server)?, cmd).await)
+    rcon_and_reply_with_note(ctx, server, cmd, None).await
+}
+
+/// like rcon_and_reply, with a line of context above the output
+pub async fn rcon_and_reply_with_note(
+    ctx: Context<'_>,
+    server: Option<SocketAddr>,
+    cmd: String,
+    note: Option<String>,
+) -> Result<(), Error> {
+    let servers = output_servers(ctx, server)?;
+    // a server can take longer than discord waits for an answer
+    ctx.defer().await?;
+    let results = rcon_each(&servers, &cmd).await;
+    reply_rcon_results(ctx, &servers, &results, note).await
+}
+
+/// replies with the results of rcon_each, as a summary embed if there are several servers
+pub async fn reply_rcon_results(
+    ctx: Context<'_>,
+    servers: &[&Server],
+    results: &[(SocketAddr, Result<String, RconError>)],
+    note: Option<String>,
+) -> Result<(), Error> {
+    if servers.len() > 1 {
+        let mut embed = rcon_summary_embed(servers, results);
+        if let Some(note) = note {
+            embed.title(note);
+        }
+        ctx.send(|m| {
+            m.embed(|e| {
+                *e = embed;
+                e
+            })
+        })
+        .await?;
+    } else {
+        let output = format_rcon_results(servers, results);
+        ctx.say(match note {
+            Some(note) => format!("{}\n{}", note, output),
+            None => output,
+        })
         .await?;
+    }
     Ok(())
 }
 
@@ -48,20 +446,73 @@ pub async fn users_autocomplete(
     ctx: Context<'_>,
     partial: &str,
 ) -> Vec<AutocompleteChoice<String>> {
+    let partial = partial.to_lowercase();
+    // only the chosen server's players, if one was picked already
+    let servers: Vec<&Server> = match selected_server(ctx).and_then(|a| ctx.data().server(a).ok()) {
+        Some(server) => vec![server],
+        None => ctx.data().servers.values().collect(),
+    };
     let mut res = vec![];
-    for (_addr, server) in &ctx.data().servers {
-        if let Some(state) = server.controller.write().await.status().await.ok() {
-            res.extend(
-                state
-                    .players
-                    .iter()
-                    .filter(|p| p.name.to_lowercase().contains(&partial.to_lowercase()))
-                    .map(|p| AutocompleteChoice {
-                        name: p.name.clone(),
-                        value: p.name.clone(),
-                    }),
-            );
+    for server in servers {
+        res.extend(
+            cached_players(ctx, server)
+                .await
+                .into_iter()
+                .filter(|p| !p.is_bot() && p.name.to_lowercase().contains(&partial))
+                .map(|p| AutocompleteChoice {
+                    name: p.name.clone(),
+                    value: p.name,
+                }),
+        );
+    }
+    res.truncate(25);
+    res
+}
+
+/// Returns the currently banned steamids, labelled with who they belong to
+pub async fn banned_players_autocomplete(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<AutocompleteChoice<String>> {
+    // % and _ in what was typed are matched literally
+    let pattern = format!(
+        "%{}%",
+        partial
+            .replace('\\', "\\\\")
+            .replace('%', "\\%")
+            .replace('_', "\\_")
+    );
+    let Ok(bans) = sqlx::query!(
+        r#"
+        SELECT `target`, `steamid` FROM `bans`
+        WHERE `lifted` = false AND (`expires_at` IS NULL OR `expires_at` > ?)
+        AND (`target` LIKE ? OR `steamid` LIKE ?)
+        ORDER BY `created_at` DESC LIMIT 25
+    "#,
+        chrono::Utc::now(),
+        pattern,
+        pattern
+    )
+    .fetch_all(&ctx.data().pool)
+    .await
+    else {
+        return vec![];
+    };
+    let mut res: Vec<AutocompleteChoice<String>> = vec![];
+    for ban in bans {
+        // username bans are only unbannable by the steamid they resolved to
+        let steamid = ban.steamid.unwrap_or(ban.target.clone());
+        if res.iter().any(|c| c.value == steamid) {
+            continue;
         }
+        res.push(AutocompleteChoice {
+            name: if ban.target == steamid {
+                steamid.clone()
+            } else {
+                format!("{} {}", ban.target, steamid)
+            },
+            value: steamid,
+        });
     }
     res
 }
@@ -72,8 +523,8 @@ pub async fn steam_id_autocomplete(
     partial: &str,
 ) -> Vec<AutocompleteChoice<String>> {
     let mut res = vec![];
-    for (_addr, server) in &ctx.data().servers {
-        if let Some(state) = server.controller.write().await.status().await.ok() {
+    for server in ctx.data().servers.values() {
+        if let Ok(state) = server.controller.write().await.status().await {
             res.extend(
                 state
                     .players
@@ -89,18 +540,119 @@ pub async fn steam_id_autocomplete(
     res
 }
 
+/// Returns the configured ban presets
+pub async fn ban_presets_autocomplete(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<AutocompleteChoice<String>> {
+    let partial = partial.to_lowercase();
+    ctx.data()
+        .ban_presets
+        .iter()
+        .filter(|p| p.name.to_lowercase().contains(&partial))
+        .map(|p| AutocompleteChoice {
+            name: format!("{} ({}): {}", p.name, format_minutes(p.minutes), p.reason),
+            value: p.name.clone(),
+        })
+        .collect()
+}
+
+/// suggests the canned mod reasons, anything else typed is still accepted
+pub async fn reasons_autocomplete(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<AutocompleteChoice<String>> {
+    let partial = partial.to_lowercase();
+    ctx.data()
+        .mod_reasons
+        .read()
+        .await
+        .iter()
+        .filter(|r| r.to_lowercase().contains(&partial))
+        .take(25)
+        .map(|r| AutocompleteChoice {
+            name: r.clone(),
+            value: r.clone(),
+        })
+        .collect()
+}
+
+/// Completes the last server in a comma separated list
+pub async fn server_list_autocomplete(
+    ctx: Context<'_>,
+    partial: &str,
+) -> Vec<AutocompleteChoice<String>> {
+    let (done, last) = match partial.rsplit_once(',') {
+        Some((done, last)) => (format!("{},", done), last.trim().to_lowercase()),
+        None => (String::new(), partial.trim().to_lowercase()),
+    };
+    let chosen: Vec<&str> = done.split(',').map(str::trim).collect();
+    ctx.data()
+        .server_choices
+        .iter()
+        .filter(|(name, _)| !chosen.contains(&name.as_str()) && name.to_lowercase().contains(&last))
+        .map(|(name, _)| AutocompleteChoice {
+            name: format!("{}{}", done, name),
+            value: format!("{}{}", done, name),
+        })
+        .collect()
+}
+
 /// Returns the list of connected servers
 pub async fn servers_autocomplete(
     ctx: Context<'_>,
     partial: &str,
 ) -> Vec<AutocompleteChoice<SocketAddr>> {
+    let partial = partial.to_lowercase();
     ctx.data()
-        .servers
+        .server_choices
         .iter()
-        .filter(|(_addr, s)| s.name.to_lowercase().contains(&partial.to_lowercase()))
-        .map(|(addr, s)| AutocompleteChoice {
-            name: s.name.clone(),
-            value: addr.clone(),
+        .filter(|(name, _addr)| name.to_lowercase().contains(&partial))
+        .map(|(name, addr)| AutocompleteChoice {
+            name: name.clone(),
+            value: *addr,
         })
         .collect()
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escape_leaves_normal_names_alone() {
+        assert_eq!(escape_rcon_arg("tiny kitty"), "tiny kitty");
+        assert_eq!(escape_rcon_arg("[TKGP] ねこ (x)"), "[TKGP] ねこ (x)");
+    }
+
+    #[test]
+    fn escape_drops_quotes_separators_and_newlines() {
+        let names = [
+            r#"evil" ; sm_rcon quit"#,
+            "\"\"\"",
+            "a;b;c",
+            "line\nbreak\r\nquit",
+            "nul\0byte",
+        ];
+        for name in names {
+            let escaped = escape_rcon_arg(name);
+            assert!(
+                !escaped.contains(['"', ';', '\n', '\r', '\0']),
+                "{:?} escaped to {:?}",
+                name,
+                escaped
+            );
+        }
+        assert_eq!(
+            escape_rcon_arg(r#"evil" ; sm_rcon quit"#),
+            "evil  sm_rcon quit"
+        );
+    }
+
+    #[test]
+    fn truncate_marks_what_was_cut() {
+        assert_eq!(truncate("meow", 4), "meow");
+        assert_eq!(truncate("meowmeow", 5), "meow…");
+        assert_eq!(truncate("ñññ", 2).chars().count(), 2);
+    }
+}
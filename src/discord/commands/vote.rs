@@ -0,0 +1,110 @@
+use std::net::SocketAddr;
+
+use chrono::Utc;
+
+use crate::discord::map_vote::{change_if_won, vote_button};
+use crate::discord::Context;
+use crate::{Error, Server};
+
+use super::map::{installed_maps_autocomplete, map_installed};
+use super::util::servers_autocomplete;
+
+/// updates the server's tally message, or posts one if there isn't one yet
+async fn show_tally(ctx: Context<'_>, server: &Server, note: String) -> Result<(), Error> {
+    let votes = &ctx.data().map_votes;
+    let embed = votes.read().await.tally_embed(server);
+    let changed = change_if_won(votes, server).await?;
+    let note = match &changed {
+        Some(map) => format!("Vote passed, changing to `{}`", map),
+        None => note,
+    };
+
+    let live = votes.read().await.message(server.addr);
+    if let Some((channel, message)) = live {
+        let edited = channel
+            .edit_message(ctx, message, |m| {
+                m.set_embed(embed.clone());
+                if changed.is_some() {
+                    m.content(&note).components(|c| c);
+                }
+                m
+            })
+            .await;
+        if edited.is_ok() {
+            ctx.send(|m| m.content(note).ephemeral(true)).await?;
+            return Ok(());
+        }
+    }
+
+    let reply = ctx
+        .send(|m| {
+            m.content(&note).embed(|e| {
+                *e = embed;
+                e
+            });
+            if changed.is_none() {
+                m.components(|c| vote_button(c, server.addr));
+            }
+            m
+        })
+        .await?;
+    if changed.is_none() {
+        let message = reply.message().await?;
+        votes.write().await.set_message(server.addr, &message);
+    }
+    Ok(())
+}
+
+/// Nominate the next map for a server
+#[poise::command(slash_command)]
+pub async fn nominate(
+    ctx: Context<'_>,
+    #[description = "The server to nominate on"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+    #[description = "The map to nominate"]
+    #[autocomplete = "installed_maps_autocomplete"]
+    map: String,
+) -> Result<(), Error> {
+    let server = ctx.data().server(server)?;
+    ctx.defer().await?;
+    if !map_installed(ctx, server, &map).await? {
+        ctx.say(format!("`{}` is not installed on {}.", map, server.name))
+            .await?;
+        return Ok(());
+    }
+    let replaced =
+        ctx.data()
+            .map_votes
+            .write()
+            .await
+            .nominate(server.addr, ctx.author().id, map.clone());
+    let note = match replaced {
+        Some(old) => format!("Nominated `{}` instead of `{}`", map, old),
+        None => format!("Nominated `{}`", map),
+    };
+    show_tally(ctx, server, note).await
+}
+
+/// Vote to change the map on a server
+#[poise::command(slash_command)]
+pub async fn rtv(
+    ctx: Context<'_>,
+    #[description = "The server to vote on"]
+    #[autocomplete = "servers_autocomplete"]
+    server: SocketAddr,
+) -> Result<(), Error> {
+    let server = ctx.data().server(server)?;
+    if !ctx
+        .data()
+        .map_votes
+        .write()
+        .await
+        .vote(server.addr, ctx.author().id, Utc::now())
+    {
+        ctx.send(|m| m.content("You already voted.").ephemeral(true))
+            .await?;
+        return Ok(());
+    }
+    show_tally(ctx, server, "Voted to change the map".to_owned()).await
+}
@@ -0,0 +1,160 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude as serenity;
+use rand::distributions::{Alphanumeric, DistString};
+use sqlx::{MySql, Pool};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, Instrument};
+
+use super::commands::MatchState;
+use crate::{parse_env_opt, Error, Server};
+
+/// generates a random password suitable for sv_password
+pub fn random_password() -> String {
+    Alphanumeric.sample_string(&mut rand::thread_rng(), 12)
+}
+
+/// how long an issued connect token stays valid for
+pub fn token_lifetime() -> Duration {
+    Duration::minutes(parse_env_opt("CONNECT_TOKEN_MINUTES").unwrap_or(10))
+}
+
+/// sets a fresh password on the server for the given user and records it.
+/// returns the password and when it expires.
+pub async fn issue_connect_token(
+    pool: &Pool<MySql>,
+    server: &Server,
+    user: serenity::UserId,
+) -> Result<(String, DateTime<Utc>), Error> {
+    let token = random_password();
+    let expires_at = Utc::now() + token_lifetime();
+
+    server
+        .controller
+        .write()
+        .await
+        .run(&format!("sv_password \"{}\"", token))
+        .await?;
+
+    let server_addr = server.addr.to_string();
+    // the password was rotated, so every older token for this server is dead
+    sqlx::query!(
+        r#"
+		UPDATE `connect_tokens` SET `revoked` = true
+		WHERE `server_addr` = ? AND `revoked` = false
+	"#,
+        server_addr
+    )
+    .execute(pool)
+    .await?;
+
+    sqlx::query!(
+        r#"
+		INSERT INTO `connect_tokens` (`user_id`, `server_addr`, `token`, `expires_at`)
+		VALUES (?, ?, ?, ?)
+	"#,
+        user.0.to_string(),
+        server_addr,
+        token,
+        expires_at
+    )
+    .execute(pool)
+    .await?;
+
+    Ok((token, expires_at))
+}
+
+/// rotates the server's password and revokes the tokens that expired before `now`
+async fn lock_server(
+    pool: &Pool<MySql>,
+    server: &Server,
+    server_addr: &str,
+    now: DateTime<Utc>,
+) -> Result<(), Error> {
+    server
+        .controller
+        .write()
+        .await
+        .run(&format!("sv_password \"{}\"", random_password()))
+        .await?;
+    sqlx::query!(
+        r#"
+		UPDATE `connect_tokens` SET `revoked` = true
+		WHERE `server_addr` = ? AND `revoked` = false AND `expires_at` < ?
+	"#,
+        server_addr,
+        now
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// locks the server back up with a password nobody knows once its latest token expires.
+/// servers in match mode keep the match password until /matchend
+async fn expire_tokens(
+    pool: &Pool<MySql>,
+    servers: &HashMap<SocketAddr, Server>,
+    match_state: &RwLock<HashMap<SocketAddr, MatchState>>,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let expired = sqlx::query!(
+        r#"
+        SELECT `server_addr` FROM `connect_tokens`
+        WHERE `revoked` = false AND `expires_at` < ?
+    "#,
+        now
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for row in expired {
+        let Some(server) = row
+            .server_addr
+            .parse::<SocketAddr>()
+            .ok()
+            .and_then(|addr| servers.get(&addr))
+        else {
+            continue;
+        };
+        if match_state.read().await.contains_key(&server.addr) {
+            continue;
+        }
+        match lock_server(pool, server, &row.server_addr, now).await {
+            Ok(()) => info!(
+                "Connect token for {} expired, password rotated.",
+                server.name
+            ),
+            // leave the tokens unrevoked so the next pass tries again
+            Err(e) => error!("Could not rotate the password on {}: {:?}", server.name, e),
+        }
+    }
+    Ok(())
+}
+
+/// periodically rotates the password of private servers whose connect tokens have expired
+pub fn spawn_token_expiry_thread(
+    servers: HashMap<SocketAddr, Server>,
+    pool: Pool<MySql>,
+    match_state: Arc<RwLock<HashMap<SocketAddr, MatchState>>>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let mut interval = time::interval(time::Duration::from_secs(30));
+    let thread = async move {
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            if let Err(e) = expire_tokens(&pool, &servers, &match_state).await {
+                error!("Could not expire connect tokens: {:?}", e);
+            }
+        }
+    };
+    tokio::spawn(thread.instrument(info_span!("token_expiry")))
+}
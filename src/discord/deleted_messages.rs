@@ -0,0 +1,105 @@
+use chrono::{Duration, Utc};
+use poise::serenity_prelude as serenity;
+use sqlx::{MySql, Pool};
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info_span, warn, Instrument};
+
+use crate::{parse_env_opt, Error};
+
+/// how long deleted messages are kept for /deletedsearch, from DELETED_MESSAGE_RETENTION_DAYS
+pub fn retention() -> Duration {
+    Duration::days(parse_env_opt("DELETED_MESSAGE_RETENTION_DAYS").unwrap_or(30))
+}
+
+/// attachments past this in total are linked instead of uploaded again, it's the upload limit without boosts
+const MAX_REHOST_BYTES: u64 = 8 * 1024 * 1024;
+
+/// a deleted message's attachments, fetched before discord drops them from its cdn.
+/// also used for messages the bot deletes itself, like the new account gate's
+#[derive(Default)]
+pub struct SavedAttachments {
+    /// (filename, bytes) to upload with the log
+    pub files: Vec<(String, Vec<u8>)>,
+    /// attachments too large to upload, or already gone
+    pub links: Vec<String>,
+    /// the first image uploaded, shown in the log embed
+    pub image: Option<String>,
+}
+
+/// downloads the message's attachments to post them again. has to happen
+/// right away, deleted attachments only stay on the cdn for a short while
+pub async fn save_attachments(message: &serenity::Message) -> SavedAttachments {
+    let mut saved = SavedAttachments::default();
+    let mut total = 0;
+    for attachment in &message.attachments {
+        if total + attachment.size > MAX_REHOST_BYTES {
+            saved.links.push(attachment.url.clone());
+            continue;
+        }
+        match attachment.download().await {
+            Ok(bytes) => {
+                let is_image = attachment
+                    .content_type
+                    .as_ref()
+                    .is_some_and(|t| t.starts_with("image/"));
+                if is_image && saved.image.is_none() {
+                    saved.image = Some(format!("attachment://{}", attachment.filename));
+                }
+                total += attachment.size;
+                saved.files.push((attachment.filename.clone(), bytes));
+            }
+            Err(e) => {
+                warn!("Could not save deleted attachment: {:?}", e);
+                saved.links.push(attachment.url.clone());
+            }
+        }
+    }
+    saved
+}
+
+/// stores a deleted message so it can be searched later
+pub async fn record_deleted_message(
+    pool: &Pool<MySql>,
+    message: &serenity::Message,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		INSERT INTO `deleted_messages` (`author_id`, `channel_id`, `content`)
+		VALUES (?, ?, ?)
+	"#,
+        message.author.id.0.to_string(),
+        message.channel_id.0.to_string(),
+        message.content
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// forgets deleted messages older than the retention window, so nothing is kept forever
+pub fn spawn_prune_thread(pool: Pool<MySql>, shutdown: CancellationToken) -> JoinHandle<()> {
+    let mut interval = time::interval(time::Duration::from_secs(60 * 60));
+    let thread = async move {
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            let cutoff = Utc::now() - retention();
+            if let Err(e) = sqlx::query!(
+                r#"
+		DELETE FROM `deleted_messages` WHERE `deleted_at` < ?
+	"#,
+                cutoff
+            )
+            .execute(&pool)
+            .await
+            {
+                error!("Could not prune deleted messages: {:?}", e);
+            }
+        }
+    };
+    tokio::spawn(thread.instrument(info_span!("prune_deleted_messages")))
+}
@@ -0,0 +1,323 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use ring::aead::{self, Aad, LessSafeKey, Nonce, UnboundKey};
+use ring::rand::{SecureRandom, SystemRandom};
+use sqlx::{MySql, Pool};
+use tracing::{info, warn};
+
+use super::PoiseData;
+use crate::{parse_env_opt, Error};
+
+const RESOLVE: &str = "feedback";
+/// reactions added to posted feedback, counted for and against
+pub const UPVOTE: &str = "👍";
+pub const DOWNVOTE: &str = "👎";
+/// open feedback shown at once on /feedback_list, one resolve button each
+const PAGE_SIZE: usize = 5;
+
+/// encrypts feedback authors with FEEDBACK_KEY, so the table alone doesn't say who sent what.
+/// only the bot can open them again, for replies and /feedback_whois
+pub struct AuthorSeal {
+    key: LessSafeKey,
+    rng: SystemRandom,
+}
+
+impl AuthorSeal {
+    /// constructs the seal from FEEDBACK_KEY, any long random string. None if it isn't set
+    pub fn from_env() -> Option<Self> {
+        let secret: String = parse_env_opt("FEEDBACK_KEY").filter(|k: &String| !k.is_empty())?;
+        let key = ring::digest::digest(&ring::digest::SHA256, secret.as_bytes());
+        let key = UnboundKey::new(&aead::AES_256_GCM, key.as_ref()).ok()?;
+        Some(Self {
+            key: LessSafeKey::new(key),
+            rng: SystemRandom::new(),
+        })
+    }
+
+    /// the user id encrypted under a fresh nonce, hex encoded as nonce then ciphertext
+    pub fn seal(&self, user: serenity::UserId) -> Result<String, Error> {
+        let mut nonce = [0u8; aead::NONCE_LEN];
+        self.rng
+            .fill(&mut nonce)
+            .map_err(|_| "Could not generate a nonce")?;
+        let mut sealed = user.0.to_le_bytes().to_vec();
+        self.key
+            .seal_in_place_append_tag(
+                Nonce::assume_unique_for_key(nonce),
+                Aad::empty(),
+                &mut sealed,
+            )
+            .map_err(|_| "Could not seal the feedback author")?;
+        Ok(hex::encode([&nonce[..], &sealed].concat()))
+    }
+
+    /// the user id back out of a sealed author
+    pub fn open(&self, sealed: &str) -> Result<serenity::UserId, Error> {
+        let mut bytes = hex::decode(sealed)?;
+        if bytes.len() < aead::NONCE_LEN {
+            return Err("Sealed feedback author is too short".into());
+        }
+        let (nonce, sealed) = bytes.split_at_mut(aead::NONCE_LEN);
+        let nonce = Nonce::try_assume_unique_for_key(nonce).map_err(|_| "Malformed nonce")?;
+        let user = self
+            .key
+            .open_in_place(nonce, Aad::empty(), sealed)
+            .map_err(|_| "Could not open the feedback author, was FEEDBACK_KEY changed?")?;
+        Ok(serenity::UserId(u64::from_le_bytes(user.try_into()?)))
+    }
+}
+
+/// a piece of /feedback. the author is sealed so staff can reply through the bot,
+/// but never see who it is
+pub struct Feedback {
+    pub id: u64,
+    /// None when FEEDBACK_KEY wasn't set at the time
+    pub sealed_author: Option<String>,
+    pub message: String,
+    pub created_at: DateTime<Utc>,
+}
+
+impl Feedback {
+    /// who sent the feedback, if it was stored with the seal
+    pub fn author(&self, seal: Option<&AuthorSeal>) -> Result<Option<serenity::UserId>, Error> {
+        match (seal, &self.sealed_author) {
+            (Some(seal), Some(sealed)) => Ok(Some(seal.open(sealed)?)),
+            _ => Ok(None),
+        }
+    }
+}
+
+/// seals the author ids stored in plain before authors were sealed, then drops their column.
+/// without the key they're left as they are until it is set.
+/// the queries aren't checked at compile time, since the column is gone once this has run
+pub async fn seal_plain_authors(
+    pool: &Pool<MySql>,
+    seal: Option<&AuthorSeal>,
+) -> Result<(), Error> {
+    let columns: i64 = sqlx::query_scalar(
+        r#"
+		SELECT COUNT(*) FROM information_schema.COLUMNS
+		WHERE `TABLE_SCHEMA` = DATABASE() AND `TABLE_NAME` = 'feedback' AND `COLUMN_NAME` = 'author_id'
+	"#,
+    )
+    .fetch_one(pool)
+    .await?;
+    if columns == 0 {
+        return Ok(());
+    }
+    let plain: Vec<(u64, String)> =
+        sqlx::query_as(r#"SELECT `id`, `author_id` FROM `feedback` WHERE `author_id` IS NOT NULL"#)
+            .fetch_all(pool)
+            .await?;
+    if !plain.is_empty() {
+        let Some(seal) = seal else {
+            warn!(
+                "{} feedback authors stay unsealed until FEEDBACK_KEY is set",
+                plain.len()
+            );
+            return Ok(());
+        };
+        for (id, author_id) in &plain {
+            let sealed = match author_id.parse() {
+                Ok(user) => Some(seal.seal(serenity::UserId(user))?),
+                Err(_) => None,
+            };
+            sqlx::query(
+                r#"UPDATE `feedback` SET `sealed_author` = ?, `author_id` = NULL WHERE `id` = ?"#,
+            )
+            .bind(sealed)
+            .bind(id)
+            .execute(pool)
+            .await?;
+        }
+        info!("Sealed {} feedback authors", plain.len());
+    }
+    sqlx::query(r#"ALTER TABLE `feedback` DROP COLUMN `author_id`"#)
+        .execute(pool)
+        .await?;
+    Ok(())
+}
+
+/// stores new feedback as open, returning its id
+pub async fn insert_feedback(
+    pool: &Pool<MySql>,
+    sealed_author: Option<String>,
+    message: &str,
+) -> Result<u64, Error> {
+    let result = sqlx::query!(
+        r#"
+		INSERT INTO `feedback` (`sealed_author`, `message`) VALUES (?, ?)
+	"#,
+        sealed_author,
+        message
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.last_insert_id())
+}
+
+/// remembers the message the feedback was posted as, so reactions on it count as votes
+pub async fn set_message(
+    pool: &Pool<MySql>,
+    id: u64,
+    message: serenity::MessageId,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"UPDATE `feedback` SET `message_id` = ? WHERE `id` = ?"#,
+        message.0.to_string(),
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// what a reaction is worth on posted feedback, None if it isn't a vote
+fn vote(emoji: &serenity::ReactionType) -> Option<i32> {
+    match emoji {
+        serenity::ReactionType::Unicode(e) if e == UPVOTE => Some(1),
+        serenity::ReactionType::Unicode(e) if e == DOWNVOTE => Some(-1),
+        _ => None,
+    }
+}
+
+/// keeps the score of posted feedback in step with its 👍 and 👎 reactions.
+/// reactions on other messages and by bots, including the bot's own, are ignored
+pub async fn handle_vote(
+    ctx: &serenity::Context,
+    reaction: &serenity::Reaction,
+    added: bool,
+    data: &PoiseData,
+) -> Result<(), Error> {
+    let Some(vote) = vote(&reaction.emoji) else {
+        return Ok(());
+    };
+    if reaction.user(ctx).await?.bot {
+        return Ok(());
+    }
+    let delta = if added { vote } else { -vote };
+    sqlx::query!(
+        r#"UPDATE `feedback` SET `score` = `score` + ? WHERE `message_id` = ?"#,
+        delta,
+        reaction.message_id.0.to_string()
+    )
+    .execute(&data.pool)
+    .await?;
+    Ok(())
+}
+
+/// the feedback with the id, resolved or not
+pub async fn feedback_by_id(pool: &Pool<MySql>, id: u64) -> Result<Option<Feedback>, Error> {
+    Ok(sqlx::query_as!(
+        Feedback,
+        r#"
+        SELECT `id`, `sealed_author`, `message`, `created_at` FROM `feedback` WHERE `id` = ?
+    "#,
+        id
+    )
+    .fetch_optional(pool)
+    .await?)
+}
+
+/// feedback nobody has resolved yet, oldest first
+pub async fn open_feedback(pool: &Pool<MySql>) -> Result<Vec<Feedback>, Error> {
+    Ok(sqlx::query_as!(
+        Feedback,
+        r#"
+        SELECT `id`, `sealed_author`, `message`, `created_at` FROM `feedback`
+        WHERE `status` = 'open' ORDER BY `created_at`
+    "#
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// marks the feedback resolved. false if it doesn't exist or already was
+pub async fn resolve(pool: &Pool<MySql>, id: u64, by: serenity::UserId) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"
+		UPDATE `feedback` SET `status` = 'resolved', `resolved_by` = ?, `resolved_at` = ?
+		WHERE `id` = ? AND `status` = 'open'
+	"#,
+        by.0.to_string(),
+        Utc::now(),
+        id
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// the oldest open feedback with a button to resolve each
+pub fn list_message(open: &[Feedback]) -> (serenity::CreateEmbed, serenity::CreateComponents) {
+    let mut embed = serenity::CreateEmbed::default();
+    embed.title(format!("{} open feedback", open.len()));
+    if open.is_empty() {
+        embed.description("Nothing left to look at :3");
+    }
+    for f in open.iter().take(PAGE_SIZE) {
+        let mut message: String = f.message.chars().take(300).collect();
+        if message.len() < f.message.len() {
+            message += "...";
+        }
+        embed.field(
+            format!("#{} <t:{}:R>", f.id, f.created_at.timestamp()),
+            message,
+            false,
+        );
+    }
+    if open.len() > PAGE_SIZE {
+        embed.footer(|f| f.text(format!("{} more after these", open.len() - PAGE_SIZE)));
+    }
+
+    let mut components = serenity::CreateComponents::default();
+    if !open.is_empty() {
+        components.create_action_row(|r| {
+            for f in open.iter().take(PAGE_SIZE) {
+                r.create_button(|b| {
+                    b.style(serenity::ButtonStyle::Success)
+                        .label(format!("Resolve #{}", f.id))
+                        .custom_id(format!("{}:{}", RESOLVE, f.id))
+                });
+            }
+            r
+        });
+    }
+    (embed, components)
+}
+
+/// handles the resolve buttons on /feedback_list
+pub async fn handle_resolve(
+    ctx: &serenity::Context,
+    mci: &serenity::MessageComponentInteraction,
+    data: &PoiseData,
+) -> Result<(), Error> {
+    let Some((RESOLVE, id)) = mci.data.custom_id.split_once(':') else {
+        return Ok(());
+    };
+    let id: u64 = id.parse()?;
+    if resolve(&data.pool, id, mci.user.id).await? {
+        info!("{} resolved feedback #{}", mci.user.tag(), id);
+    }
+
+    let (embed, components) = list_message(&open_feedback(&data.pool).await?);
+    mci.create_interaction_response(ctx, |r| {
+        r.kind(serenity::InteractionResponseType::UpdateMessage)
+            .interaction_response_data(|d| d.set_embed(embed).set_components(components))
+    })
+    .await?;
+    Ok(())
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn only_thumbs_are_votes() {
+        let unicode = |e: &str| serenity::ReactionType::Unicode(e.to_owned());
+        assert_eq!(vote(&unicode(UPVOTE)), Some(1));
+        assert_eq!(vote(&unicode(DOWNVOTE)), Some(-1));
+        assert_eq!(vote(&unicode("🐱")), None);
+    }
+}
@@ -0,0 +1,192 @@
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude as serenity;
+use std::collections::{HashMap, VecDeque};
+use std::net::SocketAddr;
+
+use crate::logs::{safe_strip, KillEvent};
+use crate::{parse_env_opt, Server};
+
+/// kills and deaths of each player on the current map, for /playerinfo
+#[derive(Default)]
+pub struct MapStats {
+    /// (kills, deaths) by server and steamid
+    players: HashMap<SocketAddr, HashMap<String, (u32, u32)>>,
+}
+
+impl MapStats {
+    /// counts the kill for the attacker and the death for the victim
+    pub fn record(&mut self, server: SocketAddr, kill: &KillEvent) {
+        let players = self.players.entry(server).or_default();
+        // a suicide is only a death
+        if kill.attacker.steamid != kill.victim.steamid {
+            players.entry(kill.attacker.steamid.clone()).or_default().0 += 1;
+        }
+        players.entry(kill.victim.steamid.clone()).or_default().1 += 1;
+    }
+
+    /// a new map starts everyone on the server over
+    pub fn map_started(&mut self, server: SocketAddr) {
+        self.players.remove(&server);
+    }
+
+    /// the player's kills and deaths on this map, None if they have neither yet
+    pub fn kd(&self, server: SocketAddr, steamid: &str) -> Option<(u32, u32)> {
+        self.players.get(&server)?.get(steamid).copied()
+    }
+}
+
+/// posts multi-kills and a periodic top fraggers summary to a channel
+pub struct KillFeed {
+    pub channel: serenity::ChannelId,
+    /// kills needed within the window to count as a multi-kill
+    multikill: usize,
+    multikill_window: Duration,
+    /// None turns the summary off
+    summary_every: Option<Duration>,
+    last_summary: DateTime<Utc>,
+    /// recent kill times of each attacker, by server and steamid
+    streaks: HashMap<(SocketAddr, String), VecDeque<DateTime<Utc>>>,
+    /// kills since the last summary, by server and attacker name
+    tally: HashMap<SocketAddr, HashMap<String, u32>>,
+}
+
+impl KillFeed {
+    /// reads KILLFEED_CHANNEL_ID, KILLFEED_MULTIKILL, KILLFEED_MULTIKILL_SECS and KILLFEED_SUMMARY_MINUTES.
+    /// disabled unless the channel is set
+    pub fn from_env() -> Option<Self> {
+        let channel: u64 = parse_env_opt("KILLFEED_CHANNEL_ID")?;
+        let summary_minutes: i64 = parse_env_opt("KILLFEED_SUMMARY_MINUTES").unwrap_or(30);
+        Some(Self {
+            channel: serenity::ChannelId(channel),
+            multikill: parse_env_opt("KILLFEED_MULTIKILL").unwrap_or(3).max(2),
+            multikill_window: Duration::seconds(
+                parse_env_opt("KILLFEED_MULTIKILL_SECS").unwrap_or(10),
+            ),
+            summary_every: (summary_minutes > 0).then(|| Duration::minutes(summary_minutes)),
+            last_summary: Utc::now(),
+            streaks: HashMap::new(),
+            tally: HashMap::new(),
+        })
+    }
+
+    /// counts the kill, returning an announcement if it completes a multi-kill
+    pub fn record(
+        &mut self,
+        server: &Server,
+        kill: &KillEvent,
+        at: DateTime<Utc>,
+    ) -> Option<String> {
+        *self
+            .tally
+            .entry(server.addr)
+            .or_default()
+            .entry(kill.attacker.name.clone())
+            .or_default() += 1;
+
+        let streak = self
+            .streaks
+            .entry((server.addr, kill.attacker.steamid.clone()))
+            .or_default();
+        streak.push_back(at);
+        while streak
+            .front()
+            .is_some_and(|first| at - *first > self.multikill_window)
+        {
+            streak.pop_front();
+        }
+        if streak.len() < self.multikill {
+            return None;
+        }
+        let kills = streak.len();
+        // start over so the same streak isn't announced again
+        streak.clear();
+        Some(format!(
+            "{} :skull: `{}` got {} kills in {}s{}",
+            server.emoji,
+            safe_strip(&kill.attacker.name),
+            kills,
+            self.multikill_window.num_seconds(),
+            if kill.crit { " :boom:" } else { "" }
+        ))
+    }
+
+    /// a new map starts every streak on the server over
+    pub fn map_started(&mut self, server: SocketAddr) {
+        self.streaks.retain(|(addr, _), _| *addr != server);
+    }
+
+    /// forgets the streak of a player who left
+    pub fn player_left(&mut self, server: SocketAddr, steamid: &str) {
+        self.streaks.remove(&(server, steamid.to_owned()));
+    }
+
+    /// the top fraggers of each server since the last summary, once it's time for one
+    pub fn take_summary(&mut self, servers: &HashMap<SocketAddr, Server>) -> Vec<String> {
+        let Some(every) = self.summary_every else {
+            return vec![];
+        };
+        if Utc::now() - self.last_summary < every {
+            return vec![];
+        }
+        self.last_summary = Utc::now();
+        // streaks that ended a while ago aren't needed anymore
+        self.streaks.retain(|_, s| !s.is_empty());
+
+        let mut lines = vec![];
+        for (addr, tally) in self.tally.drain() {
+            let Some(server) = servers.get(&addr) else {
+                continue;
+            };
+            let mut top: Vec<(String, u32)> = tally.into_iter().collect();
+            top.sort_by(|a, b| b.1.cmp(&a.1).then_with(|| a.0.cmp(&b.0)));
+            let top: Vec<String> = top
+                .iter()
+                .take(5)
+                .map(|(name, kills)| format!("`{}` {}", safe_strip(name), kills))
+                .collect();
+            lines.push(format!("{} top fraggers: {}", server.emoji, top.join(", ")));
+        }
+        lines
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use crate::logs::User;
+
+    fn user(steamid: &str) -> User {
+        User {
+            name: steamid.to_owned(),
+            uid: 1,
+            steamid: steamid.to_owned(),
+            team: "Red".to_owned(),
+        }
+    }
+
+    fn kill(attacker: &str, victim: &str) -> KillEvent {
+        KillEvent {
+            attacker: user(attacker),
+            victim: user(victim),
+            weapon: "scattergun".to_owned(),
+            crit: false,
+        }
+    }
+
+    #[test]
+    fn kills_and_deaths_are_counted_per_map() {
+        let server: SocketAddr = "127.0.0.1:27015".parse().unwrap();
+        let mut stats = MapStats::default();
+        stats.record(server, &kill("[U:1:1]", "[U:1:2]"));
+        stats.record(server, &kill("[U:1:1]", "[U:1:2]"));
+        stats.record(server, &kill("[U:1:2]", "[U:1:1]"));
+        // a suicide
+        stats.record(server, &kill("[U:1:2]", "[U:1:2]"));
+        assert_eq!(stats.kd(server, "[U:1:1]"), Some((2, 1)));
+        assert_eq!(stats.kd(server, "[U:1:2]"), Some((1, 3)));
+        assert_eq!(stats.kd(server, "[U:1:3]"), None);
+
+        stats.map_started(server);
+        assert_eq!(stats.kd(server, "[U:1:1]"), None);
+    }
+}
@@ -0,0 +1,101 @@
+use poise::serenity_prelude as serenity;
+use sqlx::{MySql, Pool};
+
+use crate::Error;
+
+/// links the discord user to a steam3 id, replacing any earlier link
+pub async fn link(pool: &Pool<MySql>, user: serenity::UserId, steamid: &str) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		INSERT INTO `steam_links` (`user_id`, `steamid`) VALUES (?, ?)
+		ON DUPLICATE KEY UPDATE `steamid` = VALUES(`steamid`), `linked_at` = CURRENT_TIMESTAMP
+	"#,
+        user.0.to_string(),
+        steamid
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// links the discord user to a steam3 id, taking it from whoever had it linked before.
+/// returns that user, if anyone else had it
+pub async fn force_link(
+    pool: &Pool<MySql>,
+    user: serenity::UserId,
+    steamid: &str,
+) -> Result<Option<serenity::UserId>, Error> {
+    let previous = linked_user(pool, steamid).await?.filter(|u| *u != user);
+    let mut tx = pool.begin().await?;
+    sqlx::query!(
+        r#"DELETE FROM `steam_links` WHERE `steamid` = ? AND `user_id` <> ?"#,
+        steamid,
+        user.0.to_string()
+    )
+    .execute(&mut *tx)
+    .await?;
+    sqlx::query!(
+        r#"
+		INSERT INTO `steam_links` (`user_id`, `steamid`) VALUES (?, ?)
+		ON DUPLICATE KEY UPDATE `steamid` = VALUES(`steamid`), `linked_at` = CURRENT_TIMESTAMP
+	"#,
+        user.0.to_string(),
+        steamid
+    )
+    .execute(&mut *tx)
+    .await?;
+    tx.commit().await?;
+    Ok(previous)
+}
+
+/// removes the user's link. false if they had none
+pub async fn unlink(pool: &Pool<MySql>, user: serenity::UserId) -> Result<bool, Error> {
+    let result = sqlx::query!(
+        r#"DELETE FROM `steam_links` WHERE `user_id` = ?"#,
+        user.0.to_string()
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected() > 0)
+}
+
+/// the steam3 id the user linked, if any
+pub async fn linked_steamid(
+    pool: &Pool<MySql>,
+    user: serenity::UserId,
+) -> Result<Option<String>, Error> {
+    Ok(sqlx::query!(
+        r#"SELECT `steamid` FROM `steam_links` WHERE `user_id` = ?"#,
+        user.0.to_string()
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|r| r.steamid))
+}
+
+/// every (user, steam3 id) link
+pub async fn all_links(pool: &Pool<MySql>) -> Result<Vec<(serenity::UserId, String)>, Error> {
+    Ok(
+        sqlx::query!(r#"SELECT `user_id`, `steamid` FROM `steam_links`"#)
+            .fetch_all(pool)
+            .await?
+            .into_iter()
+            .filter_map(|r| Some((serenity::UserId(r.user_id.parse().ok()?), r.steamid)))
+            .collect(),
+    )
+}
+
+/// the discord user who linked the steam3 id, if any
+pub async fn linked_user(
+    pool: &Pool<MySql>,
+    steamid: &str,
+) -> Result<Option<serenity::UserId>, Error> {
+    Ok(sqlx::query!(
+        r#"SELECT `user_id` FROM `steam_links` WHERE `steamid` = ?"#,
+        steamid
+    )
+    .fetch_optional(pool)
+    .await?
+    .and_then(|r| r.user_id.parse().ok())
+    .map(serenity::UserId))
+}
@@ -1,11 +1,204 @@
-use crate::logs::{LogReceiver, ParsedLogMessage};
-use crate::{Error, Server};
+use super::kill_feed::{KillFeed, MapStats};
+use super::map_vote::MapVotes;
+use super::media_cooldown::LeakyBucket;
+use super::{population, sessions};
+use crate::logs::{safe_strip, LogReceiver, ParsedLogMessage};
+use crate::{parse_env_opt, Error, Server};
+use chrono::{DateTime, Duration, Utc};
 use poise::serenity_prelude as serenity;
 use sqlx::{MySql, Pool};
-use std::collections::HashMap;
+use std::collections::{HashMap, VecDeque};
 use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument};
+
+/// the last few in-game chat lines of each server, for looking back at context
+pub struct ChatHistory {
+    capacity: usize,
+    lines: HashMap<SocketAddr, VecDeque<(DateTime<Utc>, String)>>,
+}
+
+impl ChatHistory {
+    /// keeps CHAT_HISTORY_LINES lines per server, 100 by default
+    pub fn from_env() -> Self {
+        Self {
+            capacity: parse_env_opt("CHAT_HISTORY_LINES").unwrap_or(100),
+            lines: HashMap::new(),
+        }
+    }
+
+    fn push(&mut self, addr: SocketAddr, line: String) {
+        let lines = self.lines.entry(addr).or_default();
+        if lines.len() >= self.capacity {
+            lines.pop_front();
+        }
+        lines.push_back((Utc::now(), line));
+    }
+
+    /// up to `n` of the server's latest lines, oldest first
+    pub fn recent(&self, addr: SocketAddr, n: usize) -> Vec<(DateTime<Utc>, String)> {
+        let Some(lines) = self.lines.get(&addr) else {
+            return vec![];
+        };
+        lines
+            .iter()
+            .skip(lines.len().saturating_sub(n))
+            .cloned()
+            .collect()
+    }
+}
+
+/// caps how many lines get relayed to each channel so bursts stay readable
+struct RelayLimiter {
+    per_minute: Option<f64>,
+    channels: HashMap<serenity::ChannelId, (LeakyBucket, u32)>,
+}
+
+impl RelayLimiter {
+    /// reads RELAY_MAX_LINES_PER_MINUTE, unset means no limit
+    fn from_env() -> Self {
+        Self {
+            per_minute: parse_env_opt("RELAY_MAX_LINES_PER_MINUTE"),
+            channels: HashMap::new(),
+        }
+    }
+
+    /// whether the channel can afford one more line, counting it as suppressed if not
+    fn allow(&mut self, channel: serenity::ChannelId) -> bool {
+        let Some(per_minute) = self.per_minute else {
+            return true;
+        };
+        let (bucket, suppressed) = self
+            .channels
+            .entry(channel)
+            .or_insert_with(|| (LeakyBucket::new(per_minute, per_minute, 1.), 0));
+        let ok = bucket.try_afford_one().is_ok();
+        if !ok {
+            *suppressed += 1;
+        }
+        ok
+    }
+
+    /// a note about lines dropped since the last one, if any
+    fn take_suppressed_note(&mut self, channel: serenity::ChannelId) -> Option<String> {
+        let (_, suppressed) = self.channels.get_mut(&channel)?;
+        if *suppressed == 0 {
+            return None;
+        }
+        let note = format!("... {} messages suppressed", suppressed);
+        *suppressed = 0;
+        Some(note)
+    }
+
+    /// keeps the lines the channel can afford and notes how many were dropped.
+    /// the note is held back until some line gets through
+    fn limit(&mut self, channel: serenity::ChannelId, lines: Vec<String>) -> Vec<String> {
+        let mut allowed: Vec<String> = lines.into_iter().filter(|_| self.allow(channel)).collect();
+        if !allowed.is_empty() {
+            allowed.extend(self.take_suppressed_note(channel));
+        }
+        allowed
+    }
+}
+
+/// chat relayed through a webhook, so it shows up under the player's name
+struct ChatRelay {
+    channel: serenity::ChannelId,
+    webhook: Option<serenity::Webhook>,
+}
+
+impl ChatRelay {
+    const WEBHOOK_NAME: &'static str = "girlpound chat relay";
+
+    /// the relay's webhook, made on first use if the channel doesn't have one yet
+    async fn webhook(&mut self, http: &serenity::Http) -> Result<&serenity::Webhook, Error> {
+        if self.webhook.is_none() {
+            let existing = self
+                .channel
+                .webhooks(http)
+                .await?
+                .into_iter()
+                .find(|w| w.name.as_deref() == Some(Self::WEBHOOK_NAME) && w.token.is_some());
+            self.webhook = Some(match existing {
+                Some(webhook) => webhook,
+                None => {
+                    self.channel
+                        .create_webhook(http, Self::WEBHOOK_NAME)
+                        .await?
+                }
+            });
+        }
+        Ok(self.webhook.as_ref().unwrap())
+    }
+
+    async fn send(
+        &mut self,
+        http: &serenity::Http,
+        username: &str,
+        content: &str,
+    ) -> Result<(), Error> {
+        let webhook = self.webhook(http).await?;
+        webhook
+            .execute(http, false, |w| {
+                w.username(username)
+                    .content(content)
+                    .allowed_mentions(|a| a.empty_parse())
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// drops control characters players can put in names and messages
+fn strip_control(s: &str) -> String {
+    s.chars().filter(|c| !c.is_control()).collect()
+}
+
+/// a webhook username discord will accept: 1 to 80 characters, without "discord" or "clyde"
+fn relay_username(name: &str) -> String {
+    let name: String = strip_control(name).trim().chars().take(80).collect();
+    let lower = name.to_lowercase();
+    if name.is_empty() || lower.contains("discord") || lower.contains("clyde") {
+        "player".to_owned()
+    } else {
+        name
+    }
+}
+
+/// joins lines into as few messages as fit discord's length limit
+fn pack_lines(lines: &[String]) -> Vec<String> {
+    const MAX_LEN: usize = 2000;
+    let mut msgs: Vec<String> = vec![];
+    let mut msg = String::new();
+    for line in lines {
+        // a line too long on its own gets cut down
+        let line: String = line.chars().take(MAX_LEN).collect();
+        if !msg.is_empty() && msg.len() + 1 + line.len() > MAX_LEN {
+            msgs.push(std::mem::take(&mut msg));
+        }
+        if !msg.is_empty() {
+            msg.push('\n');
+        }
+        msg += &line;
+    }
+    if !msg.is_empty() {
+        msgs.push(msg);
+    }
+    msgs
+}
+
+/// what the log thread keeps up to date for commands, and where it relays chat
+pub struct LogState {
+    pub chat_history: Arc<RwLock<ChatHistory>>,
+    pub map_stats: Arc<RwLock<MapStats>>,
+    /// votes are thrown away when a new map starts
+    pub map_votes: Arc<RwLock<MapVotes>>,
+    pub chat_relay_channel: Option<serenity::ChannelId>,
+}
 
 /// receives logs from the tf2 server & posts them in a channel
 pub fn spawn_log_thread(
@@ -13,14 +206,58 @@ pub fn spawn_log_thread(
     servers: HashMap<SocketAddr, Server>,
     pool: Pool<MySql>,
     ctx: Arc<serenity::CacheAndHttp>,
-) {
-    let mut interval = time::interval(time::Duration::from_secs(3));
-    tokio::spawn(async move {
+    state: LogState,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let LogState {
+        chat_history,
+        map_stats,
+        map_votes,
+        chat_relay_channel,
+    } = state;
+    let mut chat_relay = chat_relay_channel.map(|channel| ChatRelay {
+        channel,
+        webhook: None,
+    });
+    // lines are collected over this window and posted together
+    let batch_secs: u64 = parse_env_opt("RELAY_BATCH_SECS").unwrap_or(3);
+    let mut interval = time::interval(time::Duration::from_secs(batch_secs.max(1)));
+    let mut limiter = RelayLimiter::from_env();
+    let mut kill_feed = KillFeed::from_env();
+    // no logs for this long while people are playing means the socket stopped getting them
+    let stall_after = Duration::minutes(parse_env_opt("LOG_STALL_MINUTES").unwrap_or(10i64).max(1));
+    let mut last_line = Utc::now();
+    let mut stalled = false;
+    let thread = async move {
         loop {
-            interval.tick().await;
+            // after shutdown, one last pass records and posts what already came in
+            let stopping = tokio::select! {
+                _ = interval.tick() => false,
+                _ = shutdown.cancelled() => true,
+            };
             // drain all received log messages
             let msgs = log_receiver.drain().await;
-            let mut output = HashMap::<SocketAddr, String>::new();
+            if !msgs.is_empty() {
+                if stalled {
+                    info!("Logs are coming in again");
+                    stalled = false;
+                }
+                last_line = Utc::now();
+            } else if Utc::now() - last_line >= stall_after {
+                // wait out another window before checking again
+                last_line = Utc::now();
+                if servers_active(&pool, &servers, stall_after).await {
+                    warn!(
+                        "No logs for {} minutes while servers had players, rebinding the log socket",
+                        stall_after.num_minutes()
+                    );
+                    stalled = true;
+                    log_receiver.rebind();
+                }
+            }
+            let mut output = HashMap::<SocketAddr, Vec<String>>::new();
+            let mut relayed = vec![];
+            let mut kill_lines = vec![];
             for msg in msgs {
                 let from = msg.from;
                 let parsed = ParsedLogMessage::from_message(&msg);
@@ -29,47 +266,182 @@ pub fn spawn_log_thread(
                     continue;
                 }
 
-                let dom_score: Option<i32> = match update_domination_score(&pool, &parsed).await {
-                    Ok(score) => Some(score),
-                    Err(_) => {
-                        // println!("Could not update dom score: {:?}", e);
-                        None
+                if let ParsedLogMessage::ChatMessage {
+                    from: user,
+                    message,
+                    team_only,
+                } = &parsed
+                {
+                    chat_history.write().await.push(
+                        from,
+                        format!("{}: {}", safe_strip(&user.name), safe_strip(message)),
+                    );
+                    if chat_relay.is_some() {
+                        relayed.push((from, user.clone(), message.clone(), *team_only));
+                    }
+                }
+
+                match &parsed {
+                    ParsedLogMessage::Kill(kill) => map_stats.write().await.record(from, kill),
+                    ParsedLogMessage::StartedMap(_) => map_stats.write().await.map_started(from),
+                    _ => {}
+                }
+
+                if let (ParsedLogMessage::Kill(kill), Some(feed)) = (&parsed, &mut kill_feed) {
+                    if let Some(server) = servers.get(&from) {
+                        kill_lines.extend(feed.record(server, kill, msg.timestamp));
+                    }
+                }
+
+                if let ParsedLogMessage::StartedMap(_) = &parsed {
+                    map_votes.write().await.reset(from);
+                }
+
+                if let Some(feed) = &mut kill_feed {
+                    match &parsed {
+                        ParsedLogMessage::StartedMap(_) => feed.map_started(from),
+                        ParsedLogMessage::Disconnected { user, .. } => {
+                            feed.player_left(from, &user.steamid)
+                        }
+                        _ => {}
+                    }
+                }
+
+                let session = match &parsed {
+                    ParsedLogMessage::Connected { user, .. } => {
+                        sessions::start_session(&pool, from, user).await
+                    }
+                    ParsedLogMessage::Disconnected { user, .. } => {
+                        sessions::end_session(&pool, from, user).await
                     }
+                    _ => Ok(()),
+                };
+                if let Err(e) = session {
+                    error!("Could not record session: {:?}", e);
+                }
+
+                let dom_score = match &parsed {
+                    ParsedLogMessage::Domination { .. } => {
+                        match update_domination_score(&pool, &parsed).await {
+                            Ok(score) => Some(score),
+                            Err(e) => {
+                                error!("Could not update domination score: {:?}", e);
+                                None
+                            }
+                        }
+                    }
+                    _ => None,
                 };
 
                 let dm = parsed.as_discord_message(dom_score);
 
                 if let Some(dm) = dm {
-                    let v = output.entry(from).or_insert_with(|| "".to_owned());
-                    *v += dm.as_str();
-                    *v += "\n";
+                    output.entry(from).or_default().push(dm);
+                }
+            }
+            if let Some(relay) = &mut chat_relay {
+                let mut any_sent = false;
+                for (addr, user, message, team_only) in relayed {
+                    if !limiter.allow(relay.channel) {
+                        continue;
+                    }
+                    let emoji = servers.get(&addr).map_or("", |s| s.emoji.as_str());
+                    let content = if team_only {
+                        format!("{} *({})* {}", emoji, user.team, strip_control(&message))
+                    } else {
+                        format!("{} {}", emoji, strip_control(&message))
+                    };
+                    match relay
+                        .send(&ctx.http, &relay_username(&user.name), &content)
+                        .await
+                    {
+                        Ok(()) => any_sent = true,
+                        Err(e) => {
+                            warn!("Could not relay chat: {:?}", e);
+                            // the webhook may have been deleted, look it up again next time
+                            relay.webhook = None;
+                        }
+                    }
+                }
+                if any_sent {
+                    if let Some(note) = limiter.take_suppressed_note(relay.channel) {
+                        if let Err(e) = relay.send(&ctx.http, "relay", &note).await {
+                            warn!("Could not relay the suppressed chat note: {:?}", e);
+                        }
+                    }
+                }
+            }
+            if let Some(feed) = &mut kill_feed {
+                kill_lines.extend(feed.take_summary(&servers));
+                let lines = limiter.limit(feed.channel, kill_lines);
+                for msg in pack_lines(&lines) {
+                    if let Err(e) = feed
+                        .channel
+                        .send_message(ctx.as_ref(), |m| m.content(&msg))
+                        .await
+                    {
+                        warn!("Could not send message to kill feed channel: {:?}", e);
+                    }
                 }
             }
             // for every output msg...
-            for (addr, msg) in &output {
+            for (addr, lines) in output {
                 // get the server its from
-                let Some(server) = servers.get(addr) else {
-                    println!("addr {:?} has no associated server", addr);
+                let Some(server) = servers.get(&addr) else {
+                    warn!("addr {:?} has no associated server", addr);
                     continue;
                 };
                 // get the log channel
                 let Some(logs_channel) = server.log_channel else {
                     continue;
                 };
+                let lines = limiter.limit(logs_channel, lines);
                 // do not send empty messages
-                if msg.len() == 0 {
+                if lines.is_empty() {
                     continue;
                 }
                 // post it
-                if let Err(e) = logs_channel
-                    .send_message(ctx.as_ref(), |m| m.content(msg))
-                    .await
-                {
-                    println!("Could not send message to logs channel: {:?}", e);
+                for msg in pack_lines(&lines) {
+                    // retry once in case discord is briefly unreachable
+                    for attempt in 0..2 {
+                        match logs_channel
+                            .send_message(ctx.as_ref(), |m| m.content(&msg))
+                            .await
+                        {
+                            Ok(_) => break,
+                            Err(e) => {
+                                warn!("Could not send message to logs channel: {:?}", e);
+                                if attempt == 0 {
+                                    time::sleep(time::Duration::from_secs(2)).await;
+                                }
+                            }
+                        }
+                    }
                 }
             }
+            if stopping {
+                break;
+            }
         }
-    });
+    };
+    tokio::spawn(thread.instrument(info_span!("log_thread")))
+}
+
+/// whether any server had players within `window`, going by the player count thread
+async fn servers_active(
+    pool: &Pool<MySql>,
+    servers: &HashMap<SocketAddr, Server>,
+    window: Duration,
+) -> bool {
+    for server in servers.values() {
+        let since = Utc::now() - window;
+        match population::population_since(pool, server, since, window.num_seconds()).await {
+            Ok(points) if points.iter().any(|p| p.players > 0) => return true,
+            Ok(_) => {}
+            Err(e) => error!("Could not check {} player counts: {:?}", server.name, e),
+        }
+    }
+    false
 }
 
 /// updates the domination score between users
@@ -112,7 +484,7 @@ async fn update_domination_score(pool: &Pool<MySql>, msg: &ParsedLogMessage) ->
         unreachable!("More than two rows in the database for a domination relationship")
     }
 
-    let new_score = if results.len() == 0 {
+    let new_score = if results.is_empty() {
         sign
     } else {
         results.first().unwrap().score + sign
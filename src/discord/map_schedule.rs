@@ -0,0 +1,100 @@
+use chrono::{Timelike, Utc};
+use sqlx::{MySql, Pool};
+use std::collections::HashMap;
+use std::net::SocketAddr;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::{parse_env_opt, Error, Server};
+
+/// changes servers to the map scheduled for the current hour, if they aren't on it already.
+/// each server is only changed once per scheduled hour, so a /changelevel or vote afterwards
+/// sticks. `applied` remembers the hour each server was last handled in
+async fn apply_schedule(
+    pool: &Pool<MySql>,
+    servers: &HashMap<SocketAddr, Server>,
+    min_players: usize,
+    applied: &mut HashMap<SocketAddr, i64>,
+) -> Result<(), Error> {
+    let now = Utc::now();
+    let hour = now.hour() as i32;
+    // hours since the epoch, so tomorrow's slot for the same hour is a new one
+    let slot = now.timestamp() / 3600;
+    let scheduled = sqlx::query!(
+        r#"
+        SELECT `server_addr`, `hour`, `map` FROM `map_schedule`
+        WHERE `hour` = ?
+    "#,
+        hour
+    )
+    .fetch_all(pool)
+    .await?;
+
+    for entry in scheduled {
+        let Some(server) = entry
+            .server_addr
+            .parse::<SocketAddr>()
+            .ok()
+            .and_then(|addr| servers.get(&addr))
+        else {
+            continue;
+        };
+        if applied.get(&server.addr) == Some(&slot) {
+            continue;
+        }
+        match change_map(server, &entry.map, min_players).await {
+            // not enough players yet, try again next minute
+            Ok(false) => {}
+            Ok(true) => {
+                applied.insert(server.addr, slot);
+            }
+            Err(e) => warn!(
+                "Could not apply the map schedule on {}: {:?}",
+                server.name, e
+            ),
+        }
+    }
+    Ok(())
+}
+
+/// changes the server to `map` unless it's already on it.
+/// returns false if it was left alone because too few players are on
+async fn change_map(server: &Server, map: &str, min_players: usize) -> Result<bool, Error> {
+    let mut rcon = server.controller.write().await;
+    let state = rcon.status().await?;
+    if state.map == map {
+        return Ok(true);
+    }
+    if state.players.len() < min_players {
+        return Ok(false);
+    }
+    rcon.run(&format!("changelevel {}", map)).await?;
+    info!("Scheduled map change on {} to {}", server.name, map);
+    Ok(true)
+}
+
+/// runs the map schedule once a minute.
+/// MAP_SCHEDULE_MIN_PLAYERS keeps it from firing until enough players are on
+pub fn spawn_map_schedule_thread(
+    servers: HashMap<SocketAddr, Server>,
+    pool: Pool<MySql>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let min_players: usize = parse_env_opt("MAP_SCHEDULE_MIN_PLAYERS").unwrap_or(0);
+    let mut interval = time::interval(time::Duration::from_secs(60));
+    let thread = async move {
+        let mut applied = HashMap::new();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            if let Err(e) = apply_schedule(&pool, &servers, min_players, &mut applied).await {
+                warn!("Could not apply map schedule: {:?}", e);
+            }
+        }
+    };
+    tokio::spawn(thread.instrument(info_span!("map_schedule")))
+}
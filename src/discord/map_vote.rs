@@ -0,0 +1,220 @@
+use std::cmp::Reverse;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude as serenity;
+use tokio::sync::RwLock;
+use tracing::info;
+
+use super::PoiseData;
+use crate::{parse_env_opt, Error, Server};
+
+const RTV: &str = "rtv";
+
+/// nominations and votes for the map after the current one. thrown away on map change
+#[derive(Default)]
+struct Round {
+    /// when the first vote came in, votes run out a window after this
+    started_at: Option<DateTime<Utc>>,
+    /// one map per user, in the order they were first nominated
+    nominations: Vec<(serenity::UserId, String)>,
+    voters: HashSet<serenity::UserId>,
+    /// the tally message kept up to date as votes come in
+    message: Option<(serenity::ChannelId, serenity::MessageId)>,
+}
+
+impl Round {
+    /// nominated maps with how many nominated them, most first. ties go to the earliest
+    fn tally(&self) -> Vec<(&str, usize)> {
+        let mut tally: Vec<(&str, usize)> = vec![];
+        for (_, map) in &self.nominations {
+            match tally.iter_mut().find(|(m, _)| m == map) {
+                Some((_, count)) => *count += 1,
+                None => tally.push((map, 1)),
+            }
+        }
+        // stable, so earlier nominations stay ahead on ties
+        tally.sort_by_key(|(_, count)| Reverse(*count));
+        tally
+    }
+}
+
+pub struct MapVotes {
+    /// votes needed to change the map
+    threshold: usize,
+    /// how long a vote stays open after the first vote
+    window: Duration,
+    rounds: HashMap<SocketAddr, Round>,
+}
+
+impl MapVotes {
+    /// constructs the votes from RTV_THRESHOLD and RTV_WINDOW_MINUTES
+    pub fn from_env() -> Self {
+        Self {
+            threshold: parse_env_opt("RTV_THRESHOLD").unwrap_or(4usize).max(1),
+            window: Duration::minutes(parse_env_opt("RTV_WINDOW_MINUTES").unwrap_or(5)),
+            rounds: HashMap::new(),
+        }
+    }
+
+    /// records the user's nomination, replacing any earlier one. returns the replaced map
+    pub fn nominate(
+        &mut self,
+        server: SocketAddr,
+        user: serenity::UserId,
+        map: String,
+    ) -> Option<String> {
+        let round = self.rounds.entry(server).or_default();
+        match round.nominations.iter_mut().find(|(u, _)| *u == user) {
+            Some((_, old)) => Some(std::mem::replace(old, map)),
+            None => {
+                round.nominations.push((user, map));
+                None
+            }
+        }
+    }
+
+    /// counts the user's vote. false if they already voted in this window
+    pub fn vote(&mut self, server: SocketAddr, user: serenity::UserId, now: DateTime<Utc>) -> bool {
+        let window = self.window;
+        let round = self.rounds.entry(server).or_default();
+        if round
+            .started_at
+            .is_some_and(|started| started + window < now)
+        {
+            round.started_at = None;
+            round.voters.clear();
+        }
+        round.started_at.get_or_insert(now);
+        round.voters.insert(user)
+    }
+
+    /// the most nominated map once enough votes are in. the round is over after this
+    fn take_winner(&mut self, server: SocketAddr) -> Option<String> {
+        let round = self.rounds.get(&server)?;
+        if round.voters.len() < self.threshold {
+            return None;
+        }
+        let winner = round.tally().first()?.0.to_owned();
+        self.rounds.remove(&server);
+        Some(winner)
+    }
+
+    /// forgets everything about the server's vote, e.g. after the map changed
+    pub fn reset(&mut self, server: SocketAddr) {
+        self.rounds.remove(&server);
+    }
+
+    pub fn message(
+        &self,
+        server: SocketAddr,
+    ) -> Option<(serenity::ChannelId, serenity::MessageId)> {
+        self.rounds.get(&server)?.message
+    }
+
+    pub fn set_message(&mut self, server: SocketAddr, message: &serenity::Message) {
+        self.rounds.entry(server).or_default().message = Some((message.channel_id, message.id));
+    }
+
+    /// the current votes and nominations for the server
+    pub fn tally_embed(&self, server: &Server) -> serenity::CreateEmbed {
+        let round = self.rounds.get(&server.addr);
+        let votes = round.map_or(0, |r| r.voters.len());
+        let mut description = format!("{}/{} votes to change the map", votes, self.threshold);
+        if let Some(started) = round.and_then(|r| r.started_at) {
+            description += &format!(", ends <t:{}:R>", (started + self.window).timestamp());
+        }
+        let nominations = round
+            .map(|r| {
+                r.tally()
+                    .iter()
+                    .map(|(map, count)| format!("`{}` - {}", map, count))
+                    .collect::<Vec<String>>()
+            })
+            .filter(|lines| !lines.is_empty())
+            .map_or("none yet, use /nominate".to_owned(), |lines| {
+                lines.join("\n")
+            });
+        let mut embed = serenity::CreateEmbed::default();
+        embed
+            .title(format!("{} Rock the vote: {}", server.emoji, server.name))
+            .description(description)
+            .field("Nominations", nominations, false);
+        embed
+    }
+}
+
+/// the vote button for the server's tally message
+pub fn vote_button(
+    c: &mut serenity::CreateComponents,
+    server: SocketAddr,
+) -> &mut serenity::CreateComponents {
+    c.create_action_row(|r| {
+        r.create_button(|b| {
+            b.style(serenity::ButtonStyle::Primary)
+                .label("Rock the vote")
+                .custom_id(format!("{}:{}", RTV, server))
+        })
+    })
+}
+
+/// changes the map if the vote passed with a map nominated. returns the new map
+pub async fn change_if_won(
+    votes: &RwLock<MapVotes>,
+    server: &Server,
+) -> Result<Option<String>, Error> {
+    let Some(map) = votes.write().await.take_winner(server.addr) else {
+        return Ok(None);
+    };
+    server
+        .controller
+        .write()
+        .await
+        .run(&format!("changelevel {}", map))
+        .await?;
+    info!("Vote changed {} to {}", server.name, map);
+    Ok(Some(map))
+}
+
+/// handles the vote button on tally messages
+pub async fn handle_vote(
+    ctx: &serenity::Context,
+    mci: &serenity::MessageComponentInteraction,
+    data: &PoiseData,
+) -> Result<(), Error> {
+    let Some((RTV, addr)) = mci.data.custom_id.split_once(':') else {
+        return Ok(());
+    };
+    let server = data.server(addr.parse()?)?;
+
+    if !data
+        .map_votes
+        .write()
+        .await
+        .vote(server.addr, mci.user.id, Utc::now())
+    {
+        mci.create_interaction_response(ctx, |r| {
+            r.interaction_response_data(|d| d.content("You already voted.").ephemeral(true))
+        })
+        .await?;
+        return Ok(());
+    }
+
+    let embed = data.map_votes.read().await.tally_embed(server);
+    let changed = change_if_won(&data.map_votes, server).await?;
+    mci.create_interaction_response(ctx, |r| {
+        r.kind(serenity::InteractionResponseType::UpdateMessage)
+            .interaction_response_data(|d| {
+                d.set_embed(embed);
+                match &changed {
+                    Some(map) => d
+                        .content(format!("Vote passed, changing to `{}`", map))
+                        .components(|c| c),
+                    None => d.components(|c| vote_button(c, server.addr)),
+                }
+            })
+    })
+    .await?;
+    Ok(())
+}
@@ -1,11 +1,13 @@
-use std::collections::HashMap;
+use std::collections::{HashMap, HashSet};
+use std::fmt;
 
 use chrono::{DateTime, Duration, Utc};
 use poise::serenity_prelude as serenity;
+use tracing::info;
 
-use crate::parse_env;
+use crate::{metrics::metrics, parse_env, parse_env_opt};
 
-struct LeakyBucket {
+pub struct LeakyBucket {
     pub max: f64,
     pub per_minute: f64,
     pub cost: f64,
@@ -36,7 +38,7 @@ impl LeakyBucket {
         let diff = now - self.last;
         let diff_mins: f64 = diff.num_milliseconds() as f64 / (1000. * 60.);
         // last remaining tokens + gained since last run, capped to max
-        let current = (self.prev as f64 + self.per_minute as f64 * diff_mins).min(self.max as f64);
+        let current = (self.prev + self.per_minute * diff_mins).min(self.max);
         // if we can afford it ....
         if current >= self.cost {
             self.prev = current - self.cost;
@@ -55,57 +57,344 @@ impl LeakyBucket {
     }
 }
 
+/// a channel specific limit: a burst of `posts` uploads, refilling one every `minutes`
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub struct MediaLimit {
+    pub posts: u32,
+    pub minutes: u32,
+}
+
+impl MediaLimit {
+    /// parses `posts:minutes`
+    fn parse(s: &str) -> Option<Self> {
+        let (posts, minutes) = s.split_once(':')?;
+        Some(Self {
+            posts: posts.trim().parse().ok()?,
+            minutes: minutes.trim().parse().ok()?,
+        })
+    }
+
+    fn bucket(&self) -> LeakyBucket {
+        LeakyBucket::new(self.posts as f64, 1. / self.minutes.max(1) as f64, 1.)
+    }
+}
+
+/// what a message posts. each kind has its own bucket, so images don't use up links
+#[derive(Debug, Clone, Copy, PartialEq, Eq, Hash)]
+pub enum MediaKind {
+    Image,
+    Video,
+    Link,
+}
+
+impl fmt::Display for MediaKind {
+    fn fmt(&self, f: &mut fmt::Formatter<'_>) -> fmt::Result {
+        f.write_str(match self {
+            Self::Image => "image",
+            Self::Video => "video",
+            Self::Link => "link",
+        })
+    }
+}
+
+impl MediaKind {
+    /// what the message mostly posts, None for plain text. uploads count before embeds,
+    /// and anything uploaded that isn't a video counts as an image.
+    /// `text_links` counts urls discord hasn't embedded yet as links
+    pub fn classify(msg: &serenity::Message, text_links: bool) -> Option<Self> {
+        if !msg.attachments.is_empty() {
+            let video = msg.attachments.iter().any(|a| {
+                a.content_type
+                    .as_deref()
+                    .is_some_and(|t| t.starts_with("video/"))
+            });
+            return Some(if video { Self::Video } else { Self::Image });
+        }
+        // gifs from tenor and such embed as gifv
+        match msg.embeds.first().and_then(|e| e.kind.as_deref()) {
+            Some("video") => Some(Self::Video),
+            Some("image" | "gifv") => Some(Self::Image),
+            Some(_) => Some(Self::Link),
+            // the embed may not be there yet
+            None if text_links
+                && (msg.content.contains("http://") || msg.content.contains("https://")) =>
+            {
+                Some(Self::Link)
+            }
+            None => None,
+        }
+    }
+}
+
 pub struct MediaCooldown {
     pub channels: Vec<serenity::ChannelId>,
-    cooldown: HashMap<serenity::ChannelId, HashMap<serenity::UserId, LeakyBucket>>,
+    /// channels without a limit here use the one for the kind of media
+    limits: HashMap<serenity::ChannelId, MediaLimit>,
+    /// kinds without a limit here use the default bucket
+    kind_limits: HashMap<MediaKind, MediaLimit>,
+    /// whether urls that aren't embedded yet count as links
+    text_links: bool,
+    cooldown: HashMap<serenity::ChannelId, HashMap<(serenity::UserId, MediaKind), LeakyBucket>>,
+    /// users who skip the cooldown, until the given time or forever
+    exemptions: HashMap<serenity::UserId, Option<DateTime<Utc>>>,
+    /// members with any of these roles skip the cooldown
+    exempt_roles: HashSet<serenity::RoleId>,
 }
 
 impl MediaCooldown {
-    /// constructs the media cooldown from the MEDIA_COOLDOWN comma separated list of channel ids
+    /// constructs the media cooldown from the MEDIA_COOLDOWN comma separated list of channel ids.
+    /// a channel can be given its own limit as `id:posts:minutes`, otherwise each kind of media
+    /// uses MEDIA_COOLDOWN_IMAGES, _VIDEOS or _LINKS as `posts:minutes`, or the default.
+    /// MEDIA_COOLDOWN_EXEMPT_ROLES lists the roles that skip it
     pub fn from_env() -> Self {
-        let channels: Vec<serenity::ChannelId> = parse_env::<String>("MEDIA_COOLDOWN")
-            .split(',')
-            .map(|s| s.parse::<u64>().unwrap())
-            .map(serenity::ChannelId)
-            .collect();
-        println!(
+        let mut channels = vec![];
+        let mut limits = HashMap::new();
+        for entry in parse_env::<String>("MEDIA_COOLDOWN").split(',') {
+            let mut parts = entry.split(':').map(|s| s.trim().parse::<u64>().unwrap());
+            let channel = serenity::ChannelId(parts.next().unwrap());
+            if let (Some(posts), Some(minutes)) = (parts.next(), parts.next()) {
+                limits.insert(
+                    channel,
+                    MediaLimit {
+                        posts: posts as u32,
+                        minutes: minutes as u32,
+                    },
+                );
+            }
+            channels.push(channel);
+        }
+        info!(
             "found media cooldown channels: {}",
             channels
                 .iter()
-                .map(|s| s.0.to_string())
+                .map(|s| match limits.get(s) {
+                    Some(l) => format!("{} ({}/{}m)", s.0, l.posts, l.minutes),
+                    None => s.0.to_string(),
+                })
                 .collect::<Vec<String>>()
                 .join(",")
         );
+        let exempt_roles = parse_env_opt::<String>("MEDIA_COOLDOWN_EXEMPT_ROLES")
+            .unwrap_or_default()
+            .split(',')
+            .filter_map(|r| r.trim().parse::<u64>().ok())
+            .map(serenity::RoleId)
+            .collect();
+        let kind_limits = [
+            (MediaKind::Image, "MEDIA_COOLDOWN_IMAGES"),
+            (MediaKind::Video, "MEDIA_COOLDOWN_VIDEOS"),
+            (MediaKind::Link, "MEDIA_COOLDOWN_LINKS"),
+        ]
+        .into_iter()
+        .filter_map(|(kind, var)| {
+            let limit = MediaLimit::parse(&parse_env_opt::<String>(var)?)?;
+            Some((kind, limit))
+        })
+        .collect();
         Self {
             channels,
+            limits,
+            kind_limits,
+            text_links: parse_env_opt("MEDIA_COOLDOWN_TEXT_LINKS").unwrap_or(false),
             cooldown: HashMap::new(),
+            exemptions: HashMap::new(),
+            exempt_roles,
         }
     }
 
+    /// lets the user post freely, until `until` if given
+    pub fn exempt(&mut self, uid: serenity::UserId, until: Option<DateTime<Utc>>) {
+        self.exemptions.insert(uid, until);
+    }
+
+    /// removes an exemption, returning whether there was one
+    pub fn unexempt(&mut self, uid: &serenity::UserId) -> bool {
+        self.exemptions.remove(uid).is_some()
+    }
+
+    /// current exemptions, dropping the expired ones
+    pub fn exemptions(&mut self) -> &HashMap<serenity::UserId, Option<DateTime<Utc>>> {
+        let now = Utc::now();
+        self.exemptions
+            .retain(|_, until| until.is_none_or(|until| until > now));
+        &self.exemptions
+    }
+
+    /// whether any of the roles lets its members skip the cooldown
+    pub fn has_exempt_role(&self, roles: &[serenity::RoleId]) -> bool {
+        roles.iter().any(|r| self.exempt_roles.contains(r))
+    }
+
+    /// the channel's own limit, None if it uses the default
+    pub fn limit(&self, cid: &serenity::ChannelId) -> Option<MediaLimit> {
+        self.limits.get(cid).copied()
+    }
+
+    /// puts the channel on the cooldown with its own limit, or back on the default one.
+    /// everyone's cooldown in the channel starts over
+    pub fn set_limit(&mut self, cid: serenity::ChannelId, limit: Option<MediaLimit>) {
+        match limit {
+            Some(limit) => self.limits.insert(cid, limit),
+            None => self.limits.remove(&cid),
+        };
+        if !self.channels.contains(&cid) {
+            self.channels.push(cid);
+        }
+        self.cooldown.remove(&cid);
+    }
+
     pub fn try_remove_from_bucket(
         &mut self,
         cid: &serenity::ChannelId,
         uid: &serenity::UserId,
+        kind: MediaKind,
     ) -> Result<(), Duration> {
+        let limit = self
+            .limit(cid)
+            .or_else(|| self.kind_limits.get(&kind).copied());
         let channel_cooldowns = self.cooldown.entry(*cid).or_default();
         channel_cooldowns
-            .entry(*uid)
-            .or_insert(LeakyBucket::default())
+            .entry((*uid, kind))
+            .or_insert_with(|| limit.map_or_else(LeakyBucket::default, |l| l.bucket()))
             .try_afford_one()
     }
 
-    /// Checks the message's channel & author & cooldowns and returns if the msg should go through
-    pub fn try_allow_one(&mut self, msg: &serenity::Message) -> Result<(), Duration> {
-        // only care about msgs with attachments
-        if msg.attachments.is_empty() && msg.embeds.is_empty() {
+    /// Checks the message's channel & author & cooldowns and returns if the msg should go through.
+    /// if not, returns the kind of media that is on cooldown and for how long
+    pub fn try_allow_one(&mut self, msg: &serenity::Message) -> Result<(), (MediaKind, Duration)> {
+        // only care about msgs with media
+        let Some(kind) = MediaKind::classify(msg, self.text_links) else {
             return Ok(());
-        }
+        };
         // only care about msgs in the media channels
         let cid = msg.channel_id;
         if !self.channels.contains(&cid) {
             return Ok(());
         }
         let uid = msg.author.id;
-        self.try_remove_from_bucket(&cid, &uid)
+        if self.exemptions().contains_key(&uid) {
+            return Ok(());
+        }
+        self.try_remove_from_bucket(&cid, &uid, kind)
+            .map_err(|time_left| {
+                metrics().media_blocked(&kind.to_string());
+                (kind, time_left)
+            })
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const MEMES: serenity::ChannelId = serenity::ChannelId(1);
+    const ART: serenity::ChannelId = serenity::ChannelId(2);
+    const USER: serenity::UserId = serenity::UserId(10);
+
+    fn cooldown() -> MediaCooldown {
+        MediaCooldown {
+            channels: vec![MEMES, ART],
+            limits: HashMap::new(),
+            kind_limits: HashMap::new(),
+            text_links: false,
+            cooldown: HashMap::new(),
+            exemptions: HashMap::new(),
+            exempt_roles: HashSet::new(),
+        }
+    }
+
+    /// how many images the user gets in before being stopped
+    fn burst(cooldown: &mut MediaCooldown, cid: serenity::ChannelId) -> usize {
+        burst_of(cooldown, cid, MediaKind::Image)
+    }
+
+    fn burst_of(cooldown: &mut MediaCooldown, cid: serenity::ChannelId, kind: MediaKind) -> usize {
+        (0..100)
+            .take_while(|_| cooldown.try_remove_from_bucket(&cid, &USER, kind).is_ok())
+            .count()
+    }
+
+    #[test]
+    fn channels_without_a_limit_use_the_default() {
+        let mut cooldown = cooldown();
+        // 15 tokens at 4 per post
+        assert_eq!(burst(&mut cooldown, MEMES), 3);
+    }
+
+    #[test]
+    fn each_channel_uses_its_own_limit() {
+        let mut cooldown = cooldown();
+        cooldown.set_limit(
+            ART,
+            Some(MediaLimit {
+                posts: 1,
+                minutes: 60,
+            }),
+        );
+        assert_eq!(burst(&mut cooldown, ART), 1);
+        assert_eq!(burst(&mut cooldown, MEMES), 3);
+    }
+
+    #[test]
+    fn hitting_the_limit_in_one_channel_leaves_the_others_alone() {
+        let mut cooldown = cooldown();
+        cooldown.set_limit(
+            MEMES,
+            Some(MediaLimit {
+                posts: 2,
+                minutes: 10,
+            }),
+        );
+        assert_eq!(burst(&mut cooldown, MEMES), 2);
+        assert!(cooldown
+            .try_remove_from_bucket(&MEMES, &USER, MediaKind::Image)
+            .is_err());
+        assert!(cooldown
+            .try_remove_from_bucket(&ART, &USER, MediaKind::Image)
+            .is_ok());
+    }
+
+    #[test]
+    fn changing_a_limit_only_resets_that_channel() {
+        let mut cooldown = cooldown();
+        burst(&mut cooldown, MEMES);
+        burst(&mut cooldown, ART);
+        cooldown.set_limit(
+            ART,
+            Some(MediaLimit {
+                posts: 5,
+                minutes: 10,
+            }),
+        );
+        assert_eq!(burst(&mut cooldown, ART), 5);
+        assert_eq!(burst(&mut cooldown, MEMES), 0);
+    }
+
+    #[test]
+    fn each_kind_uses_its_own_limit() {
+        let mut cooldown = cooldown();
+        cooldown.kind_limits.insert(
+            MediaKind::Link,
+            MediaLimit {
+                posts: 5,
+                minutes: 1,
+            },
+        );
+        assert_eq!(burst_of(&mut cooldown, MEMES, MediaKind::Image), 3);
+        assert_eq!(burst_of(&mut cooldown, MEMES, MediaKind::Link), 5);
+        assert_eq!(burst_of(&mut cooldown, MEMES, MediaKind::Video), 3);
+    }
+
+    #[test]
+    fn limits_parse_as_posts_and_minutes() {
+        assert_eq!(
+            MediaLimit::parse("4: 30"),
+            Some(MediaLimit {
+                posts: 4,
+                minutes: 30
+            })
+        );
+        assert_eq!(MediaLimit::parse("4"), None);
+        assert_eq!(MediaLimit::parse("four:30"), None);
     }
 }
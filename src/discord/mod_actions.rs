@@ -0,0 +1,95 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use sqlx::{MySql, Pool};
+use std::net::SocketAddr;
+
+use super::warnings;
+use crate::steamid;
+use crate::Error;
+
+/// one thing done to a player, from any of the tables that record moderation
+pub struct HistoryEntry {
+    pub kind: String,
+    /// None for actions without a duration, 0 for permanent
+    pub minutes: Option<u32>,
+    pub issued_by: String,
+    pub reason: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// records an action that isn't a ban or warning, those have their own tables.
+/// the steamid is stored as steam3
+pub async fn insert_action(
+    pool: &Pool<MySql>,
+    kind: &str,
+    steamid: &str,
+    server: Option<SocketAddr>,
+    issued_by: serenity::UserId,
+    reason: &str,
+    minutes: Option<u32>,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		INSERT INTO `mod_actions` (`kind`, `steamid`, `server_addr`, `issued_by`, `reason`, `minutes`)
+		VALUES (?, ?, ?, ?, ?, ?)
+	"#,
+        kind,
+        steamid::normalize(steamid),
+        server.map(|s| s.to_string()),
+        issued_by.0.to_string(),
+        reason,
+        minutes
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// every ban, warning and other action against the steamid, in any format, oldest first
+pub async fn history_for(pool: &Pool<MySql>, steamid: &str) -> Result<Vec<HistoryEntry>, Error> {
+    let steamid = &steamid::normalize(steamid);
+    let mut history: Vec<HistoryEntry> = sqlx::query_as!(
+        HistoryEntry,
+        r#"
+        SELECT `kind`, `minutes`, `issued_by`, `reason`, `created_at` FROM `mod_actions`
+        WHERE `steamid` = ?
+    "#,
+        steamid
+    )
+    .fetch_all(pool)
+    .await?;
+
+    let bans = sqlx::query!(
+        r#"
+        SELECT `issued_by`, `reason`, `created_at`, `expires_at` FROM `bans`
+        WHERE `steamid` = ?
+    "#,
+        steamid
+    )
+    .fetch_all(pool)
+    .await?;
+    history.extend(bans.into_iter().map(|b| HistoryEntry {
+        kind: "Ban".to_owned(),
+        minutes: Some(b.expires_at.map_or(0, |at| {
+            (at - b.created_at).num_minutes().clamp(1, u32::MAX as i64) as u32
+        })),
+        issued_by: b.issued_by,
+        reason: b.reason,
+        created_at: b.created_at,
+    }));
+
+    history.extend(
+        warnings::warnings_for(pool, steamid)
+            .await?
+            .into_iter()
+            .map(|w| HistoryEntry {
+                kind: "Warn".to_owned(),
+                minutes: None,
+                issued_by: w.issued_by,
+                reason: w.reason,
+                created_at: w.created_at,
+            }),
+    );
+    history.sort_by_key(|e| e.created_at);
+    Ok(history)
+}
@@ -0,0 +1,248 @@
+use std::collections::HashSet;
+use std::str::FromStr;
+
+use chrono::{Duration, Utc};
+use poise::serenity_prelude as serenity;
+use sqlx::{MySql, Pool};
+use tokio::sync::RwLock;
+use tracing::info;
+
+use super::deleted_messages::{save_attachments, SavedAttachments};
+use crate::{parse_env_opt, Error};
+
+const APPROVE: &str = "newacct-approve";
+const DENY: &str = "newacct-deny";
+
+/// what happens to the first message of a too young account
+#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+pub enum GateAction {
+    /// removed, with a copy sent to the review channel
+    Delete,
+    /// removed, and reposted if a mod approves it
+    Hold,
+}
+
+impl FromStr for GateAction {
+    type Err = Error;
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        match s.to_lowercase().as_str() {
+            "delete" => Ok(Self::Delete),
+            "hold" => Ok(Self::Hold),
+            _ => Err(format!("Unknown new account action {}", s).into()),
+        }
+    }
+}
+
+pub struct NewAccountGate {
+    min_age: Duration,
+    action: GateAction,
+    review_channel: serenity::ChannelId,
+    /// young accounts whose first message was already seen, so the db isn't asked every time.
+    /// accounts leave it once they're old enough to never be gated.
+    /// the only part that changes, so only it is locked
+    seen: RwLock<HashSet<serenity::UserId>>,
+}
+
+impl NewAccountGate {
+    /// constructs the gate from NEW_ACCOUNT_MIN_DAYS, NEW_ACCOUNT_ACTION and NEW_ACCOUNT_REVIEW_CHANNEL_ID.
+    /// disabled unless both the age and channel are set
+    pub fn from_env() -> Option<Self> {
+        let min_days: i64 = parse_env_opt("NEW_ACCOUNT_MIN_DAYS")?;
+        let review_channel: u64 = parse_env_opt("NEW_ACCOUNT_REVIEW_CHANNEL_ID")?;
+        let action = parse_env_opt("NEW_ACCOUNT_ACTION").unwrap_or(GateAction::Hold);
+        info!(
+            "new account gate: {:?} first messages from accounts under {} days old",
+            action, min_days
+        );
+        Some(Self {
+            min_age: Duration::days(min_days),
+            action,
+            review_channel: serenity::ChannelId(review_channel),
+            seen: RwLock::new(HashSet::new()),
+        })
+    }
+
+    /// whether the account was made less than min_age ago
+    fn is_young(&self, user: serenity::UserId) -> bool {
+        let age = Utc::now().timestamp() - user.created_at().unix_timestamp();
+        age < self.min_age.num_seconds()
+    }
+
+    /// forgets accounts seen longer than min_age ago, they're too old to be gated by now
+    pub async fn forget_grown(&self, pool: &Pool<MySql>) -> Result<(), Error> {
+        sqlx::query!(
+            r#"DELETE FROM `new_account_seen` WHERE `seen_at` < ?"#,
+            Utc::now() - self.min_age
+        )
+        .execute(pool)
+        .await?;
+        Ok(())
+    }
+
+    /// true if this is the author's first message and their account is too young.
+    /// old accounts are let through without taking the lock. accounts seen before a restart
+    /// are remembered by the db
+    pub async fn should_gate(
+        &self,
+        pool: &Pool<MySql>,
+        msg: &serenity::Message,
+    ) -> Result<bool, Error> {
+        if msg.author.bot || !self.is_young(msg.author.id) {
+            return Ok(false);
+        }
+        if self.seen.read().await.contains(&msg.author.id) {
+            return Ok(false);
+        }
+        let first = sqlx::query!(
+            r#"INSERT IGNORE INTO `new_account_seen` (`user_id`) VALUES (?)"#,
+            msg.author.id.0.to_string()
+        )
+        .execute(pool)
+        .await?
+        .rows_affected()
+            > 0;
+
+        let mut seen = self.seen.write().await;
+        let min_age = self.min_age.num_seconds();
+        let now = Utc::now().timestamp();
+        seen.retain(|uid| now - uid.created_at().unix_timestamp() < min_age);
+        seen.insert(msg.author.id);
+        Ok(first)
+    }
+
+    /// removes the message and sends it to the review channel, attachments included
+    pub async fn gate(
+        &self,
+        ctx: &serenity::Context,
+        msg: &serenity::Message,
+    ) -> Result<(), Error> {
+        // the attachments are gone from the cdn soon after the message is
+        let attachments = save_attachments(msg).await;
+        msg.delete(ctx).await?;
+        let custom_id = |kind: &str| format!("{}:{}:{}", kind, msg.channel_id.0, msg.author.id.0);
+        self.review_channel
+            .send_message(ctx, |m| {
+                m.embed(|e| {
+                    e.title(match self.action {
+                        GateAction::Delete => "New account message deleted",
+                        GateAction::Hold => "New account message held",
+                    })
+                    .description(&msg.content)
+                    .field("Author", format!("<@{}>", msg.author.id.0), true)
+                    .field("Channel", format!("<#{}>", msg.channel_id.0), true)
+                    .field(
+                        "Account created",
+                        format!("<t:{}:R>", msg.author.created_at().unix_timestamp()),
+                        true,
+                    );
+                    if !attachments.links.is_empty() {
+                        e.field("Attachments", attachments.links.join("\n"), false);
+                    }
+                    if let Some(image) = &attachments.image {
+                        e.image(image);
+                    }
+                    e
+                });
+                add_saved_files(m, &attachments);
+                if self.action == GateAction::Hold {
+                    m.components(|c| {
+                        c.create_action_row(|r| {
+                            r.create_button(|b| {
+                                b.style(serenity::ButtonStyle::Success)
+                                    .label("Approve")
+                                    .custom_id(custom_id(APPROVE))
+                            })
+                            .create_button(|b| {
+                                b.style(serenity::ButtonStyle::Danger)
+                                    .label("Deny")
+                                    .custom_id(custom_id(DENY))
+                            })
+                        })
+                    });
+                }
+                m
+            })
+            .await?;
+        Ok(())
+    }
+}
+
+/// uploads the saved attachments with the message
+fn add_saved_files<'a, 'b>(
+    m: &'b mut serenity::CreateMessage<'a>,
+    attachments: &SavedAttachments,
+) -> &'b mut serenity::CreateMessage<'a> {
+    m.add_files(
+        attachments
+            .files
+            .iter()
+            .map(|(filename, data)| serenity::AttachmentType::Bytes {
+                data: data.clone().into(),
+                filename: filename.clone(),
+            }),
+    )
+}
+
+/// handles the approve/deny buttons on held messages. approved messages are reposted by the bot,
+/// with the attachments kept on the review message
+pub async fn handle_review(
+    ctx: &serenity::Context,
+    mci: &serenity::MessageComponentInteraction,
+) -> Result<(), Error> {
+    let mut parts = mci.data.custom_id.split(':');
+    let (Some(kind @ (APPROVE | DENY)), Some(channel), Some(user)) =
+        (parts.next(), parts.next(), parts.next())
+    else {
+        return Ok(());
+    };
+    let can_review = mci
+        .member
+        .as_ref()
+        .and_then(|m| m.permissions)
+        .is_some_and(|p| p.manage_messages());
+    if !can_review {
+        mci.create_interaction_response(ctx, |r| {
+            r.kind(serenity::InteractionResponseType::ChannelMessageWithSource)
+                .interaction_response_data(|d| {
+                    d.content("You need Manage Messages to review held messages.")
+                        .ephemeral(true)
+                })
+        })
+        .await?;
+        return Ok(());
+    }
+    let channel = serenity::ChannelId(channel.parse()?);
+    let content = mci
+        .message
+        .embeds
+        .first()
+        .and_then(|e| e.description.clone())
+        .unwrap_or_default();
+
+    let verdict = if kind == APPROVE {
+        let attachments = save_attachments(&mci.message).await;
+        let mut content = format!("<@{}> (held for approval): {}", user, content);
+        for link in &attachments.links {
+            content += &format!("\n{}", link);
+        }
+        channel
+            .send_message(ctx, |m| {
+                m.content(content).allowed_mentions(|a| a.empty_parse());
+                add_saved_files(m, &attachments)
+            })
+            .await?;
+        "Approved"
+    } else {
+        "Denied"
+    };
+
+    mci.create_interaction_response(ctx, |r| {
+        r.kind(serenity::InteractionResponseType::UpdateMessage)
+            .interaction_response_data(|d| {
+                d.content(format!("{} by <@{}>", verdict, mci.user.id.0))
+                    .components(|c| c)
+            })
+    })
+    .await?;
+    Ok(())
+}
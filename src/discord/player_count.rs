@@ -1,46 +1,243 @@
+use chrono::{DateTime, Duration, Utc};
 use poise::serenity_prelude as serenity;
+use sqlx::{MySql, Pool};
+use std::collections::{HashSet, VecDeque};
+use std::net::SocketAddr;
 use std::sync::Arc;
+use tokio::sync::RwLock;
+use tokio::task::JoinHandle;
 use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{error, info, info_span, warn, Instrument};
 
-use crate::Server;
-
-/// spawns a thread that uses RCON to count the players on the server and update the corresponding channel name
-pub fn spawn_player_count_thread(server: Server, ctx: Arc<serenity::CacheAndHttp>) {
-    if let Some(player_count_channel) = server.player_count_channel {
-        // check player count in this interval
-        let mut interval = time::interval(time::Duration::from_secs(5 * 61));
-        tokio::spawn(async move {
-            loop {
-                interval.tick().await;
-                let status = {
-                    let mut rcon = server.controller.write().await;
-                    match rcon.status().await {
-                        Ok(v) => v,
-                        Err(e) => {
-                            // try to reconnect on error.
-                            println!("Error getting player count: {:?}", e);
-                            let _ = rcon.reconnect().await;
-                            continue;
+use super::auto_cap::AutoCap;
+use super::{bans, population};
+use crate::{metrics::metrics, parse_env_opt, Error, Server};
+
+/// how far below the threshold a server has to drop before it can alert again,
+/// so players hovering around it don't cause a ping every poll
+const ACTIVE_HYSTERESIS: usize = 3;
+
+/// discord only allows 2 renames of a channel per 10 minutes
+const MAX_RENAMES: usize = 2;
+const RENAME_WINDOW_MINUTES: i64 = 10;
+
+/// renames a channel, skipping names it already has and staying under discord's rename limit
+struct ChannelRenamer {
+    channel: serenity::ChannelId,
+    current: Option<String>,
+    renames: VecDeque<DateTime<Utc>>,
+}
+
+impl ChannelRenamer {
+    fn new(channel: serenity::ChannelId) -> Self {
+        Self {
+            channel,
+            current: None,
+            renames: VecDeque::new(),
+        }
+    }
+
+    /// returns whether the channel was renamed. throttled names are picked up on a later poll
+    async fn rename(&mut self, http: &serenity::Http, name: String) -> Result<bool, Error> {
+        if self.current.as_ref() == Some(&name) {
+            return Ok(false);
+        }
+        let now = Utc::now();
+        while self
+            .renames
+            .front()
+            .is_some_and(|at| now - *at >= Duration::minutes(RENAME_WINDOW_MINUTES))
+        {
+            self.renames.pop_front();
+        }
+        if self.renames.len() >= MAX_RENAMES {
+            return Ok(false);
+        }
+        self.channel.edit(http, |c| c.name(&name)).await?;
+        self.renames.push_back(now);
+        self.current = Some(name);
+        Ok(true)
+    }
+}
+
+/// pings the seeder role once a server climbs past its active threshold
+pub struct ActiveAlert {
+    pub channel: serenity::ChannelId,
+    pub role: serenity::RoleId,
+    pub threshold: usize,
+    cooldown: Duration,
+    /// whether the server has been below the threshold since the last alert
+    armed: bool,
+    last_alert: Option<DateTime<Utc>>,
+}
+
+impl ActiveAlert {
+    /// the cooldown between alerts comes from ACTIVE_ALERT_COOLDOWN_MINUTES
+    pub fn new(channel: serenity::ChannelId, role: serenity::RoleId, threshold: usize) -> Self {
+        Self {
+            channel,
+            role,
+            threshold,
+            cooldown: Duration::minutes(
+                parse_env_opt("ACTIVE_ALERT_COOLDOWN_MINUTES").unwrap_or(120),
+            ),
+            armed: true,
+            last_alert: None,
+        }
+    }
+
+    /// whether this player count should alert. only fires on the way up, at most once per cooldown
+    fn check(&mut self, players: usize, now: DateTime<Utc>) -> bool {
+        if players < self.threshold.saturating_sub(ACTIVE_HYSTERESIS) {
+            self.armed = true;
+            return false;
+        }
+        if players < self.threshold || !self.armed {
+            return false;
+        }
+        if self
+            .last_alert
+            .is_some_and(|last| now - last < self.cooldown)
+        {
+            return false;
+        }
+        self.armed = false;
+        self.last_alert = Some(now);
+        true
+    }
+}
+
+/// spawns a thread that uses RCON to count the players on the server and update the corresponding channel names.
+/// whenever the server answers again after being unreachable (or for the first time), its bans are reapplied.
+/// while the server is in `auto_cap_servers` its cap is moved with the player count
+pub fn spawn_player_count_thread(
+    server: Server,
+    ctx: Arc<serenity::CacheAndHttp>,
+    pool: Pool<MySql>,
+    mut active_alert: Option<ActiveAlert>,
+    auto_cap_servers: Arc<RwLock<HashSet<SocketAddr>>>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let auto_cap = server.auto_cap.clone().map(AutoCap::new);
+    // check player count in this interval
+    let mut interval = time::interval(time::Duration::from_secs(5 * 61));
+    let mut player_count_channel = server.player_count_channel.map(ChannelRenamer::new);
+    let mut status_channel = server.status_channel.map(ChannelRenamer::new);
+    let span = info_span!("player_count", server = %server.name);
+    let thread = async move {
+        let mut reachable = false;
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            let status = {
+                let mut rcon = server.controller.write().await;
+                match rcon.status().await {
+                    Ok(v) => v,
+                    Err(e) => {
+                        // try to reconnect on error.
+                        warn!("Error getting player count: {:?}", e);
+                        reachable = false;
+                        if let Err(e) = rcon.reconnect().await {
+                            warn!("Could not reconnect to {}: {:?}", server.name, e);
                         }
+                        drop(rcon);
+                        if let Some(status_channel) = &mut status_channel {
+                            let name = format!("🔴 {} offline", server.emoji);
+                            if let Err(e) = status_channel.rename(&ctx.http, name).await {
+                                warn!("Could not update {} status: {:?}", server.name, e);
+                            }
+                        }
+                        continue;
+                    }
+                }
+            };
+            metrics().players(server.addr, status.players.len());
+            if !reachable {
+                reachable = true;
+                // the server may have restarted and lost its ban list
+                match bans::reapply_bans(&pool, &server).await {
+                    Ok(n) => info!("Reapplied {} bans on {}", n, server.name),
+                    Err(e) => warn!("Could not reapply bans on {}: {:?}", server.name, e),
+                }
+            }
+            if let Err(e) = population::record_player_count(
+                &pool,
+                &server,
+                status.players.len() as i32,
+                status.max_players,
+            )
+            .await
+            {
+                error!("Could not record {} player count: {:?}", server.name, e);
+            }
+            if let Some(auto_cap) = &auto_cap {
+                if auto_cap_servers.read().await.contains(&server.addr) {
+                    match auto_cap
+                        .apply(&server, status.players.len(), status.max_players)
+                        .await
+                    {
+                        Ok(Some(cap)) => info!("Auto cap set {} to {}", server.name, cap),
+                        Ok(None) => (),
+                        Err(e) => warn!("Could not auto cap {}: {:?}", server.name, e),
+                    }
+                }
+            }
+            if let Some(alert) = &mut active_alert {
+                if alert.check(status.players.len(), Utc::now()) {
+                    let role = alert.role;
+                    if let Err(e) = alert
+                        .channel
+                        .send_message(ctx.as_ref(), |m| {
+                            m.content(format!(
+                                "<@&{}> {} is getting active with {}/{} players, come fwag :3\n`connect {}`",
+                                role.0,
+                                server.emoji,
+                                status.players.len(),
+                                status.max_players,
+                                server.addr
+                            ))
+                            .allowed_mentions(|am| am.roles(vec![role.0]))
+                        })
+                        .await
+                    {
+                        warn!("Could not send active alert for {}: {:?}", server.name, e);
                     }
-                };
+                }
+            }
+            if let Some(status_channel) = &mut status_channel {
+                let name = format!(
+                    "🟢 {} {}/{} players",
+                    server.emoji,
+                    status.players.len(),
+                    status.max_players,
+                );
+                if let Err(e) = status_channel.rename(&ctx.http, name).await {
+                    warn!("Could not update {} status: {:?}", server.name, e);
+                }
+            }
+            if let Some(player_count_channel) = &mut player_count_channel {
                 // edit channel name to reflect player count
-                let _ = player_count_channel
-                    .edit(ctx.as_ref(), |c| {
-                        c.name(format!(
-                            "{} {}/{} online",
-                            server.emoji,
-                            status.players.len(),
-                            status.max_players,
-                        ))
-                    })
-                    .await;
-                println!(
-                    "Updated {} player count to {}",
-                    server.name,
-                    status.players.len()
+                let name = format!(
+                    "{} {}/{} online",
+                    server.emoji,
+                    status.players.len(),
+                    status.max_players,
                 );
+                match player_count_channel.rename(&ctx.http, name).await {
+                    Ok(true) => info!(
+                        "Updated {} player count to {}",
+                        server.name,
+                        status.players.len()
+                    ),
+                    Ok(false) => (),
+                    // tried again next interval
+                    Err(e) => warn!("Could not update {} player count: {:?}", server.name, e),
+                }
             }
-        });
-    }
+        }
+    };
+    tokio::spawn(thread.instrument(span))
 }
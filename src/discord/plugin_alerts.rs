@@ -0,0 +1,71 @@
+use poise::serenity_prelude as serenity;
+use std::collections::{HashMap, HashSet};
+use std::net::SocketAddr;
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{info_span, warn, Instrument};
+
+use crate::Server;
+
+/// checks every server's plugins at startup and every 10 minutes,
+/// alerting the channel once per plugin when it fails to load
+pub fn spawn_plugin_check_thread(
+    servers: HashMap<SocketAddr, Server>,
+    ctx: Arc<serenity::CacheAndHttp>,
+    channel: serenity::ChannelId,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let mut interval = time::interval(time::Duration::from_secs(10 * 60));
+    let thread = async move {
+        // (server, plugin) pairs already alerted, so they aren't repeated every check
+        let mut alerted: HashSet<(SocketAddr, String)> = HashSet::new();
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            for server in servers.values() {
+                let plugins = match server.controller.write().await.plugins().await {
+                    Ok(plugins) => plugins,
+                    Err(e) => {
+                        warn!("Could not check {} plugins: {:?}", server.name, e);
+                        continue;
+                    }
+                };
+                let failed: HashSet<(SocketAddr, String)> = plugins
+                    .into_iter()
+                    .filter(|p| p.failed())
+                    .map(|p| (server.addr, p.name))
+                    .collect();
+
+                let new: Vec<&String> = failed
+                    .iter()
+                    .filter(|f| !alerted.contains(*f))
+                    .map(|(_, name)| name)
+                    .collect();
+                if !new.is_empty() {
+                    let msg = format!(
+                        ":warning: {} {} has plugins that failed to load: `{}`",
+                        server.emoji,
+                        server.name,
+                        new.iter()
+                            .map(|n| n.as_str())
+                            .collect::<Vec<&str>>()
+                            .join("`, `")
+                    );
+                    if let Err(e) = channel.send_message(ctx.as_ref(), |m| m.content(msg)).await {
+                        warn!("Could not send plugin alert: {:?}", e);
+                        continue;
+                    }
+                }
+
+                // forget recovered plugins so they alert again if they break again
+                alerted.retain(|(addr, _)| *addr != server.addr);
+                alerted.extend(failed);
+            }
+        }
+    };
+    tokio::spawn(thread.instrument(info_span!("plugin_check")))
+}
@@ -0,0 +1,129 @@
+use std::io::Cursor;
+use std::sync::OnceLock;
+
+use chrono::{DateTime, Utc};
+use plotters::prelude::*;
+use sqlx::{MySql, Pool};
+
+use crate::{parse_env_opt, Error, Server};
+
+const WIDTH: u32 = 800;
+const HEIGHT: u32 = 400;
+
+/// the peak player count of one bucket of time
+pub struct PopulationPoint {
+    /// unix time the bucket starts at
+    pub bucket: i64,
+    pub players: i32,
+    pub max_players: i32,
+}
+
+/// stores one poll of the server's player count
+pub async fn record_player_count(
+    pool: &Pool<MySql>,
+    server: &Server,
+    players: i32,
+    max_players: i32,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		INSERT INTO `player_counts` (`server_addr`, `players`, `max_players`)
+		VALUES (?, ?, ?)
+	"#,
+        server.addr.to_string(),
+        players,
+        max_players
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// the server's peak player count since `since`, in buckets of `bucket_secs`
+pub async fn population_since(
+    pool: &Pool<MySql>,
+    server: &Server,
+    since: DateTime<Utc>,
+    bucket_secs: i64,
+) -> Result<Vec<PopulationPoint>, Error> {
+    Ok(sqlx::query_as!(
+        PopulationPoint,
+        r#"
+        SELECT CAST(FLOOR(UNIX_TIMESTAMP(`recorded_at`) / ?) * ? AS SIGNED) as `bucket!`,
+        MAX(`players`) as `players!`, MAX(`max_players`) as `max_players!`
+        FROM `player_counts`
+        WHERE `server_addr` = ? AND `recorded_at` >= ?
+        GROUP BY 1 ORDER BY 1
+    "#,
+        bucket_secs,
+        bucket_secs,
+        server.addr.to_string(),
+        since
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// registers the font for chart labels once, from POPULATION_FONT
+fn load_font() -> Result<(), Error> {
+    static FONT: OnceLock<Result<(), String>> = OnceLock::new();
+    FONT.get_or_init(|| {
+        let path: String = parse_env_opt("POPULATION_FONT")
+            .unwrap_or("/usr/share/fonts/truetype/dejavu/DejaVuSans.ttf".to_owned());
+        let bytes = std::fs::read(&path).map_err(|e| format!("Could not read {}: {}", path, e))?;
+        // plotters keeps the font around for good
+        plotters::style::register_font(
+            "sans-serif",
+            FontStyle::Normal,
+            Box::leak(bytes.into_boxed_slice()),
+        )
+        .map_err(|_| format!("{} is not a usable font", path))
+    })
+    .clone()
+    .map_err(|e| e.into())
+}
+
+/// draws the points as a line chart and encodes it as a png.
+/// the x axis counts back from now in units of `label_secs`
+pub fn render_population(
+    title: &str,
+    points: &[PopulationPoint],
+    since: DateTime<Utc>,
+    label_secs: i64,
+    label_unit: &str,
+) -> Result<Vec<u8>, Error> {
+    load_font()?;
+    let now = Utc::now().timestamp();
+    let max_players = points.iter().map(|p| p.max_players).max().unwrap_or(0);
+    let start = (since.timestamp() - now) as f64 / label_secs as f64;
+
+    let mut pixels = vec![0; (WIDTH * HEIGHT * 3) as usize];
+    {
+        let root = BitMapBackend::with_buffer(&mut pixels, (WIDTH, HEIGHT)).into_drawing_area();
+        root.fill(&WHITE)?;
+        let mut chart = ChartBuilder::on(&root)
+            .caption(title, ("sans-serif", 24))
+            .margin(12)
+            .x_label_area_size(32)
+            .y_label_area_size(40)
+            .build_cartesian_2d(start..0., 0..max_players.max(1))?;
+        chart
+            .configure_mesh()
+            .x_label_formatter(&|x| format!("{:.0}{} ago", (-x).max(0.), label_unit))
+            .y_desc("players")
+            .draw()?;
+        chart.draw_series(LineSeries::new(
+            points
+                .iter()
+                .map(|p| ((p.bucket - now) as f64 / label_secs as f64, p.players)),
+            BLUE.stroke_width(2),
+        ))?;
+        root.present()?;
+    }
+
+    let image =
+        image::RgbImage::from_raw(WIDTH, HEIGHT, pixels).ok_or("Chart buffer is the wrong size")?;
+    let mut png = vec![];
+    image.write_to(&mut Cursor::new(&mut png), image::ImageOutputFormat::Png)?;
+    Ok(png)
+}
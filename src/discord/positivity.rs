@@ -0,0 +1,64 @@
+use std::collections::HashMap;
+
+use chrono::{DateTime, Duration, Utc};
+use poise::serenity_prelude as serenity;
+use rand::prelude::*;
+
+use crate::parse_env_opt;
+
+const DEFAULT_MESSAGE: &str = "keep up the good work :white_check_mark:";
+
+/// the random encouragement posted in the trial mod channel
+pub struct Positivity {
+    /// chance of a message getting a reply, 0 to 1
+    chance: f64,
+    /// least time between replies in the same channel
+    interval: Duration,
+    /// one of these is picked for every reply
+    messages: Vec<String>,
+    last_sent: HashMap<serenity::ChannelId, DateTime<Utc>>,
+}
+
+impl Positivity {
+    /// constructs the quota from POSITIVITY_CHANCE, POSITIVITY_INTERVAL_MINUTES
+    /// and POSITIVITY_MESSAGES, which is separated by `|`
+    pub fn from_env() -> Self {
+        let messages: Vec<String> = parse_env_opt::<String>("POSITIVITY_MESSAGES")
+            .unwrap_or_default()
+            .split('|')
+            .map(|m| m.trim().to_owned())
+            .filter(|m| !m.is_empty())
+            .collect();
+        Self {
+            // NaN would get past the clamp and make every roll fail
+            chance: parse_env_opt("POSITIVITY_CHANCE")
+                .filter(|c: &f64| c.is_finite())
+                .unwrap_or(0.1f64)
+                .clamp(0., 1.),
+            interval: Duration::minutes(parse_env_opt("POSITIVITY_INTERVAL_MINUTES").unwrap_or(30)),
+            messages: if messages.is_empty() {
+                vec![DEFAULT_MESSAGE.to_owned()]
+            } else {
+                messages
+            },
+            last_sent: HashMap::new(),
+        }
+    }
+
+    /// rolls for a reply in the channel. returns the message to send, if it's time for one
+    pub fn roll(&mut self, channel: serenity::ChannelId, now: DateTime<Utc>) -> Option<String> {
+        if self
+            .last_sent
+            .get(&channel)
+            .is_some_and(|last| *last + self.interval > now)
+        {
+            return None;
+        }
+        let mut rng = thread_rng();
+        if !rng.gen_bool(self.chance) {
+            return None;
+        }
+        self.last_sent.insert(channel, now);
+        self.messages.choose(&mut rng).cloned()
+    }
+}
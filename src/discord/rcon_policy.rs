@@ -0,0 +1,123 @@
+use poise::serenity_prelude as serenity;
+use tracing::warn;
+
+use super::commands::util::has_role;
+use super::Context;
+use crate::{parse_env_opt, Error};
+
+/// console commands refused on raw rcon unless RCON_DENYLIST says otherwise.
+/// alias is here since it could wrap any of the others
+const DEFAULT_DENYLIST: &str =
+    "quit,exit,_restart,killserver,exec,sm_execcfg,rcon_password,sm_rcon,alias";
+
+fn parse_list(key: &str) -> Option<Vec<String>> {
+    parse_env_opt::<String>(key).map(|list| {
+        list.split(',')
+            .map(|c| c.trim().to_lowercase())
+            .filter(|c| !c.is_empty())
+            .collect()
+    })
+}
+
+/// who may use raw rcon, and which console commands it may send
+pub struct RconPolicy {
+    /// role required for raw rcon. unset leaves it to discord permissions
+    role: Option<serenity::RoleId>,
+    /// commands that are always refused
+    denylist: Vec<String>,
+    /// if not empty, the only commands allowed
+    allowlist: Vec<String>,
+}
+
+impl RconPolicy {
+    /// constructs the policy from RCON_ROLE, RCON_DENYLIST and RCON_ALLOWLIST
+    pub fn from_env() -> Self {
+        Self {
+            role: parse_env_opt("RCON_ROLE").map(serenity::RoleId),
+            denylist: parse_list("RCON_DENYLIST")
+                .unwrap_or_else(|| DEFAULT_DENYLIST.split(',').map(str::to_owned).collect()),
+            allowlist: parse_list("RCON_ALLOWLIST").unwrap_or_default(),
+        }
+    }
+
+    /// the first console command in the line that isn't allowed, if any.
+    /// every `;` separated statement is checked, so nothing can hide behind an allowed one
+    pub fn blocked<'a>(&self, cmd: &'a str) -> Option<&'a str> {
+        cmd.split([';', '\n', '\r'])
+            .filter_map(|statement| statement.split_whitespace().next())
+            .map(|name| name.trim_matches('"'))
+            .find(|name| {
+                let name = name.to_lowercase();
+                self.denylist.contains(&name)
+                    || (!self.allowlist.is_empty() && !self.allowlist.contains(&name))
+            })
+    }
+}
+
+/// the raw console line given in the command's `cmd` or `command` option
+fn raw_command(ctx: Context<'_>) -> Option<String> {
+    let poise::Context::Application(ctx) = ctx else {
+        return None;
+    };
+    ctx.args
+        .iter()
+        .find(|o| o.name == "cmd" || o.name == "command")?
+        .value
+        .as_ref()?
+        .as_str()
+        .map(str::to_owned)
+}
+
+/// records a refused rcon attempt in the mod log
+async fn log_blocked(ctx: Context<'_>, reason: &str) {
+    warn!("{} was refused raw rcon: {}", ctx.author().tag(), reason);
+    if let Some(channel) = ctx.data().mod_log_channel {
+        let sent = channel
+            .send_message(ctx, |m| {
+                m.content(format!(
+                    "<@{}> was refused /{}: {}",
+                    ctx.author().id.0,
+                    ctx.command().qualified_name,
+                    reason
+                ))
+                .allowed_mentions(|a| a.empty_parse())
+            })
+            .await;
+        if let Err(e) = sent {
+            warn!("Could not send message to mod log channel: {:?}", e);
+        }
+    }
+}
+
+/// check for commands that send raw console lines. needs the rcon role,
+/// and refuses lines that run a denied or unlisted command
+pub async fn rcon_check(ctx: Context<'_>) -> Result<bool, Error> {
+    allow_rcon(ctx, &raw_command(ctx).unwrap_or_default()).await
+}
+
+/// like rcon_check, for console lines that aren't an option as given, like expanded aliases.
+/// tells the invoker why if it isn't allowed
+pub async fn allow_rcon(ctx: Context<'_>, cmd: &str) -> Result<bool, Error> {
+    let policy = &ctx.data().rcon_policy;
+    if let Some(role) = policy.role {
+        if !has_role(ctx, role).await {
+            log_blocked(ctx, "missing the rcon role").await;
+            ctx.send(|m| {
+                m.content("You don't have permission to use raw rcon.")
+                    .ephemeral(true)
+            })
+            .await?;
+            return Ok(false);
+        }
+    }
+    if let Some(name) = policy.blocked(cmd) {
+        log_blocked(ctx, &format!("`{}` is not allowed in `{}`", name, cmd)).await;
+        ctx.send(|m| {
+            m.content(format!("`{}` can't be run through rcon.", name))
+                .ephemeral(true)
+        })
+        .await?;
+        return Ok(false);
+    }
+    Ok(true)
+}
@@ -0,0 +1,244 @@
+use chrono::{DateTime, Duration, Utc};
+use sqlx::{MySql, Pool};
+use std::net::SocketAddr;
+
+use crate::logs::User;
+use crate::Error;
+
+/// one stay on a server, from connecting to disconnecting
+pub struct Session {
+    pub started_at: DateTime<Utc>,
+    /// None while the player is still on, or if the session was cut off by a restart
+    pub seconds: Option<i32>,
+    pub orphaned: bool,
+}
+
+impl Session {
+    /// how long the session counts for. open sessions count up to now, orphaned ones don't count
+    pub fn duration(&self, now: DateTime<Utc>) -> Duration {
+        match self.seconds {
+            Some(seconds) => Duration::seconds(seconds.into()),
+            None if self.orphaned => Duration::zero(),
+            None => now - self.started_at,
+        }
+    }
+}
+
+/// a player's playtime summed over their sessions
+pub struct Playtime {
+    pub total: Duration,
+    /// only sessions started after the cutoff
+    pub recent: Duration,
+    pub sessions: usize,
+    pub orphaned: usize,
+}
+
+/// a player on the playtime leaderboard
+pub struct TopPlayer {
+    pub steamid: String,
+    pub name: String,
+    pub seconds: i64,
+}
+
+/// adds up the sessions, counting those started after `since` as recent
+pub fn accumulate(sessions: &[Session], since: DateTime<Utc>, now: DateTime<Utc>) -> Playtime {
+    let mut playtime = Playtime {
+        total: Duration::zero(),
+        recent: Duration::zero(),
+        sessions: sessions.len(),
+        orphaned: 0,
+    };
+    for session in sessions {
+        let duration = session.duration(now);
+        playtime.total = playtime.total + duration;
+        if session.started_at >= since {
+            playtime.recent = playtime.recent + duration;
+        }
+        if session.orphaned {
+            playtime.orphaned += 1;
+        }
+    }
+    playtime
+}
+
+/// opens a session for the player, unless they already have one on this server.
+/// players reconnect on map changes without disconnecting, so that keeps the original start.
+/// sessions are timed by when the bot saw the event, log times are in the server's timezone
+pub async fn start_session(
+    pool: &Pool<MySql>,
+    server: SocketAddr,
+    user: &User,
+) -> Result<(), Error> {
+    let server_addr = server.to_string();
+    let open = sqlx::query!(
+        r#"
+        SELECT `id` FROM `sessions`
+        WHERE `steamid` = ? AND `server_addr` = ? AND `ended_at` IS NULL AND `orphaned` = false
+    "#,
+        user.steamid,
+        server_addr
+    )
+    .fetch_optional(pool)
+    .await?;
+    if open.is_some() {
+        return Ok(());
+    }
+    sqlx::query!(
+        r#"
+		INSERT INTO `sessions` (`steamid`, `name`, `server_addr`, `started_at`)
+		VALUES (?, ?, ?, ?)
+	"#,
+        user.steamid,
+        user.name,
+        server_addr,
+        Utc::now()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// closes the player's open session on this server and stores how long it lasted
+pub async fn end_session(pool: &Pool<MySql>, server: SocketAddr, user: &User) -> Result<(), Error> {
+    let at = Utc::now();
+    sqlx::query!(
+        r#"
+		UPDATE `sessions` SET `ended_at` = ?, `seconds` = GREATEST(TIMESTAMPDIFF(SECOND, `started_at`, ?), 0)
+		WHERE `steamid` = ? AND `server_addr` = ? AND `ended_at` IS NULL AND `orphaned` = false
+	"#,
+        at,
+        at,
+        user.steamid,
+        server.to_string()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// marks sessions left open by the last run as orphaned, since their disconnects were never seen.
+/// returns how many there were
+pub async fn close_orphaned_sessions(pool: &Pool<MySql>) -> Result<u64, Error> {
+    let result = sqlx::query!(
+        r#"
+		UPDATE `sessions` SET `orphaned` = true, `ended_at` = CURRENT_TIMESTAMP
+		WHERE `ended_at` IS NULL AND `orphaned` = false
+	"#
+    )
+    .execute(pool)
+    .await?;
+    Ok(result.rows_affected())
+}
+
+/// every session the player has had
+pub async fn sessions_for(pool: &Pool<MySql>, steamid: &str) -> Result<Vec<Session>, Error> {
+    Ok(sqlx::query_as!(
+        Session,
+        r#"
+        SELECT `started_at`, `seconds`, `orphaned` as `orphaned: bool` FROM `sessions`
+        WHERE `steamid` = ? ORDER BY `started_at`
+    "#,
+        steamid
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// the players with the most playtime in sessions started after `since`, optionally on one server.
+/// counted like [`Session::duration`], but summed by the database
+pub async fn top_playtime(
+    pool: &Pool<MySql>,
+    server: Option<SocketAddr>,
+    since: Option<DateTime<Utc>>,
+    limit: u32,
+) -> Result<Vec<TopPlayer>, Error> {
+    let server_addr = server.map(|s| s.to_string());
+    Ok(sqlx::query_as!(
+        TopPlayer,
+        r#"
+        SELECT `steamid`, MAX(`name`) as `name!`,
+        CAST(SUM(COALESCE(`seconds`, IF(`orphaned`, 0, GREATEST(TIMESTAMPDIFF(SECOND, `started_at`, ?), 0)))) AS SIGNED) as `seconds!`
+        FROM `sessions`
+        WHERE (? IS NULL OR `started_at` >= ?) AND (? IS NULL OR `server_addr` = ?)
+        GROUP BY `steamid` ORDER BY 3 DESC LIMIT ?
+    "#,
+        Utc::now(),
+        since,
+        since,
+        server_addr,
+        server_addr,
+        limit
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+/// the steamid last seen using this name, for looking players up by name
+pub async fn steamid_for_name(pool: &Pool<MySql>, name: &str) -> Result<Option<String>, Error> {
+    Ok(sqlx::query!(
+        r#"
+        SELECT `steamid` FROM `sessions`
+        WHERE `name` = ? ORDER BY `started_at` DESC LIMIT 1
+    "#,
+        name
+    )
+    .fetch_optional(pool)
+    .await?
+    .map(|row| row.steamid))
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use chrono::TimeZone;
+
+    fn now() -> DateTime<Utc> {
+        Utc.with_ymd_and_hms(2026, 10, 14, 12, 0, 0).unwrap()
+    }
+
+    fn session(hours_ago: i64, seconds: Option<i32>, orphaned: bool) -> Session {
+        Session {
+            started_at: now() - Duration::hours(hours_ago),
+            seconds,
+            orphaned,
+        }
+    }
+
+    #[test]
+    fn sessions_add_up() {
+        let sessions = [
+            session(48, Some(3600), false),
+            session(30, Some(1800), false),
+            session(5, Some(600), false),
+        ];
+        let playtime = accumulate(&sessions, now() - Duration::days(1), now());
+        assert_eq!(playtime.total, Duration::seconds(3600 + 1800 + 600));
+        assert_eq!(playtime.recent, Duration::seconds(600));
+        assert_eq!(playtime.sessions, 3);
+        assert_eq!(playtime.orphaned, 0);
+    }
+
+    #[test]
+    fn open_sessions_count_up_to_now() {
+        let sessions = [session(10, Some(60), false), session(2, None, false)];
+        let playtime = accumulate(&sessions, now() - Duration::days(1), now());
+        assert_eq!(playtime.total, Duration::seconds(60) + Duration::hours(2));
+        assert_eq!(playtime.recent, playtime.total);
+    }
+
+    #[test]
+    fn orphaned_sessions_count_for_nothing() {
+        let sessions = [session(3, Some(120), false), session(2, None, true)];
+        let playtime = accumulate(&sessions, now() - Duration::days(1), now());
+        assert_eq!(playtime.total, Duration::seconds(120));
+        assert_eq!(playtime.sessions, 2);
+        assert_eq!(playtime.orphaned, 1);
+    }
+
+    #[test]
+    fn no_sessions_is_no_playtime() {
+        let playtime = accumulate(&[], now() - Duration::days(1), now());
+        assert_eq!(playtime.total, Duration::zero());
+        assert_eq!(playtime.sessions, 0);
+    }
+}
@@ -0,0 +1,38 @@
+use sqlx::{MySql, Pool};
+use std::net::SocketAddr;
+use std::str::FromStr;
+
+use crate::Error;
+
+/// how long servers wait between seeder pings, in minutes, set with /seeder_cooldown
+pub const SEED_COOLDOWN_MINUTES: &str = "seed_cooldown_minutes";
+
+/// whether the server's cap follows its schedule, set with /playercap auto
+pub fn auto_cap(server: SocketAddr) -> String {
+    format!("auto_cap:{}", server)
+}
+
+/// a setting changed at runtime, None if it was never set or doesn't parse anymore
+pub async fn get<T: FromStr>(pool: &Pool<MySql>, name: &str) -> Result<Option<T>, Error> {
+    Ok(
+        sqlx::query!(r#"SELECT `value` FROM `settings` WHERE `name` = ?"#, name)
+            .fetch_optional(pool)
+            .await?
+            .and_then(|r| r.value.parse().ok()),
+    )
+}
+
+/// stores a setting so it survives restarts
+pub async fn set(pool: &Pool<MySql>, name: &str, value: &str) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		INSERT INTO `settings` (`name`, `value`) VALUES (?, ?)
+		ON DUPLICATE KEY UPDATE `value` = VALUES(`value`)
+	"#,
+        name,
+        value
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
@@ -0,0 +1,140 @@
+use poise::serenity_prelude as serenity;
+use sqlx::{MySql, Pool};
+use std::sync::Arc;
+use tokio::task::JoinHandle;
+use tokio::time;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, info_span, warn, Instrument};
+
+use super::links;
+use crate::steamid::{steamid64, SteamIDClient};
+use crate::{parse_env_opt, Error};
+
+/// gives members who linked a steam account in STEAM_GROUP_ID the STEAM_GROUP_ROLE
+#[derive(Clone, Copy)]
+pub struct GroupSync {
+    /// the group's 32 bit id, which is what GetUserGroupList lists
+    group_id: u64,
+    pub role: serenity::RoleId,
+}
+
+/// what a sync changed
+#[derive(Default)]
+pub struct SyncCounts {
+    pub granted: usize,
+    pub removed: usize,
+    /// linked accounts whose groups couldn't be read, like private profiles
+    pub unknown: usize,
+}
+
+enum Outcome {
+    Granted,
+    Removed,
+    Unchanged,
+    Unknown,
+}
+
+impl GroupSync {
+    /// None unless both STEAM_GROUP_ID and STEAM_GROUP_ROLE are set
+    pub fn from_env() -> Option<Self> {
+        let group_id: u64 = parse_env_opt("STEAM_GROUP_ID")?;
+        let role: u64 = parse_env_opt("STEAM_GROUP_ROLE")?;
+        Some(Self {
+            // the groupID64 from the group page works too, its low 32 bits are the id
+            group_id: group_id & 0xFFFF_FFFF,
+            role: serenity::RoleId(role),
+        })
+    }
+
+    /// gives the role to linked members in the group and takes it from linked members who aren't.
+    /// accounts whose groups can't be read are left as they are
+    pub async fn sync(
+        &self,
+        http: &serenity::Http,
+        guild: serenity::GuildId,
+        pool: &Pool<MySql>,
+        client: &SteamIDClient,
+    ) -> Result<SyncCounts, Error> {
+        let mut counts = SyncCounts::default();
+        for (user, steam3) in links::all_links(pool).await? {
+            match self.sync_user(http, guild, client, user, &steam3).await {
+                Ok(Outcome::Granted) => counts.granted += 1,
+                Ok(Outcome::Removed) => counts.removed += 1,
+                Ok(Outcome::Unchanged) => {}
+                Ok(Outcome::Unknown) => counts.unknown += 1,
+                Err(e) => {
+                    warn!("Could not sync the steam group role of {}: {:?}", user, e);
+                    counts.unknown += 1;
+                }
+            }
+        }
+        Ok(counts)
+    }
+
+    async fn sync_user(
+        &self,
+        http: &serenity::Http,
+        guild: serenity::GuildId,
+        client: &SteamIDClient,
+        user: serenity::UserId,
+        steam3: &str,
+    ) -> Result<Outcome, Error> {
+        let Some(id) = steamid64(steam3) else {
+            return Ok(Outcome::Unknown);
+        };
+        let Some(groups) = client.group_ids(id).await? else {
+            return Ok(Outcome::Unknown);
+        };
+        let member = match http.get_member(guild.0, user.0).await {
+            Ok(member) => member,
+            // linked and then left the server
+            Err(serenity::Error::Http(e)) if e.status_code().is_some_and(|s| s.as_u16() == 404) => {
+                return Ok(Outcome::Unchanged)
+            }
+            Err(e) => return Err(e.into()),
+        };
+        let in_group = groups.contains(&self.group_id);
+        let has_role = member.roles.contains(&self.role);
+        if in_group && !has_role {
+            http.add_member_role(guild.0, user.0, self.role.0, Some("steam group sync"))
+                .await?;
+            Ok(Outcome::Granted)
+        } else if !in_group && has_role {
+            http.remove_member_role(guild.0, user.0, self.role.0, Some("steam group sync"))
+                .await?;
+            Ok(Outcome::Removed)
+        } else {
+            Ok(Outcome::Unchanged)
+        }
+    }
+}
+
+/// syncs the steam group role every STEAM_GROUP_SYNC_MINUTES, hourly by default
+pub fn spawn_group_sync_thread(
+    sync: GroupSync,
+    ctx: Arc<serenity::CacheAndHttp>,
+    guild: serenity::GuildId,
+    pool: Pool<MySql>,
+    client: Arc<SteamIDClient>,
+    shutdown: CancellationToken,
+) -> JoinHandle<()> {
+    let minutes: u64 = parse_env_opt("STEAM_GROUP_SYNC_MINUTES").unwrap_or(60);
+    let mut interval = time::interval(time::Duration::from_secs(minutes.max(5) * 60));
+    let thread = async move {
+        loop {
+            tokio::select! {
+                _ = interval.tick() => {}
+                _ = shutdown.cancelled() => break,
+            }
+            match sync.sync(&ctx.http, guild, &pool, &client).await {
+                Ok(counts) if counts.granted + counts.removed > 0 => info!(
+                    "Steam group sync gave {} and took {} roles",
+                    counts.granted, counts.removed
+                ),
+                Ok(_) => {}
+                Err(e) => warn!("Could not sync the steam group role: {:?}", e),
+            }
+        }
+    };
+    tokio::spawn(thread.instrument(info_span!("steam_group_sync")))
+}
@@ -0,0 +1,77 @@
+use chrono::{DateTime, Utc};
+use poise::serenity_prelude as serenity;
+use sqlx::{MySql, Pool};
+
+use crate::steamid;
+use crate::{parse_env_opt, Error};
+
+/// a warning given to a player
+pub struct Warning {
+    pub reason: String,
+    pub issued_by: String,
+    pub created_at: DateTime<Utc>,
+}
+
+/// how many warnings before mods are told to escalate, from WARN_ESCALATION_THRESHOLD
+pub fn escalation_threshold() -> usize {
+    parse_env_opt("WARN_ESCALATION_THRESHOLD").unwrap_or(3)
+}
+
+/// whether a player with this many warnings should be dealt with more seriously
+pub fn should_escalate(warnings: usize, threshold: usize) -> bool {
+    warnings >= threshold
+}
+
+/// records a warning against the steamid, stored as steam3
+pub async fn insert_warning(
+    pool: &Pool<MySql>,
+    steamid: &str,
+    reason: &str,
+    issued_by: serenity::UserId,
+) -> Result<(), Error> {
+    sqlx::query!(
+        r#"
+		INSERT INTO `warnings` (`steamid`, `reason`, `issued_by`)
+		VALUES (?, ?, ?)
+	"#,
+        steamid::normalize(steamid),
+        reason,
+        issued_by.0.to_string()
+    )
+    .execute(pool)
+    .await?;
+    Ok(())
+}
+
+/// every warning the player has had, by a steamid in any format, oldest first
+pub async fn warnings_for(pool: &Pool<MySql>, steamid: &str) -> Result<Vec<Warning>, Error> {
+    Ok(sqlx::query_as!(
+        Warning,
+        r#"
+        SELECT `reason`, `issued_by`, `created_at` FROM `warnings`
+        WHERE `steamid` = ? ORDER BY `created_at`
+    "#,
+        steamid::normalize(steamid)
+    )
+    .fetch_all(pool)
+    .await?)
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    #[test]
+    fn escalates_once_the_threshold_is_reached() {
+        assert!(!should_escalate(0, 3));
+        assert!(!should_escalate(2, 3));
+        assert!(should_escalate(3, 3));
+        assert!(should_escalate(7, 3));
+    }
+
+    #[test]
+    fn a_threshold_of_one_escalates_on_the_first_warning() {
+        assert!(!should_escalate(0, 1));
+        assert!(should_escalate(1, 1));
+    }
+}
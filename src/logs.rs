@@ -3,10 +3,15 @@ use std::{
     fmt::Display,
     net::{Ipv4Addr, SocketAddr},
     sync::Arc,
+    time::Duration,
 };
 
 use chrono::{DateTime, NaiveDateTime, Utc};
-use tokio::{net::UdpSocket, sync::RwLock};
+use tokio::{
+    net::UdpSocket,
+    sync::{Notify, RwLock},
+};
+use tracing::{info, info_span, warn, Instrument};
 
 mod parsing;
 pub use parsing::*;
@@ -24,6 +29,7 @@ pub struct LogMessage {
     pub from: SocketAddr,
     pub timestamp: DateTime<chrono::Utc>,
     pub message: String,
+    #[allow(dead_code)]
     pub password: Option<String>,
 }
 
@@ -38,44 +44,57 @@ impl Display for LogMessage {
     }
 }
 
+/// consecutive receive errors before the socket is thrown away and bound again
+const MAX_RECV_ERRORS: u32 = 5;
+
 #[derive(Clone)]
 pub struct LogReceiver {
     messages: Arc<RwLock<VecDeque<LogMessage>>>,
+    /// asks the socket task to bind a fresh socket
+    rebind: Arc<Notify>,
 }
 
 impl LogReceiver {
-    /// create and bind a udp socket to listen to srcds logs
+    /// create and bind a udp socket to listen to srcds logs.
+    /// the socket is bound again whenever it breaks or [`Self::rebind`] is called
     pub async fn connect(addr: Ipv4Addr, port: u16) -> Result<Self, Error> {
-        let sock = Arc::new(UdpSocket::bind((addr, port)).await?);
+        let sock = UdpSocket::bind((addr, port)).await?;
         let messages = Arc::new(RwLock::new(VecDeque::new()));
+        let rebind = Arc::new(Notify::new());
 
         let expected_password: Option<String> = std::env::var("SRCDS_LOG_PASSWORD")
             .ok()
-            .and_then(|p| if p.len() > 0 { Some(p) } else { None });
+            .and_then(|p| if !p.is_empty() { Some(p) } else { None });
 
         let _task = {
-            let sock = sock.clone();
             let messages = messages.clone();
-            tokio::spawn(async move {
-                let mut buf = [0u8; 1024];
+            let rebind = rebind.clone();
+            let thread = async move {
+                let mut sock = Some(sock);
                 loop {
-                    let (len, from) = sock.recv_from(&mut buf).await.unwrap();
-                    let message =
-                        match try_parse_packet(from, &buf[..len], expected_password.as_deref()) {
-                            Ok(m) => m,
-                            Err(e) => {
-                                println!(
-                                    "Could not parse packet from {from:?} with len {len}: {e:?}"
-                                );
-                                continue;
-                            }
-                        };
-                    messages.write().await.push_back(message);
+                    let sock = match sock.take() {
+                        Some(sock) => sock,
+                        None => {
+                            let sock = bind_with_backoff(addr, port).await;
+                            info!("Log socket bound again on {}:{}", addr, port);
+                            sock
+                        }
+                    };
+                    tokio::select! {
+                        _ = receive(&sock, &messages, expected_password.as_deref()) => {}
+                        _ = rebind.notified() => info!("Rebinding the log socket"),
+                    }
                 }
-            })
+            };
+            tokio::spawn(thread.instrument(info_span!("log_receiver", port)))
         };
 
-        Ok(LogReceiver { messages })
+        Ok(LogReceiver { messages, rebind })
+    }
+
+    /// drops the socket and binds a new one, for when logs stop arriving
+    pub fn rebind(&self) {
+        self.rebind.notify_one();
     }
 
     /// retrieve all log messages from the queue
@@ -87,7 +106,7 @@ impl LogReceiver {
     pub async fn _spoof_message(&self, msg: &str) {
         let expected_password: Option<String> = std::env::var("SRCDS_LOG_PASSWORD")
             .ok()
-            .and_then(|p| if p.len() > 0 { Some(p) } else { None });
+            .and_then(|p| if !p.is_empty() { Some(p) } else { None });
         self.messages.write().await.push_back(LogMessage {
             from: ([0, 0, 0, 0], 0).into(),
             timestamp: Utc::now(),
@@ -115,6 +134,59 @@ impl Display for PacketParseError {
 
 impl std::error::Error for PacketParseError {}
 
+/// binds the log socket, retrying with a growing wait until it works
+async fn bind_with_backoff(addr: Ipv4Addr, port: u16) -> UdpSocket {
+    let mut backoff = Duration::from_secs(1);
+    loop {
+        match UdpSocket::bind((addr, port)).await {
+            Ok(sock) => return sock,
+            Err(e) => {
+                warn!(
+                    "Could not bind the log socket: {:?}, retrying in {}s",
+                    e,
+                    backoff.as_secs()
+                );
+                tokio::time::sleep(backoff).await;
+                backoff = (backoff * 2).min(Duration::from_secs(60));
+            }
+        }
+    }
+}
+
+/// queues packets from the socket until it keeps failing
+async fn receive(
+    sock: &UdpSocket,
+    messages: &RwLock<VecDeque<LogMessage>>,
+    expected_password: Option<&str>,
+) {
+    let mut buf = [0u8; 1024];
+    let mut errors = 0;
+    loop {
+        let (len, from) = match sock.recv_from(&mut buf).await {
+            Ok(v) => v,
+            Err(e) => {
+                // transient socket errors shouldn't end log relaying, but a broken socket won't recover
+                warn!("Could not receive log packet: {:?}", e);
+                errors += 1;
+                if errors >= MAX_RECV_ERRORS {
+                    warn!("The log socket keeps failing, binding a new one");
+                    return;
+                }
+                continue;
+            }
+        };
+        errors = 0;
+        let message = match try_parse_packet(from, &buf[..len], expected_password) {
+            Ok(m) => m,
+            Err(e) => {
+                warn!("Could not parse packet from {from:?} with len {len}: {e:?}");
+                continue;
+            }
+        };
+        messages.write().await.push_back(message);
+    }
+}
+
 fn try_parse_packet(
     from: SocketAddr,
     data: &[u8],
@@ -4,23 +4,58 @@ use std::net::Ipv4Addr;
 use super::LogMessage;
 
 use nom::{
-    bytes::complete::*, character::complete::*, combinator::fail, sequence::Tuple, Err, IResult,
-    Parser,
+    bytes::complete::*,
+    character::complete::*,
+    combinator::{fail, map_res},
+    sequence::Tuple,
+    Err, IResult, Parser,
 };
 
 /// a parsed log message
 #[derive(Debug, PartialEq, Eq, Clone)]
 pub enum ParsedLogMessage {
-    ChatMessage { from: User, message: String },
-    Connected { user: User, ip: Ipv4Addr, port: u16 },
-    Disconnected { user: User, reason: String },
-    JoinedTeam { user: User, team: String },
+    ChatMessage {
+        from: User,
+        message: String,
+        /// said with say_team
+        team_only: bool,
+    },
+    Connected {
+        user: User,
+        ip: Ipv4Addr,
+        port: u16,
+    },
+    Disconnected {
+        user: User,
+        reason: String,
+    },
+    JoinedTeam {
+        user: User,
+        team: String,
+    },
     StartedMap(String),
-    Domination { from: User, to: User },
-    Revenge { from: User, to: User },
+    Domination {
+        from: User,
+        to: User,
+    },
+    Revenge {
+        from: User,
+        to: User,
+    },
+    Kill(KillEvent),
     Unknown,
 }
 
+/// one player killing another
+#[derive(Debug, PartialEq, Eq, Clone)]
+pub struct KillEvent {
+    pub attacker: User,
+    pub victim: User,
+    pub weapon: String,
+    /// a full crit, not a mini crit
+    pub crit: bool,
+}
+
 impl ParsedLogMessage {
     pub fn from_message(msg: &LogMessage) -> Self {
         let i: &str = &msg.message;
@@ -31,10 +66,7 @@ impl ParsedLogMessage {
     }
 
     pub fn is_unknown(&self) -> bool {
-        match self {
-            Self::Unknown => true,
-            _ => false,
-        }
+        matches!(self, Self::Unknown)
     }
 
     pub fn as_discord_message(&self, dom_score: Option<i32>) -> Option<String> {
@@ -42,12 +74,20 @@ impl ParsedLogMessage {
             .map(|s| format!(" **({})**", s))
             .unwrap_or("".to_owned());
         let victim_dom_score = dom_score
-            .map(|s| format!(" **({})**", s * -1))
+            .map(|s| format!(" **({})**", -s))
             .unwrap_or("".to_owned());
         match self {
-            ParsedLogMessage::ChatMessage { from, message } => {
-                format!("`{}: {}`", safe_strip(&from.name), safe_strip(message)).into()
-            }
+            ParsedLogMessage::ChatMessage {
+                from,
+                message,
+                team_only,
+            } => format!(
+                "`{}{}: {}`",
+                if *team_only { "(TEAM) " } else { "" },
+                safe_strip(&from.name),
+                safe_strip(message)
+            )
+            .into(),
             ParsedLogMessage::Connected { user, .. } => {
                 format!("+ `{} {} connected.`", safe_strip(&user.name), user.steamid).into()
             }
@@ -92,6 +132,7 @@ pub fn safe_strip(s: &str) -> String {
 
 fn parse_log_message(i: &str) -> IResult<&str, ParsedLogMessage> {
     chat_message
+        .or(kill_message)
         .or(connect_message)
         .or(disconnect_message)
         .or(start_map_message)
@@ -139,26 +180,20 @@ fn start_map_message(i: &str) -> IResult<&str, ParsedLogMessage> {
 }
 
 fn ipv4(i: &str) -> IResult<&str, Ipv4Addr> {
+    // octets over 255 fail the parse instead of panicking
+    let octet = || map_res(digit1, str::parse::<u8>);
     let (i, (a, _, b, _, c, _, d)) = (
-        digit1,
+        octet(),
         char('.'),
-        digit1,
+        octet(),
         char('.'),
-        digit1,
+        octet(),
         char('.'),
-        digit1,
+        octet(),
     )
         .parse(i)?;
 
-    Ok((
-        i,
-        Ipv4Addr::new(
-            a.parse().unwrap(),
-            b.parse().unwrap(),
-            c.parse().unwrap(),
-            d.parse().unwrap(),
-        ),
-    ))
+    Ok((i, Ipv4Addr::new(a, b, c, d)))
 }
 
 fn user(i: &str) -> IResult<&str, User> {
@@ -170,17 +205,23 @@ fn user(i: &str) -> IResult<&str, User> {
         )));
     };
 
-    let len = caps.get(0).unwrap().len();
+    let end = caps.get(0).unwrap().end();
     let name = caps.get(1).unwrap().as_str();
     let uid = caps.get(2).unwrap().as_str();
     let steamid = caps.get(3).unwrap().as_str();
     let team = caps.get(4).unwrap().as_str();
+    let Ok(uid) = uid.parse() else {
+        return Err(Err::Error(nom::error::Error::new(
+            i,
+            nom::error::ErrorKind::Digit,
+        )));
+    };
 
     Ok((
-        &i[len..],
+        &i[end..],
         User {
             name: name.to_owned(),
-            uid: uid.parse().unwrap(),
+            uid,
             steamid: steamid.to_owned(),
             team: team.to_owned(),
         },
@@ -204,20 +245,32 @@ fn connect_message(i: &str) -> IResult<&str, ParsedLogMessage> {
     let (i, user) = user(i)?;
     let (i, _) = tag(" connected, address ")(i)?;
     let (i, (_, ip, _)) = (char('"'), ipv4, char(':')).parse(i)?;
-    let (i, port) = digit1(i)?;
+    let (i, port) = map_res(digit1, str::parse::<u16>)(i)?;
+    Ok((i, ParsedLogMessage::Connected { user, ip, port }))
+}
+
+fn kill_message(i: &str) -> IResult<&str, ParsedLogMessage> {
+    let (i, attacker) = user(i)?;
+    let (i, _) = tag(" killed ")(i)?;
+    let (i, victim) = user(i)?;
+    let (i, _) = tag(" with ")(i)?;
+    let (i, (_, weapon, _)) = (char('"'), take_until1("\""), char('"')).parse(i)?;
+    // the rest is properties like (crit "crit") (attacker_position "...")
+    let crit = i.contains("(crit \"crit\")");
     Ok((
-        i,
-        ParsedLogMessage::Connected {
-            user,
-            ip,
-            port: port.parse().unwrap(),
-        },
+        "",
+        ParsedLogMessage::Kill(KillEvent {
+            attacker,
+            victim,
+            weapon: weapon.to_owned(),
+            crit,
+        }),
     ))
 }
 
 fn chat_message(i: &str) -> IResult<&str, ParsedLogMessage> {
     let (i, user) = user(i)?;
-    let (i, _say) = tag(" say ")(i)?;
+    let (i, say) = tag(" say_team ").or(tag(" say ")).parse(i)?;
     let (i, (_, message, _)) = (char('"'), take_until1("\""), char('"')).parse(i)?;
 
     Ok((
@@ -225,6 +278,7 @@ fn chat_message(i: &str) -> IResult<&str, ParsedLogMessage> {
         ParsedLogMessage::ChatMessage {
             from: user,
             message: message.to_owned(),
+            team_only: say == " say_team ",
         },
     ))
 }
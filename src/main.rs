@@ -1,17 +1,20 @@
 use std::collections::HashMap;
 use std::net::{SocketAddr, ToSocketAddrs};
+use std::process::ExitCode;
 use std::str::FromStr;
 use std::sync::Arc;
+use std::time::Duration;
 use std::{env, net::Ipv4Addr};
 
 use dotenv::dotenv;
 
 use poise::serenity_prelude as serenity;
-use tokio;
 
+mod a2s;
 mod discord;
 mod ftp;
 mod logs;
+mod metrics;
 mod steamid;
 mod tf2_rcon;
 
@@ -23,6 +26,8 @@ use tf2_rcon::RconController;
 use sqlx::mysql::MySql;
 use sqlx::Pool;
 use tokio::sync::RwLock;
+use tracing::{info, warn};
+use tracing_subscriber::EnvFilter;
 
 pub type Error = Box<dyn std::error::Error + Send + Sync>;
 
@@ -32,24 +37,36 @@ pub struct ServerBuilder {
     pub addr: SocketAddr,
     pub rcon_pass: String,
     pub player_count_cid: Option<u64>,
+    pub status_cid: Option<u64>,
     pub log_cid: Option<u64>,
     pub ftp_credentials: (String, String),
+    pub private: bool,
+    pub seeder_role_id: Option<u64>,
+    pub active_threshold: Option<usize>,
+    pub auto_cap: Option<discord::CapSchedule>,
+    pub rcon_timeout: Duration,
 }
 
 impl ServerBuilder {
     pub async fn build(self) -> Result<Server, Error> {
         let ftp_url: SocketAddr = (self.addr.ip(), 21).into();
-        println!("Connecting to {:?}...", self.addr);
+        info!("Connecting to {:?}...", self.addr);
         Ok(Server {
             name: self.name,
             emoji: self.emoji,
             addr: self.addr,
             controller: Arc::new(RwLock::new(
-                RconController::connect(self.addr, &self.rcon_pass).await?,
+                RconController::connect(self.addr, &self.rcon_pass, self.rcon_timeout).await?,
             )),
             player_count_channel: self.player_count_cid.map(serenity::ChannelId),
+            status_channel: self.status_cid.map(serenity::ChannelId),
             log_channel: self.log_cid.map(serenity::ChannelId),
             ftp: ServerFtp::new(ftp_url, self.ftp_credentials),
+            private: self.private,
+            seeder_role: self.seeder_role_id.map(serenity::RoleId),
+            active_threshold: self.active_threshold,
+            auto_cap: self.auto_cap,
+            rcon_timeout: self.rcon_timeout,
         })
     }
 }
@@ -62,30 +79,65 @@ pub struct Server {
     pub addr: SocketAddr,
     pub controller: Arc<RwLock<RconController>>,
     pub player_count_channel: Option<serenity::ChannelId>,
+    /// voice channel renamed to show whether the server is up and how full it is
+    pub status_channel: Option<serenity::ChannelId>,
     pub log_channel: Option<serenity::ChannelId>,
     pub ftp: ServerFtp,
+    /// whether players need a connect token from the bot to join
+    pub private: bool,
+    /// pinged by /seeder for this server instead of the global seeder role
+    pub seeder_role: Option<serenity::RoleId>,
+    /// player count at which seeders are pinged that the server is getting active
+    pub active_threshold: Option<usize>,
+    /// how the player count thread moves the cap with population, when turned on
+    pub auto_cap: Option<discord::CapSchedule>,
+    /// how long a command waits on the server before it counts as unreachable
+    pub rcon_timeout: Duration,
 }
 
 fn parse_env<T: FromStr>(name: &str) -> T {
     env::var(name)
         .ok()
         .and_then(|v| v.parse().ok())
-        .expect(&format!("Could not find env variable {}", name))
+        .unwrap_or_else(|| panic!("Could not find env variable {}", name))
+}
+
+/// like parse_env, but for settings that are allowed to be left blank
+fn parse_env_opt<T: FromStr>(name: &str) -> Option<T> {
+    env::var(name).ok().and_then(|v| v.parse().ok())
+}
+
+/// logs to stdout, filtered by LOG_LEVEL. takes a level or per module filters,
+/// e.g. `info,girlpound_bot=debug,serenity=warn`
+fn init_logging() {
+    let level: String = parse_env_opt("LOG_LEVEL")
+        .filter(|l: &String| !l.is_empty())
+        .unwrap_or_else(|| "info".to_owned());
+    let (filter, bad) = match EnvFilter::try_new(&level) {
+        Ok(filter) => (filter, None),
+        Err(e) => (EnvFilter::new("info"), Some(e)),
+    };
+    tracing_subscriber::fmt().with_env_filter(filter).init();
+    if let Some(e) = bad {
+        warn!("Ignoring LOG_LEVEL `{}`: {}", level, e);
+    }
 }
 
 #[tokio::main]
-async fn main() -> Result<(), Error> {
+async fn main() -> Result<ExitCode, Error> {
     dotenv().ok();
-    println!("Starting the girlpound bot...");
+    init_logging();
+    info!("Starting the girlpound bot...");
 
     let db_url: String = parse_env("DATABASE_URL");
 
     // migrate the db
     let pool = Pool::<MySql>::connect(&db_url).await?;
     sqlx::migrate!().run(&pool).await?;
-    println!("DB Migrated.");
+    info!("DB Migrated.");
 
     let rcon_pass: String = parse_env("RCON_PASS");
+    let rcon_timeout = Duration::from_secs(parse_env_opt("RCON_TIMEOUT_SECS").unwrap_or(5));
 
     // load servers
     let tkgp4 = ServerBuilder {
@@ -97,8 +149,14 @@ async fn main() -> Result<(), Error> {
             .expect("Could not resolve RCON address."),
         rcon_pass: rcon_pass.clone(),
         player_count_cid: Some(parse_env("PLAYER_COUNT_CID_4")),
+        status_cid: parse_env_opt("STATUS_CID_4"),
         log_cid: Some(parse_env("RELAY_CID_4")),
         ftp_credentials: (parse_env("FTP_USER_4"), parse_env("FTP_PASS_4")),
+        private: parse_env_opt("PRIVATE_SERVER_4").unwrap_or(false),
+        seeder_role_id: parse_env_opt("SEEDER_ROLE_4"),
+        active_threshold: parse_env_opt("ACTIVE_THRESHOLD_4"),
+        auto_cap: parse_env_opt("AUTO_CAP_4"),
+        rcon_timeout,
     }
     .build()
     .await
@@ -112,8 +170,14 @@ async fn main() -> Result<(), Error> {
             .expect("Could not resolve RCON address."),
         rcon_pass: rcon_pass.clone(),
         player_count_cid: Some(parse_env("PLAYER_COUNT_CID_5")),
+        status_cid: parse_env_opt("STATUS_CID_5"),
         log_cid: Some(parse_env("RELAY_CID_5")),
         ftp_credentials: (parse_env("FTP_USER_5"), parse_env("FTP_PASS_5")),
+        private: parse_env_opt("PRIVATE_SERVER_5").unwrap_or(false),
+        seeder_role_id: parse_env_opt("SEEDER_ROLE_5"),
+        active_threshold: parse_env_opt("ACTIVE_THRESHOLD_5"),
+        auto_cap: parse_env_opt("AUTO_CAP_5"),
+        rcon_timeout,
     }
     .build()
     .await
@@ -123,16 +187,15 @@ async fn main() -> Result<(), Error> {
     servers.insert(tkgp5.addr, tkgp5);
     servers.insert(tkgp4.addr, tkgp4);
 
-    println!("{} servers loaded.", servers.len());
+    info!("{} servers loaded.", servers.len());
 
-    println!("Launching UDP log receiver...");
+    info!("Launching UDP log receiver...");
     let logs_addr: Ipv4Addr = parse_env("SRCDS_LOG_ADDR");
     let logs_port: u16 = parse_env("SRCDS_LOG_PORT");
     let log_receiver = LogReceiver::connect(logs_addr, logs_port)
         .await
         .expect("Could not bind log receiver");
 
-    println!("Starting discord bot...");
-    discord::start_bot(pool, log_receiver, servers).await;
-    Ok(())
+    info!("Starting discord bot...");
+    discord::start_bot(pool, log_receiver, servers).await
 }
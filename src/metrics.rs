@@ -0,0 +1,180 @@
+use std::net::SocketAddr;
+use std::sync::OnceLock;
+use std::time::Duration;
+
+use prometheus::{
+    Encoder, HistogramOpts, HistogramVec, IntCounterVec, IntGauge, IntGaugeVec, Opts, Registry,
+    TextEncoder,
+};
+use tokio::io::{AsyncReadExt, AsyncWriteExt};
+use tokio::net::{TcpListener, TcpStream};
+use tokio::task::JoinHandle;
+use tokio_util::sync::CancellationToken;
+use tracing::{info, info_span, warn, Instrument};
+
+use crate::Error;
+
+/// what the bot is doing, for graphing. always counted, only served if METRICS_ADDR is set
+pub struct Metrics {
+    registry: Registry,
+    commands: IntCounterVec,
+    rcon_seconds: HistogramVec,
+    rcon_failures: IntCounterVec,
+    players: IntGaugeVec,
+    media_blocked: IntCounterVec,
+    cooldown_queue: IntGauge,
+}
+
+impl Metrics {
+    fn new() -> Result<Self, prometheus::Error> {
+        let registry = Registry::new_custom(Some("girlpound".to_owned()), None)?;
+        let commands = IntCounterVec::new(
+            Opts::new("commands_total", "Commands run, by command"),
+            &["command"],
+        )?;
+        let rcon_seconds = HistogramVec::new(
+            HistogramOpts::new("rcon_seconds", "How long rcon commands took, by server"),
+            &["server"],
+        )?;
+        let rcon_failures = IntCounterVec::new(
+            Opts::new(
+                "rcon_failures_total",
+                "Rcon commands that failed or timed out, by server",
+            ),
+            &["server"],
+        )?;
+        let players = IntGaugeVec::new(
+            Opts::new("players", "Humans on each server at the last check"),
+            &["server"],
+        )?;
+        let media_blocked = IntCounterVec::new(
+            Opts::new(
+                "media_blocked_total",
+                "Media messages deleted for being on cooldown, by kind",
+            ),
+            &["kind"],
+        )?;
+        let cooldown_queue =
+            IntGauge::new("cooldown_queue", "Cooldown messages waiting to be deleted")?;
+        registry.register(Box::new(commands.clone()))?;
+        registry.register(Box::new(rcon_seconds.clone()))?;
+        registry.register(Box::new(rcon_failures.clone()))?;
+        registry.register(Box::new(players.clone()))?;
+        registry.register(Box::new(media_blocked.clone()))?;
+        registry.register(Box::new(cooldown_queue.clone()))?;
+        Ok(Self {
+            registry,
+            commands,
+            rcon_seconds,
+            rcon_failures,
+            players,
+            media_blocked,
+            cooldown_queue,
+        })
+    }
+
+    pub fn command(&self, name: &str) {
+        self.commands.with_label_values(&[name]).inc();
+    }
+
+    /// records an rcon command that got an answer or an error back
+    pub fn rcon(&self, server: SocketAddr, took: Duration, ok: bool) {
+        let server = server.to_string();
+        self.rcon_seconds
+            .with_label_values(&[&server])
+            .observe(took.as_secs_f64());
+        if !ok {
+            self.rcon_failures.with_label_values(&[&server]).inc();
+        }
+    }
+
+    /// an rcon command that was given up on before the server answered
+    pub fn rcon_timeout(&self, server: SocketAddr) {
+        self.rcon_failures
+            .with_label_values(&[&server.to_string()])
+            .inc();
+    }
+
+    pub fn players(&self, server: SocketAddr, players: usize) {
+        self.players
+            .with_label_values(&[&server.to_string()])
+            .set(players as i64);
+    }
+
+    pub fn media_blocked(&self, kind: &str) {
+        self.media_blocked.with_label_values(&[kind]).inc();
+    }
+
+    pub fn cooldown_queue(&self, len: usize) {
+        self.cooldown_queue.set(len as i64);
+    }
+
+    /// the metrics in prometheus' text format
+    fn render(&self) -> Result<Vec<u8>, Error> {
+        let mut buf = vec![];
+        TextEncoder::new().encode(&self.registry.gather(), &mut buf)?;
+        Ok(buf)
+    }
+}
+
+/// the bot's metrics, registered on first use
+pub fn metrics() -> &'static Metrics {
+    static METRICS: OnceLock<Metrics> = OnceLock::new();
+    METRICS.get_or_init(|| Metrics::new().expect("Could not register metrics"))
+}
+
+/// answers one request, with the metrics for `GET /metrics` and a 404 otherwise
+async fn respond(mut stream: TcpStream) -> Result<(), Error> {
+    let mut buf = [0u8; 1024];
+    let len = stream.read(&mut buf).await?;
+    let request = String::from_utf8_lossy(&buf[..len]);
+    let (status, body) = if request.starts_with("GET /metrics ") {
+        ("200 OK", metrics().render()?)
+    } else {
+        ("404 Not Found", b"Not found\n".to_vec())
+    };
+    let head = format!(
+        "HTTP/1.1 {}\r\nContent-Type: text/plain; version=0.0.4\r\nContent-Length: {}\r\nConnection: close\r\n\r\n",
+        status,
+        body.len()
+    );
+    stream.write_all(head.as_bytes()).await?;
+    stream.write_all(&body).await?;
+    stream.shutdown().await?;
+    Ok(())
+}
+
+/// serves the metrics on `addr` for prometheus to scrape, until shutdown
+pub fn spawn_metrics_server(addr: SocketAddr, shutdown: CancellationToken) -> JoinHandle<()> {
+    let thread = async move {
+        let listener = match TcpListener::bind(addr).await {
+            Ok(listener) => listener,
+            Err(e) => {
+                warn!("Could not serve metrics on {}: {:?}", addr, e);
+                return;
+            }
+        };
+        info!("Serving metrics on http://{}/metrics", addr);
+        loop {
+            let stream = tokio::select! {
+                accepted = listener.accept() => match accepted {
+                    Ok((stream, _)) => stream,
+                    Err(e) => {
+                        warn!("Could not accept a metrics request: {:?}", e);
+                        continue;
+                    }
+                },
+                _ = shutdown.cancelled() => break,
+            };
+            tokio::spawn(async move {
+                // a scraper that stops reading shouldn't hold the connection open
+                match tokio::time::timeout(Duration::from_secs(10), respond(stream)).await {
+                    Ok(Ok(())) => {}
+                    Ok(Err(e)) => warn!("Could not answer a metrics request: {:?}", e),
+                    Err(_) => warn!("A metrics request timed out"),
+                }
+            });
+        }
+    };
+    tokio::spawn(thread.instrument(info_span!("metrics_server")))
+}
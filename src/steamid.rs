@@ -1,19 +1,52 @@
+use futures::future::BoxFuture;
 use poise::serenity_prelude as serenity;
-use reqwest;
 use serenity::CreateEmbed;
+use std::collections::HashMap;
+use std::sync::atomic::{AtomicU64, Ordering};
+use std::time::{Duration, Instant};
+use tokio::sync::RwLock;
 
 use crate::Error;
+use regex::Regex;
 use serde::{Deserialize, Serialize};
 
-const BASEURL: &'static str = "https://steamidapi.uk/v2/";
+const BASEURL: &str = "https://steamidapi.uk/v2/";
+const STEAM_API_URL: &str = "https://api.steampowered.com/ISteamUser/";
+
+/// converts ids and names the cache doesn't have. steamidapi.uk, unless a test swaps it out
+pub type Fetcher =
+    Box<dyn Fn(String) -> BoxFuture<'static, Result<Vec<SteamIDProfile>, Error>> + Send + Sync>;
 
 pub struct SteamIDClient {
-    myid: u64,
-    api_key: String,
+    fetch: Fetcher,
+    /// steam web api key for profiles and bans. without it lookups only convert ids
+    steam_api_key: Option<String>,
     client: reqwest::Client,
+    /// how long a lookup is reused for
+    cache_ttl: Duration,
+    cache: RwLock<ProfileCache>,
+    /// accounts by steamid64, reused for cache_ttl too
+    accounts: RwLock<HashMap<String, (Instant, SteamAccount)>>,
+    hits: AtomicU64,
+    misses: AtomicU64,
+}
+
+/// converted profiles by steamid64, so every format of an id finds the same entry
+#[derive(Default)]
+struct ProfileCache {
+    profiles: HashMap<String, (Instant, SteamIDProfile)>,
+    /// the steamid64 that vanity names looked up before belong to
+    names: HashMap<String, String>,
+}
+
+/// how well the lookup cache is doing, for debugging
+pub struct CacheStats {
+    pub hits: u64,
+    pub misses: u64,
+    pub entries: usize,
 }
 
-#[derive(Deserialize, Serialize)]
+#[derive(Clone, Deserialize, Serialize)]
 pub struct SteamIDProfile {
     pub steamid64: String,
     pub steamid: String,
@@ -35,43 +68,507 @@ impl SteamIDProfile {
     }
 }
 
+/// an entry of GetPlayerBans
+#[derive(Clone, Deserialize)]
+pub struct PlayerBans {
+    #[serde(rename = "SteamId")]
+    pub steamid64: String,
+    #[serde(rename = "NumberOfVACBans")]
+    pub vac_bans: u32,
+    #[serde(rename = "NumberOfGameBans")]
+    pub game_bans: u32,
+    #[serde(rename = "DaysSinceLastBan")]
+    pub days_since_last_ban: u32,
+}
+
+/// an entry of GetPlayerSummaries
+#[derive(Clone, Deserialize)]
+struct PlayerSummary {
+    steamid: String,
+    personaname: String,
+    /// 3 is public, anything else hides the profile
+    communityvisibilitystate: u8,
+}
+
+/// what steam says about an account
+#[derive(Clone)]
+pub struct SteamAccount {
+    /// None if steam doesn't know the account
+    pub name: Option<String>,
+    pub private: bool,
+    pub bans: Option<PlayerBans>,
+}
+
+impl SteamAccount {
+    pub fn populate_embed<'a>(&self, embed: &'a mut CreateEmbed) -> &'a mut CreateEmbed {
+        let Some(name) = &self.name else {
+            embed.field("Profile", "no such steam account", false);
+            return embed;
+        };
+        embed.field(
+            "Profile",
+            if self.private {
+                format!("{} (private profile)", name)
+            } else {
+                name.clone()
+            },
+            false,
+        );
+        if let Some(bans) = &self.bans {
+            embed.field("VAC bans", bans.vac_bans, true);
+            embed.field("Game bans", bans.game_bans, true);
+            embed.field(
+                "Last ban",
+                if bans.vac_bans + bans.game_bans > 0 {
+                    format!("{} days ago", bans.days_since_last_ban)
+                } else {
+                    "never".to_owned()
+                },
+                true,
+            );
+        }
+        embed
+    }
+}
+
+/// the offset between an account's 32 bit id and its steamid64
+const STEAMID64_BASE: u64 = 76561197960265728;
+
+/// the steamid64 of a steamid64, STEAM_X:Y:Z or [U:1:Z] id, without asking anyone
+pub fn steamid64(id: &str) -> Option<u64> {
+    let id = id.trim();
+    if let Some(rest) = id.strip_prefix("STEAM_") {
+        let mut parts = rest.split(':').skip(1);
+        let y: u64 = parts.next()?.parse().ok()?;
+        let z: u64 = parts.next()?.parse().ok()?;
+        return (y <= 1).then_some(STEAMID64_BASE + z * 2 + y);
+    }
+    if let Some(rest) = id.strip_prefix("[U:1:") {
+        let z: u64 = rest.strip_suffix(']')?.parse().ok()?;
+        return Some(STEAMID64_BASE + z);
+    }
+    id.parse().ok().filter(|&n| n > STEAMID64_BASE)
+}
+
+/// the [U:1:Z] id of a steamid64, STEAM_X:Y:Z or [U:1:Z] id, without asking anyone
+pub fn steam3(id: &str) -> Option<String> {
+    steamid64(id).map(|n| format!("[U:1:{}]", n - STEAMID64_BASE))
+}
+
+/// the id as steam3 if it is a steamid, as given otherwise. status and links use steam3,
+/// so steamids are stored and compared this way
+pub fn normalize(id: &str) -> String {
+    steam3(id).unwrap_or_else(|| id.to_owned())
+}
+
+/// turns steamcommunity.com profile urls into the id or vanity name in them,
+/// so every format can be converted
+fn normalize_input(input: &str) -> String {
+    let re =
+        Regex::new(r#"(?:https?://)?steamcommunity\.com/(?:profiles|id)/([^/?#\s]+)/?"#).unwrap();
+    input
+        .split(',')
+        .map(|part| {
+            let part = part.trim();
+            re.captures(part)
+                .map_or(part.to_owned(), |caps| caps[1].to_owned())
+        })
+        .collect::<Vec<String>>()
+        .join(",")
+}
+
 impl SteamIDClient {
-    pub fn new(myid: u64, api_key: String) -> Self {
+    pub fn new(
+        myid: u64,
+        api_key: String,
+        steam_api_key: Option<String>,
+        cache_ttl: Duration,
+    ) -> Self {
+        let client = reqwest::Client::new();
+        let fetch: Fetcher = {
+            let client = client.clone();
+            Box::new(move |input| Box::pin(convert(client.clone(), myid, api_key.clone(), input)))
+        };
         Self {
-            myid,
-            api_key,
-            client: reqwest::Client::new(),
+            fetch,
+            steam_api_key: steam_api_key.filter(|k| !k.is_empty()),
+            client,
+            cache_ttl,
+            cache: RwLock::new(ProfileCache::default()),
+            accounts: RwLock::new(HashMap::new()),
+            hits: AtomicU64::new(0),
+            misses: AtomicU64::new(0),
         }
     }
 
-    pub async fn lookup(&self, input: &str) -> Result<Vec<SteamIDProfile>, Error> {
+    /// a client with the fetcher swapped in, for tests
+    #[cfg(test)]
+    fn with_fetcher(fetch: Fetcher, cache_ttl: Duration) -> Self {
+        Self {
+            fetch,
+            ..Self::new(0, String::new(), None, cache_ttl)
+        }
+    }
+
+    /// like lookup, but answers from memory for ids looked up less than cache_ttl ago,
+    /// in whatever format they were given. `refresh` skips the cache and stores the fresh answer
+    pub async fn lookup_cached(
+        &self,
+        input: &str,
+        refresh: bool,
+    ) -> Result<Vec<SteamIDProfile>, Error> {
+        let query = normalize_input(input);
+        let parts: Vec<&str> = query
+            .split(',')
+            .map(str::trim)
+            .filter(|p| !p.is_empty())
+            .collect();
+        if !refresh {
+            if let Some(profiles) = self.cached(&parts).await {
+                self.hits.fetch_add(1, Ordering::Relaxed);
+                return Ok(profiles);
+            }
+        }
+        self.misses.fetch_add(1, Ordering::Relaxed);
+        let profiles = self.lookup(&query).await?;
+
+        let mut cache = self.cache.write().await;
+        // drop stale entries so the cache can't grow forever
+        cache
+            .profiles
+            .retain(|_, (at, _)| at.elapsed() < self.cache_ttl);
+        let ProfileCache {
+            profiles: kept,
+            names,
+        } = &mut *cache;
+        names.retain(|_, id| kept.contains_key(id));
+        for profile in &profiles {
+            kept.insert(profile.steamid64.clone(), (Instant::now(), profile.clone()));
+        }
+        // a lone vanity name can be tied to what it resolved to
+        if let ([part], [profile]) = (&parts[..], &profiles[..]) {
+            if steamid64(part).is_none() {
+                names.insert(part.to_lowercase(), profile.steamid64.clone());
+            }
+        }
+        Ok(profiles)
+    }
+
+    /// the fresh cached profile of every part, None if any of them has to be fetched
+    async fn cached(&self, parts: &[&str]) -> Option<Vec<SteamIDProfile>> {
+        if parts.is_empty() {
+            return None;
+        }
+        let cache = self.cache.read().await;
+        parts
+            .iter()
+            .map(|part| {
+                let id = match steamid64(part) {
+                    Some(id) => id.to_string(),
+                    None => cache.names.get(&part.to_lowercase())?.clone(),
+                };
+                let (at, profile) = cache.profiles.get(&id)?;
+                (at.elapsed() < self.cache_ttl).then(|| profile.clone())
+            })
+            .collect()
+    }
+
+    /// profile and ban info for each steamid64 steam knows about, from the cache where possible.
+    /// empty without a steam api key
+    pub async fn accounts_cached(
+        &self,
+        steamids64: &[&str],
+        refresh: bool,
+    ) -> Result<HashMap<String, SteamAccount>, Error> {
+        let Some(key) = &self.steam_api_key else {
+            return Ok(HashMap::new());
+        };
+        let mut found = HashMap::new();
+        let mut missing = vec![];
+        {
+            let accounts = self.accounts.read().await;
+            for id in steamids64 {
+                match accounts.get(*id) {
+                    Some((at, account)) if !refresh && at.elapsed() < self.cache_ttl => {
+                        found.insert(id.to_string(), account.clone());
+                    }
+                    _ => missing.push(*id),
+                }
+            }
+        }
+        if missing.is_empty() {
+            return Ok(found);
+        }
+
+        #[derive(Deserialize)]
+        struct Players<T> {
+            players: Vec<T>,
+        }
+        #[derive(Deserialize)]
+        struct Summaries {
+            response: Players<PlayerSummary>,
+        }
+        let ids = missing.join(",");
+        let summaries: Summaries = self
+            .client
+            .get(format!("{}GetPlayerSummaries/v2/", STEAM_API_URL))
+            .query(&[("key", key.as_str()), ("steamids", &ids)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+        let bans: Players<PlayerBans> = self
+            .client
+            .get(format!("{}GetPlayerBans/v1/", STEAM_API_URL))
+            .query(&[("key", key.as_str()), ("steamids", &ids)])
+            .send()
+            .await?
+            .error_for_status()?
+            .json()
+            .await?;
+
+        let mut accounts = self.accounts.write().await;
+        accounts.retain(|_, (at, _)| at.elapsed() < self.cache_ttl);
+        for id in missing {
+            let summary = summaries.response.players.iter().find(|p| p.steamid == id);
+            let account = SteamAccount {
+                name: summary.map(|s| s.personaname.clone()),
+                private: summary.is_some_and(|s| s.communityvisibilitystate != 3),
+                bans: bans.players.iter().find(|b| b.steamid64 == id).cloned(),
+            };
+            accounts.insert(id.to_owned(), (Instant::now(), account.clone()));
+            found.insert(id.to_owned(), account);
+        }
+        Ok(found)
+    }
+
+    /// the ids of the steam groups the account is in, as GetUserGroupList gives them.
+    /// None without a steam api key, or if the profile is private
+    pub async fn group_ids(&self, steamid64: u64) -> Result<Option<Vec<u64>>, Error> {
+        let Some(key) = &self.steam_api_key else {
+            return Ok(None);
+        };
+        #[derive(Deserialize)]
+        struct Group {
+            gid: String,
+        }
+        #[derive(Deserialize)]
+        struct Groups {
+            success: bool,
+            #[serde(default)]
+            groups: Vec<Group>,
+        }
+        #[derive(Deserialize)]
+        struct Response {
+            response: Groups,
+        }
         let resp = self
             .client
-            .get(format!("{}{}", BASEURL, "convert.php"))
-            .query(&[
-                ("myid", &self.myid.to_string()),
-                ("apikey", &self.api_key),
-                ("input", &input.to_owned()),
-            ])
+            .get(format!("{}GetUserGroupList/v1/", STEAM_API_URL))
+            .query(&[("key", key.as_str()), ("steamid", &steamid64.to_string())])
             .send()
             .await?;
-        // check for errors
-        let body = resp.text().await?;
-        let response: serde_json::Value = serde_json::from_str(&body)?;
-        let response = if let Some(errormsg) = response.get("error").and_then(|e| e.get("errormsg"))
-        {
-            Err(errormsg.to_string())?
-        } else if let Some(converted) = response.get("converted") {
-            if converted.is_object() {
-                Ok(vec![serde_json::from_value(converted.clone())?])
-            } else if converted.is_array() {
-                Ok(serde_json::from_value(converted.clone())?)
-            } else {
-                Err("Invalid response from SteamID API".to_string())?
-            }
+        // private profiles come back as forbidden
+        if resp.status() == reqwest::StatusCode::FORBIDDEN {
+            return Ok(None);
+        }
+        let response: Response = resp.error_for_status()?.json().await?;
+        if !response.response.success {
+            return Ok(None);
+        }
+        Ok(Some(
+            response
+                .response
+                .groups
+                .iter()
+                .filter_map(|g| g.gid.parse().ok())
+                .collect(),
+        ))
+    }
+
+    pub async fn cache_stats(&self) -> CacheStats {
+        CacheStats {
+            hits: self.hits.load(Ordering::Relaxed),
+            misses: self.misses.load(Ordering::Relaxed),
+            entries: self.cache.read().await.profiles.len(),
+        }
+    }
+
+    pub async fn lookup(&self, input: &str) -> Result<Vec<SteamIDProfile>, Error> {
+        (self.fetch)(input.to_owned()).await
+    }
+}
+
+/// asks steamidapi.uk to convert the ids or names
+async fn convert(
+    client: reqwest::Client,
+    myid: u64,
+    api_key: String,
+    input: String,
+) -> Result<Vec<SteamIDProfile>, Error> {
+    let resp = client
+        .get(format!("{}{}", BASEURL, "convert.php"))
+        .query(&[
+            ("myid", &myid.to_string()),
+            ("apikey", &api_key),
+            ("input", &input),
+        ])
+        .send()
+        .await?;
+    // check for errors
+    let body = resp.text().await?;
+    let response: serde_json::Value = serde_json::from_str(&body)?;
+    let response = if let Some(errormsg) = response.get("error").and_then(|e| e.get("errormsg")) {
+        Err(errormsg.to_string())?
+    } else if let Some(converted) = response.get("converted") {
+        if converted.is_object() {
+            Ok(vec![serde_json::from_value(converted.clone())?])
+        } else if converted.is_array() {
+            Ok(serde_json::from_value(converted.clone())?)
         } else {
             Err("Invalid response from SteamID API".to_string())?
-        };
-        response
+        }
+    } else {
+        Err("Invalid response from SteamID API".to_string())?
+    };
+    response
+}
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+    use std::sync::atomic::AtomicUsize;
+    use std::sync::Arc;
+
+    /// answers like steamidapi.uk would for ids, and knows one vanity name.
+    /// counts how often it's asked
+    fn fake_client(ttl: Duration) -> (SteamIDClient, Arc<AtomicUsize>) {
+        let calls = Arc::new(AtomicUsize::new(0));
+        let counter = calls.clone();
+        let fetch: Fetcher = Box::new(move |input| {
+            counter.fetch_add(1, Ordering::SeqCst);
+            Box::pin(async move {
+                input
+                    .split(',')
+                    .map(|part| {
+                        let id = match part {
+                            "gaben" => 76561197960287930,
+                            part => steamid64(part).ok_or("no such account")?,
+                        };
+                        Ok(SteamIDProfile {
+                            steamid64: id.to_string(),
+                            steamid: String::new(),
+                            steam3: format!("[U:1:{}]", id - STEAMID64_BASE),
+                            steamidurl: String::new(),
+                            inviteurl: None,
+                        })
+                    })
+                    .collect()
+            })
+        });
+        (SteamIDClient::with_fetcher(fetch, ttl), calls)
+    }
+
+    #[test]
+    fn every_format_gives_the_same_steamid64() {
+        let id = Some(76561197960287930);
+        assert_eq!(steamid64("76561197960287930"), id);
+        assert_eq!(steamid64("[U:1:22202]"), id);
+        assert_eq!(steamid64("STEAM_0:0:11101"), id);
+        assert_eq!(steamid64("STEAM_1:0:11101"), id);
+        assert_eq!(steamid64("STEAM_0:1:11101"), Some(76561197960287931));
+        assert_eq!(steamid64("gaben"), None);
+        assert_eq!(steamid64("12345"), None);
+        assert_eq!(steamid64("STEAM_0:2:11101"), None);
+    }
+
+    #[test]
+    fn every_format_normalizes_to_steam3() {
+        for id in [
+            "76561197960287930",
+            "[U:1:22202]",
+            "STEAM_0:0:11101",
+            " STEAM_1:0:11101",
+        ] {
+            assert_eq!(normalize(id), "[U:1:22202]");
+        }
+        assert_eq!(normalize("gaben"), "gaben");
+    }
+
+    #[tokio::test]
+    async fn second_lookup_within_ttl_is_cached() {
+        let (client, calls) = fake_client(Duration::from_secs(60));
+        client.lookup_cached("[U:1:22202]", false).await.unwrap();
+        let again = client.lookup_cached("[U:1:22202]", false).await.unwrap();
+        assert_eq!(again[0].steamid64, "76561197960287930");
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+        let stats = client.cache_stats().await;
+        assert_eq!((stats.hits, stats.misses), (1, 1));
+    }
+
+    #[tokio::test]
+    async fn other_formats_of_the_same_account_are_cached() {
+        let (client, calls) = fake_client(Duration::from_secs(60));
+        client.lookup_cached("[U:1:22202]", false).await.unwrap();
+        client
+            .lookup_cached("STEAM_0:0:11101", false)
+            .await
+            .unwrap();
+        client
+            .lookup_cached(
+                "https://steamcommunity.com/profiles/76561197960287930",
+                false,
+            )
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn vanity_names_are_cached() {
+        let (client, calls) = fake_client(Duration::from_secs(60));
+        client.lookup_cached("gaben", false).await.unwrap();
+        client.lookup_cached("GabeN", false).await.unwrap();
+        client.lookup_cached("[U:1:22202]", false).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 1);
+    }
+
+    #[tokio::test]
+    async fn lists_are_fetched_unless_every_id_is_cached() {
+        let (client, calls) = fake_client(Duration::from_secs(60));
+        client.lookup_cached("[U:1:22202]", false).await.unwrap();
+        let both = client
+            .lookup_cached("[U:1:22202],[U:1:22203]", false)
+            .await
+            .unwrap();
+        assert_eq!(both.len(), 2);
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+        client
+            .lookup_cached("[U:1:22203], STEAM_0:0:11101", false)
+            .await
+            .unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn refresh_and_expiry_fetch_again() {
+        let (client, calls) = fake_client(Duration::from_secs(60));
+        client.lookup_cached("[U:1:22202]", false).await.unwrap();
+        client.lookup_cached("[U:1:22202]", true).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+
+        let (client, calls) = fake_client(Duration::ZERO);
+        client.lookup_cached("[U:1:22202]", false).await.unwrap();
+        client.lookup_cached("[U:1:22202]", false).await.unwrap();
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
+    }
+
+    #[tokio::test]
+    async fn failed_lookups_are_not_cached() {
+        let (client, calls) = fake_client(Duration::from_secs(60));
+        assert!(client.lookup_cached("nobody", false).await.is_err());
+        assert!(client.lookup_cached("nobody", false).await.is_err());
+        assert_eq!(calls.load(Ordering::SeqCst), 2);
     }
 }
@@ -1,19 +1,124 @@
 use std::{
     net::SocketAddr,
-    time::{self, Duration},
+    time::{self, Duration, Instant},
 };
 
-use crate::{logs::safe_strip, Error, Server};
+use crate::{logs::safe_strip, metrics::metrics, Error, Server};
 
 use rcon::Connection;
 use regex::Regex;
 use tokio::net::TcpStream;
+use tracing::warn;
 
 #[derive(Debug, Clone)]
 pub struct Player {
     pub name: String,
     pub connected: time::Duration,
     pub id: String,
+    pub ping: Option<u32>,
+}
+
+/// one row of the player table printed by `status`
+#[derive(Debug, Clone)]
+pub struct PlayerRow {
+    pub userid: u32,
+    pub name: String,
+    /// None for bots, including SourceTV
+    pub steamid: Option<String>,
+    pub connected: Option<Duration>,
+    pub ping: Option<u32>,
+    /// e.g. active or spawning
+    pub state: String,
+}
+
+impl PlayerRow {
+    pub fn is_bot(&self) -> bool {
+        self.steamid.is_none()
+    }
+}
+
+/// parses `mm:ss` or `hh:mm:ss` from the connected column
+fn parse_connected(s: &str) -> Option<Duration> {
+    let parts: Vec<u64> = s
+        .split(':')
+        .map(|p| p.parse().ok())
+        .collect::<Option<_>>()?;
+    let secs = match parts[..] {
+        [m, s] => m * 60 + s,
+        [h, m, s] => h * 3600 + m * 60 + s,
+        _ => return None,
+    };
+    Some(Duration::from_secs(secs))
+}
+
+/// parses the player table out of a `status` response. bots only have a state after their name
+pub fn parse_status(status_msg: &str) -> Vec<PlayerRow> {
+    // names are quoted but may contain spaces and quotes themselves, so the name runs up to the last quote before the id
+    let re = Regex::new(r#"^#\s*(\d+)\s+"(.*)"\s+(\[U:\d+:\d+\]|BOT)\s*(.*)$"#).unwrap();
+    status_msg
+        .lines()
+        .filter_map(|line| {
+            let caps = re.captures(line.trim_end())?;
+            let steamid = (&caps[3] != "BOT").then(|| caps[3].to_owned());
+            let rest: Vec<&str> = caps[4].split_whitespace().collect();
+            // humans have connected, ping, loss, state and address
+            let (connected, ping, state) = match (&steamid, &rest[..]) {
+                (Some(_), [connected, ping, _, state, ..]) => {
+                    (parse_connected(connected), ping.parse().ok(), *state)
+                }
+                _ => (None, None, rest.first().copied().unwrap_or_default()),
+            };
+            Some(PlayerRow {
+                userid: caps[1].parse().ok()?,
+                name: caps[2].to_owned(),
+                steamid,
+                connected,
+                ping,
+                state: state.to_owned(),
+            })
+        })
+        .collect()
+}
+
+/// parsed output of tv_status
+#[derive(Debug, Clone)]
+pub struct TvStatus {
+    pub active: bool,
+    pub spectators: Option<u32>,
+    /// demo file currently being recorded
+    pub recording: Option<String>,
+}
+
+/// one row of sm plugins list
+#[derive(Debug, Clone)]
+pub struct SmPlugin {
+    pub index: u32,
+    /// plugin name, or the file name if it failed to load
+    pub name: String,
+    pub version: Option<String>,
+    pub author: Option<String>,
+    /// Running, Failed, Error, Paused, Disabled...
+    pub status: String,
+}
+
+impl SmPlugin {
+    /// whether the plugin is broken rather than just turned off
+    pub fn failed(&self) -> bool {
+        matches!(self.status.as_str(), "Failed" | "Error")
+    }
+}
+
+/// a quick health snapshot from `status` and `stats`
+#[derive(Debug, Clone)]
+pub struct ServerInfo {
+    pub hostname: String,
+    pub map: String,
+    /// humans only
+    pub players: usize,
+    pub max_players: Option<u32>,
+    /// server frames per second, which is the tickrate unless the server is struggling
+    pub tickrate: Option<f32>,
+    pub uptime: Option<Duration>,
 }
 
 #[derive(Debug, Clone)]
@@ -23,7 +128,7 @@ pub struct GameState {
     pub map: String,
 }
 
-fn hhmmss(duration: &Duration) -> String {
+pub fn hhmmss(duration: &Duration) -> String {
     let secs = duration.as_secs();
     let hours = secs / 3600;
     let minutes = (secs % 3600) / 60;
@@ -73,39 +178,64 @@ impl GameState {
     }
 }
 
+/// the server didn't answer an rcon command in time
+#[derive(Debug)]
+pub struct RconTimeout;
+
+impl std::fmt::Display for RconTimeout {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        write!(f, "timed out")
+    }
+}
+
+impl std::error::Error for RconTimeout {}
+
 pub struct RconController {
     pub connection: Connection<TcpStream>,
     pub address: SocketAddr,
     pub password: String,
+    /// how long a command may take before the server counts as unreachable
+    pub timeout: Duration,
+    /// a reply was given up on, so the connection must be replaced before it's used again
+    broken: bool,
 }
 
 impl RconController {
     /// initialize the controller
-    pub async fn connect(address: SocketAddr, password: &str) -> Result<Self, Error> {
-        let connection = <Connection<TcpStream>>::builder()
-            .connect(address, password)
-            .await?;
+    pub async fn connect(
+        address: SocketAddr,
+        password: &str,
+        timeout: Duration,
+    ) -> Result<Self, Error> {
+        let connect = <Connection<TcpStream>>::builder().connect(address, password);
+        let connection = tokio::time::timeout(timeout, connect)
+            .await
+            .map_err(|_| RconTimeout)??;
 
         let rc = RconController {
             connection,
             address,
             password: password.to_owned(),
+            timeout,
+            broken: false,
         };
         Ok(rc)
     }
 
     /// reconnect to tf2 on failure
     pub async fn reconnect(&mut self) -> Result<(), Error> {
-        self.connection = <Connection<TcpStream>>::builder()
-            .connect(&self.address, &self.password)
-            .await?;
+        let connect = <Connection<TcpStream>>::builder().connect(&self.address, &self.password);
+        self.connection = tokio::time::timeout(self.timeout, connect)
+            .await
+            .map_err(|_| RconTimeout)??;
+        self.broken = false;
 
         Ok(())
     }
 
     /// fetch the value of a convar
     pub async fn convar(&mut self, convar: &str) -> Result<String, Error> {
-        let result = self.run(convar).await?;
+        let result = self.query(convar).await?;
         let re = Regex::new(r#"^".*" = "(.*)""#).unwrap();
         if let Some(caps) = re.captures(&result) {
             Ok(caps[1].to_owned())
@@ -114,21 +244,92 @@ impl RconController {
         }
     }
 
-    /// run an rcon command and return the output
+    /// fetch the value of a convar along with its default, if the server reports one
+    pub async fn convar_with_default(
+        &mut self,
+        convar: &str,
+    ) -> Result<(String, Option<String>), Error> {
+        let result = self.query(convar).await?;
+        // "sv_gravity" = "800" ( def. "800" ) notify replicated
+        let re = Regex::new(r#"^".*" = "(.*?)"(?: \( def\. "(.*?)" \))?"#).unwrap();
+        if let Some(caps) = re.captures(&result) {
+            Ok((
+                caps[1].to_owned(),
+                caps.get(2).map(|d| d.as_str().to_owned()),
+            ))
+        } else {
+            Err("Could not parse convar result".into())
+        }
+    }
+
+    /// run an rcon command and return the output.
+    /// if the connection broke it reconnects, but doesn't send the command again:
+    /// the server may have run it before the reply was lost
+    #[tracing::instrument(level = "debug", skip(self), fields(server = %self.address))]
     pub async fn run(&mut self, cmd: &str) -> Result<String, Error> {
-        match self.connection.cmd(cmd).await {
-            Ok(msg) => Ok(msg),
-            Err(e) => {
-                self.reconnect().await?;
-                Err(format!("Failed to connect, retrying. Error {}", e))?
+        self.timed(cmd, false).await
+    }
+
+    /// like run, for commands that only read state, like status or a convar.
+    /// these are safe to send twice, so they're retried once on a fresh connection
+    #[tracing::instrument(level = "debug", skip(self), fields(server = %self.address))]
+    pub async fn query(&mut self, cmd: &str) -> Result<String, Error> {
+        self.timed(cmd, true).await
+    }
+
+    async fn timed(&mut self, cmd: &str, retry: bool) -> Result<String, Error> {
+        let started = Instant::now();
+        let result = self.try_run(cmd, retry).await;
+        metrics().rcon(self.address, started.elapsed(), result.is_ok());
+        result
+    }
+
+    async fn try_run(&mut self, cmd: &str, retry: bool) -> Result<String, Error> {
+        if self.broken {
+            self.reconnect()
+                .await
+                .map_err(|e| format!("Server unreachable: {}", e))?;
+        }
+        match self.send(cmd).await {
+            Err(e) if !e.is::<RconTimeout>() => {
+                warn!("RCON to {} failed, reconnecting: {}", self.address, e);
+                self.reconnect()
+                    .await
+                    .map_err(|re| format!("Server unreachable: {} ({})", e, re))?;
+                if !retry {
+                    return Err(
+                        format!("Connection lost, the command may not have run: {}", e).into(),
+                    );
+                }
+                Ok(self
+                    .send(cmd)
+                    .await
+                    .map_err(|e| format!("Server unreachable: {}", e))?)
+            }
+            result => result,
+        }
+    }
+
+    /// sends the command once, giving up after the timeout. a reply that comes in late would
+    /// be read as the answer to the next command, so the connection is replaced when that happens
+    async fn send(&mut self, cmd: &str) -> Result<String, Error> {
+        match tokio::time::timeout(self.timeout, self.connection.cmd(cmd)).await {
+            Ok(result) => Ok(result?),
+            Err(_) => {
+                self.broken = true;
+                warn!("RCON to {} timed out, reconnecting", self.address);
+                if let Err(e) = self.reconnect().await {
+                    warn!("Could not reconnect to {}: {}", self.address, e);
+                }
+                Err(RconTimeout.into())
             }
         }
     }
 
     /// fetch the results of the status command
     pub async fn status(&mut self) -> Result<GameState, Error> {
-        let status_msg = self.run("status").await?;
-        let max_player_msg = self.run("sv_visiblemaxplayers").await?;
+        let status_msg = self.query("status").await?;
+        let max_player_msg = self.query("sv_visiblemaxplayers").await?;
         let re = Regex::new(r#""sv_visiblemaxplayers" = "(-?\d+)""#).unwrap();
         let Some(max_players) = re
             .captures(&max_player_msg)
@@ -151,33 +352,123 @@ impl RconController {
         Ok(gs)
     }
 
-    fn parse_player_list(status_msg: &str) -> Result<Vec<Player>, Error> {
-        let re = Regex::new(r#"\d+\s+"(.+)"\s+(\[U:.*\])\s+(\d+):(\d+)(?::(\d+))?"#).unwrap();
-        let mut players = Vec::new();
-        for caps in re.captures_iter(status_msg) {
-            let id = caps[2].to_owned();
-            let h = caps[3].parse::<u64>()?;
-            let m = caps[4].parse::<u64>()?;
-            let s: Option<u64> = caps.get(5).map(|s| {
-                s.as_str()
-                    .parse::<u64>()
-                    .expect("Could not parse status message seconds connected.")
-            });
-
-            let connected = time::Duration::from_secs(if let Some(s) = s {
-                h * 3600 + m * 60 + s
-            } else {
-                h * 60 + m
-            });
+    /// fetch the hostname, map, player count, tickrate and uptime
+    pub async fn info(&mut self) -> Result<ServerInfo, Error> {
+        let status_msg = self.query("status").await?;
+        let stats_msg = self.query("stats").await?;
+        let hostname = Regex::new(r#"(?m)^hostname\s*:\s*(.+?)\s*$"#).unwrap();
+        let max_players = Regex::new(r#"(?m)^players\s*:.*\((\d+) max\)"#).unwrap();
+        let stat = |column: &str| Self::parse_stat(&stats_msg, column);
+        Ok(ServerInfo {
+            hostname: hostname
+                .captures(&status_msg)
+                .map_or_else(String::new, |caps| caps[1].to_owned()),
+            map: Self::parse_current_map(&status_msg)?,
+            players: Self::parse_player_list(&status_msg)?.len(),
+            max_players: max_players
+                .captures(&status_msg)
+                .and_then(|caps| caps[1].parse().ok()),
+            tickrate: stat("FPS").and_then(|fps| fps.parse().ok()),
+            // stats gives uptime in minutes
+            uptime: stat("Uptime")
+                .and_then(|m| m.parse::<u64>().ok())
+                .map(|m| Duration::from_secs(m * 60)),
+        })
+    }
 
-            players.push(Player {
-                name: caps[1].to_owned(),
-                id,
-                connected,
-            });
+    /// a value from the `stats` table, found by its column header
+    fn parse_stat<'a>(stats_msg: &'a str, column: &str) -> Option<&'a str> {
+        // CPU    In_(KB/s)  Out_(KB/s)  Uptime  Map_changes  FPS      Players  Connects
+        // 0.00   0.00       0.00        1234    3            66.67    0        0
+        let mut lines = stats_msg.lines();
+        let header = lines.find(|l| l.split_whitespace().any(|h| h == column))?;
+        let index = header.split_whitespace().position(|h| h == column)?;
+        lines.next()?.split_whitespace().nth(index)
+    }
+
+    /// fetch the sourcetv state, including the demo being recorded if any
+    pub async fn tv_status(&mut self) -> Result<TvStatus, Error> {
+        let msg = self.query("tv_status").await?;
+        Ok(Self::parse_tv_status(&msg))
+    }
+
+    /// fetch the maps installed on the server, named as changelevel takes them
+    pub async fn maps(&mut self) -> Result<Vec<String>, Error> {
+        let msg = self.query("maps *").await?;
+        Ok(Self::parse_map_list(&msg))
+    }
+
+    /// fetch the map currently being played
+    pub async fn current_map(&mut self) -> Result<String, Error> {
+        let status_msg = self.query("status").await?;
+        Self::parse_current_map(&status_msg)
+    }
+
+    fn parse_map_list(msg: &str) -> Vec<String> {
+        // PENDING:   (fs) ctf_2fort.bsp
+        let mut maps: Vec<String> = msg
+            .lines()
+            .filter_map(|line| line.split_whitespace().last()?.strip_suffix(".bsp"))
+            .map(str::to_owned)
+            .collect();
+        maps.sort();
+        maps.dedup();
+        maps
+    }
+
+    /// fetch the loaded sourcemod plugins
+    pub async fn plugins(&mut self) -> Result<Vec<SmPlugin>, Error> {
+        let msg = self.query("sm plugins list").await?;
+        Ok(Self::parse_plugin_list(&msg))
+    }
+
+    fn parse_plugin_list(msg: &str) -> Vec<SmPlugin> {
+        // 01 "Basic Chat" (1.11.0.6911) by AlliedModders LLC
+        // 02 <Failed> "broken.smx"
+        let re = Regex::new(
+            r#"(?m)^\s*(\d+)\s+(?:<(\w+)>\s+)?"(.+?)"(?:\s+\((.*?)\))?(?:\s+by\s+(.+?))?\s*$"#,
+        )
+        .unwrap();
+        re.captures_iter(msg)
+            .filter_map(|caps| {
+                Some(SmPlugin {
+                    index: caps[1].parse().ok()?,
+                    status: caps
+                        .get(2)
+                        .map_or("Running".to_owned(), |s| s.as_str().to_owned()),
+                    name: caps[3].to_owned(),
+                    version: caps.get(4).map(|v| v.as_str().to_owned()),
+                    author: caps.get(5).map(|a| a.as_str().to_owned()),
+                })
+            })
+            .collect()
+    }
+
+    fn parse_tv_status(msg: &str) -> TvStatus {
+        let spectators = Regex::new(r#"Local Slots \d+, Spectators (\d+)"#).unwrap();
+        let recording = Regex::new(r#"Recording to "(.+?)""#).unwrap();
+        TvStatus {
+            active: msg.contains("SourceTV online") || msg.contains("SourceTV Relay"),
+            spectators: spectators
+                .captures(msg)
+                .and_then(|caps| caps[1].parse().ok()),
+            recording: recording.captures(msg).map(|caps| caps[1].to_owned()),
         }
+    }
 
-        Ok(players)
+    /// the human players in a `status` response
+    fn parse_player_list(status_msg: &str) -> Result<Vec<Player>, Error> {
+        Ok(parse_status(status_msg)
+            .into_iter()
+            .filter_map(|row| {
+                Some(Player {
+                    id: row.steamid?,
+                    name: row.name,
+                    connected: row.connected.unwrap_or_default(),
+                    ping: row.ping,
+                })
+            })
+            .collect())
     }
 
     fn parse_current_map(status_msg: &str) -> Result<String, Error> {
@@ -189,3 +480,59 @@ impl RconController {
         }
     }
 }
+
+#[cfg(test)]
+mod tests {
+    use super::*;
+
+    const PLUGINS: &str = r#"[SM] Listing 5 plugins:
+  01 "Admin File Reader" (1.11.0.6911) by AlliedModders LLC
+  02 "Basic Chat" (1.11.0.6911) by AlliedModders LLC
+  03 <Failed> "broken.smx"
+  04 <Disabled> "Fun Commands" (1.11.0.6911) by AlliedModders LLC
+  05 "No Frills"
+"#;
+
+    #[test]
+    fn parses_running_plugins() {
+        let plugins = RconController::parse_plugin_list(PLUGINS);
+        assert_eq!(plugins.len(), 5);
+        let chat = &plugins[1];
+        assert_eq!(chat.index, 2);
+        assert_eq!(chat.name, "Basic Chat");
+        assert_eq!(chat.version.as_deref(), Some("1.11.0.6911"));
+        assert_eq!(chat.author.as_deref(), Some("AlliedModders LLC"));
+        assert_eq!(chat.status, "Running");
+        assert!(!chat.failed());
+    }
+
+    #[test]
+    fn parses_failed_and_disabled_plugins() {
+        let plugins = RconController::parse_plugin_list(PLUGINS);
+        let broken = &plugins[2];
+        assert_eq!(broken.name, "broken.smx");
+        assert_eq!(broken.status, "Failed");
+        assert_eq!(broken.version, None);
+        assert!(broken.failed());
+
+        let fun = &plugins[3];
+        assert_eq!(fun.status, "Disabled");
+        assert_eq!(fun.name, "Fun Commands");
+        assert!(!fun.failed());
+    }
+
+    #[test]
+    fn version_and_author_are_optional() {
+        let plugins = RconController::parse_plugin_list(PLUGINS);
+        let plain = &plugins[4];
+        assert_eq!(plain.name, "No Frills");
+        assert_eq!(plain.version, None);
+        assert_eq!(plain.author, None);
+    }
+
+    #[test]
+    fn ignores_other_output() {
+        assert!(RconController::parse_plugin_list("[SM] No plugins loaded.\n").is_empty());
+        assert!(RconController::parse_plugin_list("").is_empty());
+    }
+}